@@ -0,0 +1,265 @@
+//!
+//! Pluggable backends for the high-level API.
+//!
+//! `Board`, `Instrument` and `InstrumentHandle` do not call the library directly. They go through
+//! an implementation of the [`Backend`] trait, which covers the 'traditional' ib* calls and the
+//! 'multidevice' IEEE 488.2 calls. The default backend calls into the installed GPIB library,
+//! but any other implementation can be used instead, either explicitly with `Board::with_backend`
+//! or process-wide with [`set_default_backend`].
+//!
+#![allow(non_snake_case)]
+
+#[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
+pub mod native;
+pub mod sim;
+
+use crate::error::GpibError;
+use crate::lowlevel::utility::Addr4882;
+use crate::status::{IbStatus, StatusByte};
+use crate::types::{
+    IbEosMode, IbLineStatus, IbOnline, IbOption, IbSendEOI, IbTimeout, PrimaryAddress,
+    SecondaryAddress,
+};
+use std::os::raw::{c_int, c_short};
+use std::sync::{Arc, RwLock};
+
+/// Operations used by the high-level API.
+///
+/// The methods mirror the functions of [`crate::lowlevel::traditional`] and
+/// [`crate::lowlevel::multidevice`], with the same arguments and return values. Functions which
+/// wait for an event (`ibwait`, `WaitSRQ`) block the calling thread, except `ibwait` with an empty
+/// status mask, which returns the current status at once. The asynchronous API relies on it to
+/// poll for events from the I/O thread of each board.
+pub trait Backend: Send + Sync {
+    /// ibask -- query configuration (board or device)
+    fn ibask(&self, ud: c_int, option: IbOption) -> Result<c_int, GpibError>;
+
+    /// ibclr -- clear device (device)
+    fn ibclr(&self, ud: c_int) -> Result<(), GpibError>;
+
+    /// ibconfig -- change configuration (board or device)
+    fn ibconfig(&self, ud: c_int, option: IbOption, setting: c_int) -> Result<(), GpibError>;
+
+    /// open a device (device)
+    fn ibdev(
+        &self,
+        board_index: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+        timeout: IbTimeout,
+        send_eoi: IbSendEOI,
+        eos: IbEosMode,
+    ) -> Result<c_int, GpibError>;
+
+    /// ibeos -- set end-of-string mode (board or device)
+    fn ibeos(&self, ud: c_int, eosmod: IbEosMode) -> Result<(), GpibError>;
+
+    /// ibeot -- assert EOI with last data byte (board or device)
+    fn ibeot(&self, ud: c_int, send_eoi: IbSendEOI) -> Result<(), GpibError>;
+
+    /// iblines -- monitor bus lines (board)
+    fn iblines(&self, ud: c_int) -> Result<IbLineStatus, GpibError>;
+
+    /// ibln -- check if listener is present (board or device)
+    fn ibln(
+        &self,
+        ud: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+    ) -> Result<bool, GpibError>;
+
+    /// ibloc -- go to local mode (board or device)
+    fn ibloc(&self, ud: c_int) -> Result<(), GpibError>;
+
+    /// ibonl -- close or reinitialize descriptor (board or device)
+    fn ibonl(&self, ud: c_int, online: IbOnline) -> Result<(), GpibError>;
+
+    /// read data bytes (board or device)
+    fn ibrd(&self, ud: c_int, buffer: &mut [u8]) -> Result<(IbStatus, usize), GpibError>;
+
+    /// read data bytes asynchronously (board or device)
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must remain valid until the transfer is resynchronized with `ibwait` or aborted
+    /// with `ibstop`.
+    unsafe fn ibrda(&self, ud: c_int, buffer: &mut [u8]) -> Result<(), GpibError>;
+
+    /// ibrsp --  read status byte / serial poll (device)
+    fn ibrsp(&self, ud: c_int) -> Result<StatusByte, GpibError>;
+
+    /// ibsic -- perform interface clear (board)
+    fn ibsic(&self, ud: c_int) -> Result<(), GpibError>;
+
+    /// ibsre -- set remote enable (board)
+    fn ibsre(&self, ud: c_int, enable: c_int) -> Result<(), GpibError>;
+
+    /// ibstop -- abort asynchronous i/o operation (board or device)
+    fn ibstop(&self, ud: c_int) -> Result<(), GpibError>;
+
+    /// ibtmo -- adjust io timeout (board or device)
+    fn ibtmo(&self, ud: c_int, timeout: IbTimeout) -> Result<(), GpibError>;
+
+    /// ibtrg -- trigger device (device)
+    fn ibtrg(&self, ud: c_int) -> Result<(), GpibError>;
+
+    /// wait for event (board or device), blocking the current thread
+    fn ibwait(&self, ud: c_int, status_mask: IbStatus) -> Result<(IbStatus, usize), GpibError>;
+
+    /// ibwrt -- write data bytes (board or device)
+    fn ibwrt(&self, ud: c_int, data: &[u8]) -> Result<usize, GpibError>;
+
+    /// write data bytes asynchronously (board or device)
+    ///
+    /// # Safety
+    ///
+    /// `data` must remain valid until the transfer is resynchronized with `ibwait` or aborted
+    /// with `ibstop`.
+    unsafe fn ibwrta(&self, ud: c_int, data: &[u8]) -> Result<(), GpibError>;
+
+    /// find devices
+    fn FindLstn(&self, board: c_int, padList: Vec<Addr4882>) -> Result<Vec<Addr4882>, GpibError>;
+
+    /// Find all listeners on board.
+    fn FindAllLstn(&self, board: c_int) -> Result<Vec<Addr4882>, GpibError> {
+        let padList = (1..31)
+            .map(|pad| Addr4882::new(PrimaryAddress::new(pad)?, SecondaryAddress::default()))
+            .collect::<Result<Vec<Addr4882>, GpibError>>()?;
+        self.FindLstn(board, padList)
+    }
+
+    /// clear a device
+    fn DevClear(&self, board: c_int, address: Addr4882) -> Result<(), GpibError>;
+
+    /// clear multiple devices
+    fn DevClearList(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError>;
+
+    /// put devices into local mode.
+    fn EnableLocal(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError>;
+
+    /// put devices into remote mode.
+    fn EnableRemote(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError>;
+
+    /// find device requesting service and read its status byte
+    fn FindRQS(
+        &self,
+        board: c_int,
+        addresses: &[Addr4882],
+    ) -> Result<(Addr4882, StatusByte), GpibError>;
+
+    /// parallel poll devices
+    fn PPoll(&self, board: c_int) -> Result<c_short, GpibError>;
+
+    /// serial poll a device
+    fn ReadStatusByte(&self, board: c_int, address: Addr4882) -> Result<StatusByte, GpibError>;
+
+    /// perform receive addressing and read data
+    fn Receive(
+        &self,
+        board: c_int,
+        address: Addr4882,
+        buffer: &mut [u8],
+        termination: c_int,
+    ) -> Result<(IbStatus, usize), GpibError>;
+
+    /// reset system
+    fn ResetSys(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError>;
+
+    /// perform send addressing and write data
+    fn Send(
+        &self,
+        board: c_int,
+        address: Addr4882,
+        buffer: &[u8],
+        eot_mode: IbSendEOI,
+    ) -> Result<(), GpibError>;
+
+    /// perform interface clear
+    fn SendIFC(&self, board: c_int) -> Result<(), GpibError>;
+
+    /// write data to multiple devices
+    fn SendList(
+        &self,
+        board: c_int,
+        addresses: &[Addr4882],
+        buffer: &[u8],
+        eot_mode: IbSendEOI,
+    ) -> Result<(), GpibError>;
+
+    /// put devices into local lockout mode
+    fn SendLLO(&self, board: c_int) -> Result<(), GpibError>;
+
+    /// put devices into remote with lockout state
+    fn SetRWLS(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError>;
+
+    ///  query state of SRQ bus line
+    fn TestSRQ(&self, board: c_int) -> Result<bool, GpibError>;
+
+    /// perform self-test queries on devices
+    fn TestSys(&self, board: c_int, addresses: &[Addr4882]) -> Result<Vec<c_short>, GpibError>;
+
+    /// trigger a device
+    fn Trigger(&self, board: c_int, address: Addr4882) -> Result<(), GpibError>;
+
+    /// trigger multiple devices
+    fn TriggerList(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError>;
+
+    /// sleep until the SRQ bus line is asserted, blocking the current thread
+    fn WaitSRQ(&self, board: c_int) -> Result<c_short, GpibError>;
+}
+
+static DEFAULT_BACKEND: RwLock<Option<Arc<dyn Backend>>> = RwLock::new(None);
+
+/// Backend used by `Board::with_board_number` and `Instrument::from_visa_string`.
+///
+/// Unless another backend has been installed with [`set_default_backend`], this is the backend
+/// calling into the installed GPIB library. When the crate is built without one (neither the
+/// `linuxgpib` nor the `nigpib` feature), it is an empty simulated bus, on which every board
+/// reports ENEB.
+pub fn default_backend() -> Arc<dyn Backend> {
+    if let Ok(guard) = DEFAULT_BACKEND.read()
+        && let Some(backend) = guard.as_ref()
+    {
+        return backend.clone();
+    }
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
+    return native::native_backend();
+    #[cfg(not(any(feature = "linuxgpib", feature = "nigpib")))]
+    return no_library_backend();
+}
+
+/// Shared, so that the boards share their I/O thread as with the native backend.
+#[cfg(not(any(feature = "linuxgpib", feature = "nigpib")))]
+fn no_library_backend() -> Arc<dyn Backend> {
+    static EMPTY: std::sync::OnceLock<Arc<dyn Backend>> = std::sync::OnceLock::new();
+    EMPTY
+        .get_or_init(|| Arc::new(sim::SimulatedBus::new()))
+        .clone()
+}
+
+/// Install the backend returned by [`default_backend`] for the whole process.
+///
+/// This is mostly useful in tests and demos, to run application code written against
+/// `Board::with_board_number` or `Instrument::from_visa_string` without a GPIB board.
+pub fn set_default_backend(backend: Arc<dyn Backend>) {
+    match DEFAULT_BACKEND.write() {
+        Ok(mut guard) => {
+            *guard = Some(backend);
+        }
+        Err(poisoned) => {
+            *poisoned.into_inner() = Some(backend);
+        }
+    }
+}
+
+/// Restore the backend calling into the installed GPIB library as default backend.
+pub fn reset_default_backend() {
+    match DEFAULT_BACKEND.write() {
+        Ok(mut guard) => {
+            *guard = None;
+        }
+        Err(poisoned) => {
+            *poisoned.into_inner() = None;
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//!
+//! Backend calling into the installed GPIB library, through the functions of
+//! [`crate::lowlevel::traditional`] and [`crate::lowlevel::multidevice`].
+//!
+#![allow(non_snake_case)]
+
+use crate::backend::Backend;
+use crate::error::GpibError;
+use crate::lowlevel::utility::Addr4882;
+use crate::lowlevel::{multidevice, traditional};
+use crate::status::{IbStatus, StatusByte};
+use crate::types::{
+    IbEosMode, IbLineStatus, IbOnline, IbOption, IbSendEOI, IbTimeout, PrimaryAddress,
+    SecondaryAddress,
+};
+use std::os::raw::{c_int, c_short};
+use std::sync::{Arc, OnceLock};
+
+#[cfg(feature = "linuxgpib")]
+/// Backend for the Linux GPIB user library (`libgpib.so`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinuxGpib;
+
+#[cfg(feature = "nigpib")]
+/// Backend for the NI-488.2 user library (`libni4882.so`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NiGpib;
+
+/// Backend for the library selected with the `linuxgpib` or `nigpib` feature.
+///
+/// The same instance is returned by every call, so that the boards share their I/O thread
+/// (see [`crate::reactor`]).
+pub fn native_backend() -> Arc<dyn Backend> {
+    static NATIVE: OnceLock<Arc<dyn Backend>> = OnceLock::new();
+    NATIVE
+        .get_or_init(|| {
+            #[cfg(feature = "linuxgpib")]
+            return Arc::new(LinuxGpib);
+            #[cfg(feature = "nigpib")]
+            return Arc::new(NiGpib);
+        })
+        .clone()
+}
+
+macro_rules! impl_native_backend {
+    ($backend:ty) => {
+        impl Backend for $backend {
+            fn ibask(&self, ud: c_int, option: IbOption) -> Result<c_int, GpibError> {
+                traditional::ibask(ud, option)
+            }
+
+            fn ibclr(&self, ud: c_int) -> Result<(), GpibError> {
+                traditional::ibclr(ud)
+            }
+
+            fn ibconfig(
+                &self,
+                ud: c_int,
+                option: IbOption,
+                setting: c_int,
+            ) -> Result<(), GpibError> {
+                traditional::ibconfig(ud, option, setting)
+            }
+
+            fn ibdev(
+                &self,
+                board_index: c_int,
+                primary_address: PrimaryAddress,
+                secondary_address: SecondaryAddress,
+                timeout: IbTimeout,
+                send_eoi: IbSendEOI,
+                eos: IbEosMode,
+            ) -> Result<c_int, GpibError> {
+                traditional::ibdev(
+                    board_index,
+                    primary_address,
+                    secondary_address,
+                    timeout,
+                    send_eoi,
+                    eos,
+                )
+            }
+
+            fn ibeos(&self, ud: c_int, eosmod: IbEosMode) -> Result<(), GpibError> {
+                traditional::ibeos(ud, eosmod)
+            }
+
+            fn ibeot(&self, ud: c_int, send_eoi: IbSendEOI) -> Result<(), GpibError> {
+                traditional::ibeot(ud, send_eoi)
+            }
+
+            fn iblines(&self, ud: c_int) -> Result<IbLineStatus, GpibError> {
+                traditional::iblines(ud)
+            }
+
+            fn ibln(
+                &self,
+                ud: c_int,
+                primary_address: PrimaryAddress,
+                secondary_address: SecondaryAddress,
+            ) -> Result<bool, GpibError> {
+                traditional::ibln(ud, primary_address, secondary_address)
+            }
+
+            fn ibloc(&self, ud: c_int) -> Result<(), GpibError> {
+                traditional::ibloc(ud)
+            }
+
+            fn ibonl(&self, ud: c_int, online: IbOnline) -> Result<(), GpibError> {
+                traditional::ibonl(ud, online)
+            }
+
+            fn ibrd(&self, ud: c_int, buffer: &mut [u8]) -> Result<(IbStatus, usize), GpibError> {
+                traditional::ibrd(ud, buffer)
+            }
+
+            unsafe fn ibrda(&self, ud: c_int, buffer: &mut [u8]) -> Result<(), GpibError> {
+                unsafe { traditional::ibrda(ud, buffer) }
+            }
+
+            fn ibrsp(&self, ud: c_int) -> Result<StatusByte, GpibError> {
+                traditional::ibrsp(ud)
+            }
+
+            fn ibsic(&self, ud: c_int) -> Result<(), GpibError> {
+                traditional::ibsic(ud)
+            }
+
+            fn ibsre(&self, ud: c_int, enable: c_int) -> Result<(), GpibError> {
+                traditional::ibsre(ud, enable)
+            }
+
+            fn ibstop(&self, ud: c_int) -> Result<(), GpibError> {
+                traditional::ibstop(ud)
+            }
+
+            fn ibtmo(&self, ud: c_int, timeout: IbTimeout) -> Result<(), GpibError> {
+                traditional::ibtmo(ud, timeout)
+            }
+
+            fn ibtrg(&self, ud: c_int) -> Result<(), GpibError> {
+                traditional::ibtrg(ud)
+            }
+
+            fn ibwait(
+                &self,
+                ud: c_int,
+                status_mask: IbStatus,
+            ) -> Result<(IbStatus, usize), GpibError> {
+                traditional::blocking_ibwait(ud, status_mask)
+            }
+
+            fn ibwrt(&self, ud: c_int, data: &[u8]) -> Result<usize, GpibError> {
+                traditional::ibwrt(ud, data)
+            }
+
+            unsafe fn ibwrta(&self, ud: c_int, data: &[u8]) -> Result<(), GpibError> {
+                unsafe { traditional::ibwrta(ud, data) }
+            }
+
+            fn FindLstn(
+                &self,
+                board: c_int,
+                padList: Vec<Addr4882>,
+            ) -> Result<Vec<Addr4882>, GpibError> {
+                multidevice::FindLstn(board, padList)
+            }
+
+            fn DevClear(&self, board: c_int, address: Addr4882) -> Result<(), GpibError> {
+                multidevice::DevClear(board, address)
+            }
+
+            fn DevClearList(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+                multidevice::DevClearList(board, addresses)
+            }
+
+            fn EnableLocal(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+                multidevice::EnableLocal(board, addresses)
+            }
+
+            fn EnableRemote(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+                multidevice::EnableRemote(board, addresses)
+            }
+
+            fn FindRQS(
+                &self,
+                board: c_int,
+                addresses: &[Addr4882],
+            ) -> Result<(Addr4882, StatusByte), GpibError> {
+                multidevice::FindRQS(board, addresses)
+            }
+
+            fn PPoll(&self, board: c_int) -> Result<c_short, GpibError> {
+                multidevice::PPoll(board)
+            }
+
+            fn ReadStatusByte(
+                &self,
+                board: c_int,
+                address: Addr4882,
+            ) -> Result<StatusByte, GpibError> {
+                multidevice::ReadStatusByte(board, address)
+            }
+
+            fn Receive(
+                &self,
+                board: c_int,
+                address: Addr4882,
+                buffer: &mut [u8],
+                termination: c_int,
+            ) -> Result<(IbStatus, usize), GpibError> {
+                multidevice::Receive(board, address, buffer, termination)
+            }
+
+            fn ResetSys(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+                multidevice::ResetSys(board, addresses)
+            }
+
+            fn Send(
+                &self,
+                board: c_int,
+                address: Addr4882,
+                buffer: &[u8],
+                eot_mode: IbSendEOI,
+            ) -> Result<(), GpibError> {
+                multidevice::Send(board, address, buffer, eot_mode)
+            }
+
+            fn SendIFC(&self, board: c_int) -> Result<(), GpibError> {
+                multidevice::SendIFC(board)
+            }
+
+            fn SendList(
+                &self,
+                board: c_int,
+                addresses: &[Addr4882],
+                buffer: &[u8],
+                eot_mode: IbSendEOI,
+            ) -> Result<(), GpibError> {
+                multidevice::SendList(board, addresses, buffer, eot_mode)
+            }
+
+            fn SendLLO(&self, board: c_int) -> Result<(), GpibError> {
+                multidevice::SendLLO(board)
+            }
+
+            fn SetRWLS(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+                multidevice::SetRWLS(board, addresses)
+            }
+
+            fn TestSRQ(&self, board: c_int) -> Result<bool, GpibError> {
+                multidevice::TestSRQ(board)
+            }
+
+            fn TestSys(
+                &self,
+                board: c_int,
+                addresses: &[Addr4882],
+            ) -> Result<Vec<c_short>, GpibError> {
+                multidevice::TestSys(board, addresses)
+            }
+
+            fn Trigger(&self, board: c_int, address: Addr4882) -> Result<(), GpibError> {
+                multidevice::Trigger(board, address)
+            }
+
+            fn TriggerList(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+                multidevice::TriggerList(board, addresses)
+            }
+
+            fn WaitSRQ(&self, board: c_int) -> Result<c_short, GpibError> {
+                multidevice::blocking_WaitSRQ(board)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "linuxgpib")]
+impl_native_backend!(LinuxGpib);
+
+#[cfg(feature = "nigpib")]
+impl_native_backend!(NiGpib);
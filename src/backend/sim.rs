@@ -0,0 +1,1313 @@
+//!
+//! In-memory simulated GPIB bus.
+//!
+//! [`SimulatedBus`] implements [`Backend`] without any GPIB library or hardware. It models one or
+//! more boards, with devices at primary/secondary addresses. Messages written to a device are
+//! split on its termination character (or on EOI) and handed to a [`SimulatedDevice`], and its
+//! replies are queued until they are read back. Reads honor the end-of-string mode of the
+//! descriptor, EOI, timeouts and reply delays. Devices can request service, which asserts the SRQ
+//! line of their board.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::instrument::Board;
+//! use linux_gpib_rs::types::{IbSendEOI, PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let bus = SimulatedBus::new().with_device(
+//!         0,
+//!         PrimaryAddress::new(5)?,
+//!         SecondaryAddress::default(),
+//!         |message: &[u8]| match message {
+//!             b"*IDN?" => Some(Reply::from("SIM,DMM,0,1.0")),
+//!             _ => None,
+//!         },
+//!     );
+//!     let board = Board::with_backend(0, Arc::new(bus));
+//!     let instruments = board.find_listeners()?;
+//!     board.send_list(&instruments, b"*IDN?\n", IbSendEOI::default())?;
+//!     assert_eq!(instruments[0].receive()?, "SIM,DMM,0,1.0\n");
+//!     Ok(())
+//! }
+//! ```
+//!
+#![allow(non_snake_case)]
+
+#[cfg(feature = "sim-config")]
+pub mod config;
+
+use crate::backend::Backend;
+use crate::error::{GpibError, IbError};
+use crate::lowlevel::utility::Addr4882;
+use crate::status::{IbStatus, StatusByte};
+use crate::sys::Addr4882_t;
+use crate::types::{
+    IbEosMode, IbLineStatus, IbOnline, IbOption, IbSendEOI, IbTimeout, PrimaryAddress,
+    SecondaryAddress,
+};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::os::raw::{c_int, c_short};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Descriptors below this value are board descriptors (the board index), as with Linux GPIB.
+const FIRST_DEVICE_DESCRIPTOR: c_int = 16;
+
+/// Period at which the state of the SRQ line is sampled while waiting for it.
+const SRQ_POLL_PERIOD: Duration = Duration::from_millis(5);
+
+/// Status byte bit set by the bus when a reply is waiting in the output queue.
+const MAV: u8 = 0x10;
+
+/// Status byte bit set by a device requesting service.
+const RQS: u8 = 0x40;
+
+/// Reply of a simulated device to a message
+pub struct Reply {
+    pub data: Vec<u8>,
+    pub delay: Duration,
+}
+
+impl Reply {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// The reply only becomes available to reads after the delay has elapsed
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl From<Vec<u8>> for Reply {
+    fn from(data: Vec<u8>) -> Self {
+        Reply::new(data)
+    }
+}
+
+impl From<&[u8]> for Reply {
+    fn from(data: &[u8]) -> Self {
+        Reply::new(data.to_vec())
+    }
+}
+
+impl From<String> for Reply {
+    fn from(data: String) -> Self {
+        Reply::new(data.into_bytes())
+    }
+}
+
+impl From<&str> for Reply {
+    fn from(data: &str) -> Self {
+        Reply::new(data.as_bytes().to_vec())
+    }
+}
+
+/// Behaviour of a device connected to a [`SimulatedBus`]
+///
+/// Any `FnMut(&[u8]) -> Option<Reply>` closure is a simulated device answering messages
+/// with the default terminations and status byte.
+pub trait SimulatedDevice: Send {
+    /// Handles a complete message (without its termination character), and returns the reply
+    /// to queue in the output buffer of the device, if any.
+    fn handle_message(&mut self, message: &[u8]) -> Option<Reply>;
+
+    /// Called when the device is cleared (ibclr, DevClear, DevClearList).
+    fn clear(&mut self) {}
+
+    /// Called when the device is triggered (ibtrg, Trigger, TriggerList).
+    fn trigger(&mut self) {}
+
+    /// Current status byte. The bus adds the MAV bit (0x10) when a reply is queued. The SRQ line
+    /// is asserted as long as a device returns a status byte with the RQS bit (0x40) set.
+    fn status_byte(&self) -> u8 {
+        0
+    }
+
+    /// Called when the device is serial polled. Returns the status byte, and should clear the
+    /// RQS bit.
+    fn serial_poll(&mut self) -> u8 {
+        self.status_byte()
+    }
+
+    /// Character ending incoming messages, in addition to EOI.
+    fn read_termination(&self) -> Option<u8> {
+        Some(b'\n')
+    }
+
+    /// Bytes appended to every reply. EOI is asserted with the last byte.
+    fn write_termination(&self) -> Vec<u8> {
+        b"\n".to_vec()
+    }
+}
+
+impl<F> SimulatedDevice for F
+where
+    F: FnMut(&[u8]) -> Option<Reply> + Send,
+{
+    fn handle_message(&mut self, message: &[u8]) -> Option<Reply> {
+        self(message)
+    }
+}
+
+struct QueuedReply {
+    data: Vec<u8>,
+    position: usize,
+    ready_at: Instant,
+}
+
+struct DeviceSlot {
+    device: Box<dyn SimulatedDevice>,
+    input: Vec<u8>,
+    output: VecDeque<QueuedReply>,
+    remote: bool,
+    lockout: bool,
+}
+
+impl DeviceSlot {
+    fn requesting_service(&self) -> bool {
+        self.device.status_byte() & RQS != 0
+    }
+
+    fn serial_poll(&mut self) -> u8 {
+        let mav = if self.output.is_empty() { 0 } else { MAV };
+        self.device.serial_poll() | mav
+    }
+
+    fn clear(&mut self) {
+        self.input.clear();
+        self.output.clear();
+        self.device.clear();
+    }
+
+    /// Feeds bytes to the device, which handles every complete message.
+    fn receive(&mut self, data: &[u8], eoi: bool, now: Instant) {
+        let termination = self.device.read_termination();
+        for &byte in data {
+            if Some(byte) == termination {
+                let message = std::mem::take(&mut self.input);
+                self.handle(&message, now);
+            } else {
+                self.input.push(byte);
+            }
+        }
+        if eoi && !self.input.is_empty() {
+            let message = std::mem::take(&mut self.input);
+            self.handle(&message, now);
+        }
+    }
+
+    fn handle(&mut self, message: &[u8], now: Instant) {
+        if let Some(reply) = self.device.handle_message(message) {
+            let mut data = reply.data;
+            data.extend(self.device.write_termination());
+            self.output.push_back(QueuedReply {
+                data,
+                position: 0,
+                ready_at: now + reply.delay,
+            });
+        }
+    }
+
+    /// Reads from the output queue into buffer, stopping on EOI, or after the eos character.
+    fn talk(
+        &mut self,
+        buffer: &mut [u8],
+        eos: Option<(u8, bool)>,
+        now: Instant,
+    ) -> Progress<(bool, usize)> {
+        let Some(reply) = self.output.front_mut() else {
+            return Progress::Pending(None);
+        };
+        if reply.ready_at > now {
+            return Progress::Pending(Some(reply.ready_at));
+        }
+        let mut count = 0;
+        let mut eos_received = false;
+        while count < buffer.len() && reply.position < reply.data.len() {
+            let byte = reply.data[reply.position];
+            buffer[count] = byte;
+            count += 1;
+            reply.position += 1;
+            if let Some((eos_char, bin)) = eos {
+                let matches = if bin {
+                    byte == eos_char
+                } else {
+                    (byte & 0x7f) == (eos_char & 0x7f)
+                };
+                if matches {
+                    eos_received = true;
+                    break;
+                }
+            }
+        }
+        let eoi = reply.position >= reply.data.len();
+        if eoi {
+            self.output.pop_front();
+        }
+        Progress::Done((eoi || eos_received, count))
+    }
+}
+
+struct SimBoard {
+    devices: BTreeMap<Addr4882_t, DeviceSlot>,
+    timeout: IbTimeout,
+    remote_enable: bool,
+}
+
+impl SimBoard {
+    fn srq(&self) -> bool {
+        self.devices.values().any(|slot| slot.requesting_service())
+    }
+
+    fn device(&mut self, address: Addr4882) -> Option<&mut DeviceSlot> {
+        self.devices.get_mut(&address.addr)
+    }
+}
+
+/// Raw buffer of an asynchronous read, filled in when the transfer is resynchronized
+struct ReadBuffer {
+    pointer: *mut u8,
+    length: usize,
+}
+
+// The buffer is only accessed with the bus mutex held, and the caller of ibrda guarantees it
+// remains valid until ibwait or ibstop.
+unsafe impl Send for ReadBuffer {}
+
+enum AsyncIo {
+    Read(ReadBuffer),
+    Write(Vec<u8>),
+}
+
+struct Descriptor {
+    board: c_int,
+    address: Addr4882,
+    timeout: IbTimeout,
+    send_eoi: IbSendEOI,
+    eos: IbEosMode,
+    pending: Option<AsyncIo>,
+    /// Incremented by each asynchronous transfer, so that a late ibwait cannot complete the
+    /// transfer started after an ibstop
+    transfer: u64,
+    /// Transfer completed by an `ibwait` with an empty status mask, reported by the next `ibwait`
+    completed: Option<(bool, usize)>,
+}
+
+impl Descriptor {
+    fn read_eos(&self) -> Option<(u8, bool)> {
+        if self.eos.reos {
+            Some((self.eos.eos_char, self.eos.bin))
+        } else {
+            None
+        }
+    }
+
+    fn write_eoi(&self, data: &[u8]) -> bool {
+        match self.send_eoi {
+            IbSendEOI::Enabled(_) => true,
+            IbSendEOI::Disabled => self.eos.xeos && data.last() == Some(&self.eos.eos_char),
+        }
+    }
+}
+
+struct BusState {
+    boards: BTreeMap<c_int, SimBoard>,
+    descriptors: HashMap<c_int, Descriptor>,
+    next_descriptor: c_int,
+}
+
+impl BusState {
+    fn board(&mut self, board: c_int) -> Result<&mut SimBoard, GpibError> {
+        self.boards
+            .get_mut(&board)
+            .ok_or_else(|| driver_error(IbStatus::default(), IbError::ENEB))
+    }
+
+    fn descriptor(&mut self, ud: c_int) -> Result<&mut Descriptor, GpibError> {
+        self.descriptors
+            .get_mut(&ud)
+            .ok_or_else(|| driver_error(IbStatus::default(), IbError::EDVR(0)))
+    }
+
+    /// Writes data to a device, and returns the number of bytes written.
+    fn send(
+        &mut self,
+        board: c_int,
+        address: Addr4882,
+        data: &[u8],
+        eoi: bool,
+    ) -> Result<usize, GpibError> {
+        let board = self.board(board)?;
+        let remote = board.remote_enable;
+        let slot = board
+            .device(address)
+            .ok_or_else(|| driver_error(IbStatus::default().with_cmpl(true), IbError::ENOL))?;
+        slot.remote = remote;
+        slot.receive(data, eoi, Instant::now());
+        Ok(data.len())
+    }
+
+    /// Selects the given devices, or every device of the board when the list is empty.
+    fn devices(
+        &mut self,
+        board: c_int,
+        addresses: &[Addr4882],
+    ) -> Result<Vec<&mut DeviceSlot>, GpibError> {
+        let board = self.board(board)?;
+        Ok(board
+            .devices
+            .iter_mut()
+            .filter(|(addr, _)| addresses.is_empty() || addresses.iter().any(|a| a.addr == **addr))
+            .map(|(_, slot)| slot)
+            .collect())
+    }
+}
+
+enum Progress<T> {
+    Done(T),
+    /// Not ready yet. Poll again at the given instant, or when the bus state changes.
+    Pending(Option<Instant>),
+}
+
+/// In-memory GPIB bus implementing [`Backend`]
+///
+/// Board 0 exists by default. Descriptors returned by `ibdev` start at 16, lower values are
+/// board descriptors, as with Linux GPIB.
+pub struct SimulatedBus {
+    state: Mutex<BusState>,
+    event: Condvar,
+}
+
+impl Default for SimulatedBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedBus {
+    pub fn new() -> Self {
+        let mut boards = BTreeMap::new();
+        boards.insert(0, SimBoard::new());
+        Self {
+            state: Mutex::new(BusState {
+                boards,
+                descriptors: HashMap::new(),
+                next_descriptor: FIRST_DEVICE_DESCRIPTOR,
+            }),
+            event: Condvar::new(),
+        }
+    }
+
+    /// Adds an empty board
+    pub fn with_board(self, board: c_int) -> Self {
+        if let Ok(mut state) = self.state.lock() {
+            state.boards.entry(board).or_insert_with(SimBoard::new);
+        }
+        self
+    }
+
+    /// Connects a device to a board, creating the board if needed
+    pub fn with_device(
+        self,
+        board: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+        device: impl SimulatedDevice + 'static,
+    ) -> Self {
+        self.add_device(board, primary_address, secondary_address, Box::new(device));
+        self
+    }
+
+    /// Connects a device to a board, creating the board if needed.
+    /// A device already present at the same address is replaced.
+    pub fn add_device(
+        &self,
+        board: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+        device: Box<dyn SimulatedDevice>,
+    ) {
+        let address = Addr4882::new(primary_address, secondary_address)
+            .expect("validated addresses always fit in Addr4882");
+        if let Ok(mut state) = self.state.lock() {
+            state
+                .boards
+                .entry(board)
+                .or_insert_with(SimBoard::new)
+                .devices
+                .insert(
+                    address.addr,
+                    DeviceSlot {
+                        device,
+                        input: Vec::new(),
+                        output: VecDeque::new(),
+                        remote: false,
+                        lockout: false,
+                    },
+                );
+        }
+        self.event.notify_all();
+    }
+
+    /// Disconnects a device from its board
+    pub fn remove_device(
+        &self,
+        board: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+    ) -> Result<(), GpibError> {
+        let address = Addr4882::new(primary_address, secondary_address)?;
+        let mut state = self.lock()?;
+        state.board(board)?.devices.remove(&address.addr);
+        Ok(())
+    }
+
+    /// Whether the device is in remote state, and whether it is locked out of local controls
+    pub fn remote_state(&self, board: c_int, address: Addr4882) -> Result<(bool, bool), GpibError> {
+        let mut state = self.lock()?;
+        let slot = state.board(board)?.device(address).ok_or_else(|| {
+            GpibError::ValueError(format!(
+                "No simulated device at {} on board {}.",
+                address, board
+            ))
+        })?;
+        Ok((slot.remote, slot.lockout))
+    }
+
+    /// Wakes up the threads waiting on the bus, so that they poll the devices again.
+    /// This is needed when the status byte of a device changes outside of bus operations.
+    pub fn notify(&self) {
+        self.event.notify_all();
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, BusState>, GpibError> {
+        self.state.lock().map_err(poisoned)
+    }
+
+    /// Polls until done, or until the timeout expires (then returns None).
+    fn wait_for<T>(
+        &self,
+        timeout: IbTimeout,
+        mut poll: impl FnMut(&mut BusState, Instant) -> Result<Progress<T>, GpibError>,
+    ) -> Result<Option<T>, GpibError> {
+        let deadline = Instant::now().checked_add(timeout.as_duration());
+        let mut state = self.lock()?;
+        loop {
+            let now = Instant::now();
+            match poll(&mut state, now)? {
+                Progress::Done(value) => {
+                    self.event.notify_all();
+                    return Ok(Some(value));
+                }
+                Progress::Pending(wake_up) => {
+                    if let Some(deadline) = deadline
+                        && now >= deadline
+                    {
+                        return Ok(None);
+                    }
+                    state = match [wake_up, deadline].into_iter().flatten().min() {
+                        Some(instant) => {
+                            self.event
+                                .wait_timeout(state, instant.saturating_duration_since(now))
+                                .map_err(poisoned)?
+                                .0
+                        }
+                        None => self.event.wait(state).map_err(poisoned)?,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Reads from a device, with the timeout and termination of a descriptor.
+    fn talk(
+        &self,
+        board: c_int,
+        address: Addr4882,
+        timeout: IbTimeout,
+        eos: Option<(u8, bool)>,
+        buffer: &mut [u8],
+    ) -> Result<(IbStatus, usize), GpibError> {
+        let result = self.wait_for(timeout, |state, now| {
+            Ok(match state.board(board)?.device(address) {
+                Some(slot) => slot.talk(buffer, eos, now),
+                None => Progress::Pending(None),
+            })
+        })?;
+        match result {
+            Some((end, count)) => Ok((IbStatus::default().with_cmpl(true).with_end(end), count)),
+            None => Err(timeout_error()),
+        }
+    }
+
+    /// Makes the asynchronous transfer pending on the descriptor progress without blocking, and
+    /// returns the current status, as `ibwait` with an empty status mask.
+    fn poll_status(&self, ud: c_int) -> Result<(IbStatus, usize), GpibError> {
+        let now = Instant::now();
+        let mut state = self.lock()?;
+        let desc = state.descriptor(ud)?;
+        let (board, address, eos) = (desc.board, desc.address, desc.read_eos());
+        let done = match desc.pending.take() {
+            None => None,
+            Some(AsyncIo::Write(data)) => {
+                let eoi = desc.write_eoi(&data);
+                Some((false, state.send(board, address, &data, eoi)?))
+            }
+            Some(AsyncIo::Read(target)) => {
+                // SAFETY: the caller of ibrda guarantees the buffer remains valid until ibwait
+                // or ibstop, and ibstop needs the mutex we are holding.
+                let buffer =
+                    unsafe { std::slice::from_raw_parts_mut(target.pointer, target.length) };
+                let progress = match state.board(board)?.device(address) {
+                    Some(slot) => slot.talk(buffer, eos, now),
+                    None => Progress::Pending(None),
+                };
+                match progress {
+                    Progress::Done(value) => Some(value),
+                    Progress::Pending(_) => {
+                        state.descriptor(ud)?.pending = Some(AsyncIo::Read(target));
+                        None
+                    }
+                }
+            }
+        };
+        if done.is_some() {
+            state.descriptor(ud)?.completed = done;
+            self.event.notify_all();
+        }
+        let desc = state.descriptor(ud)?;
+        let status = IbStatus::default().with_cmpl(desc.pending.is_none());
+        let (status, count) = match desc.completed {
+            Some((end, count)) => (status.with_end(end), count),
+            None => (status, 0),
+        };
+        let rqs = state
+            .board(board)?
+            .device(address)
+            .is_some_and(|slot| slot.requesting_service());
+        Ok((status.with_rqs(rqs), count))
+    }
+
+    /// Performs the asynchronous transfer pending on the descriptor.
+    fn resynchronize(&self, ud: c_int) -> Result<(IbStatus, usize), GpibError> {
+        let (board, address, timeout, eos, pending, transfer) = {
+            let mut state = self.lock()?;
+            let desc = state.descriptor(ud)?;
+            if let Some((end, count)) = desc.completed.take() {
+                return Ok((IbStatus::default().with_cmpl(true).with_end(end), count));
+            }
+            let pending = match desc.pending {
+                None => None,
+                Some(AsyncIo::Read(_)) => Some(true),
+                Some(AsyncIo::Write(_)) => Some(false),
+            };
+            (
+                desc.board,
+                desc.address,
+                desc.timeout,
+                desc.read_eos(),
+                pending,
+                desc.transfer,
+            )
+        };
+        match pending {
+            None => Ok((IbStatus::default().with_cmpl(true), 0)),
+            Some(false) => {
+                let mut state = self.lock()?;
+                let desc = state.descriptor(ud)?;
+                if desc.transfer != transfer {
+                    return Ok((IbStatus::default().with_cmpl(true), 0));
+                }
+                let Some(AsyncIo::Write(data)) = desc.pending.take() else {
+                    return Ok((IbStatus::default().with_cmpl(true), 0));
+                };
+                let eoi = desc.write_eoi(&data);
+                let count = state.send(board, address, &data, eoi)?;
+                self.event.notify_all();
+                Ok((IbStatus::default().with_cmpl(true), count))
+            }
+            Some(true) => {
+                let result = self.wait_for(timeout, |state, now| {
+                    let Some(Some(AsyncIo::Read(target))) = state
+                        .descriptors
+                        .get(&ud)
+                        .filter(|desc| desc.transfer == transfer)
+                        .map(|desc| &desc.pending)
+                    else {
+                        // aborted with ibstop
+                        return Ok(Progress::Done(None));
+                    };
+                    // SAFETY: the caller of ibrda guarantees the buffer remains valid until
+                    // ibwait or ibstop, and ibstop needs the mutex we are holding.
+                    let buffer =
+                        unsafe { std::slice::from_raw_parts_mut(target.pointer, target.length) };
+                    let progress = match state.board(board)?.device(address) {
+                        Some(slot) => slot.talk(buffer, eos, now),
+                        None => Progress::Pending(None),
+                    };
+                    Ok(match progress {
+                        Progress::Done(value) => {
+                            if let Some(desc) = state.descriptors.get_mut(&ud) {
+                                desc.pending = None;
+                            }
+                            Progress::Done(Some(value))
+                        }
+                        Progress::Pending(wake_up) => Progress::Pending(wake_up),
+                    })
+                })?;
+                match result {
+                    Some(Some((end, count))) => {
+                        Ok((IbStatus::default().with_cmpl(true).with_end(end), count))
+                    }
+                    Some(None) => Err(driver_error(
+                        IbStatus::default().with_cmpl(true),
+                        IbError::EABO,
+                    )),
+                    None => {
+                        if let Ok(mut state) = self.lock()
+                            && let Some(desc) = state.descriptors.get_mut(&ud)
+                            && desc.transfer == transfer
+                        {
+                            desc.pending = None;
+                        }
+                        Ok((IbStatus::default().with_cmpl(true).with_timo(true), 0))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl SimBoard {
+    fn new() -> Self {
+        Self {
+            devices: BTreeMap::new(),
+            timeout: IbTimeout::T3s,
+            remote_enable: true,
+        }
+    }
+}
+
+fn driver_error(status: IbStatus, error: IbError) -> GpibError {
+    GpibError::DriverError(status.with_err(true), error)
+}
+
+fn timeout_error() -> GpibError {
+    driver_error(
+        IbStatus::default().with_cmpl(true).with_timo(true),
+        IbError::EABO,
+    )
+}
+
+fn poisoned<T>(e: PoisonError<T>) -> GpibError {
+    GpibError::ValueError(format!("Simulated bus state poisoned ({})", e))
+}
+
+fn flag(value: bool) -> c_int {
+    if value { 1 } else { 0 }
+}
+
+impl Backend for SimulatedBus {
+    fn ibask(&self, ud: c_int, option: IbOption) -> Result<c_int, GpibError> {
+        let mut state = self.lock()?;
+        if ud < FIRST_DEVICE_DESCRIPTOR {
+            let board = state.board(ud)?;
+            return match option {
+                IbOption::PAD => Ok(0),
+                IbOption::SAD => Ok(0),
+                IbOption::TMO => Ok(board.timeout.as_timeout()),
+                IbOption::SC => Ok(1),
+                IbOption::SRE => Ok(flag(board.remote_enable)),
+                IbOption::BNA => Ok(ud),
+                IbOption::EndBitIsNormal => Ok(1),
+                _ => Err(driver_error(IbStatus::default(), IbError::ECAP)),
+            };
+        }
+        let desc = state.descriptor(ud)?;
+        match option {
+            IbOption::PAD => Ok(desc.address.pad().into()),
+            IbOption::SAD => Ok(desc.address.sad().into()),
+            IbOption::TMO => Ok(desc.timeout.as_timeout()),
+            IbOption::EOT => Ok(desc.send_eoi.as_eot()),
+            IbOption::EOSrd => Ok(flag(desc.eos.reos)),
+            IbOption::EOSwrt => Ok(flag(desc.eos.xeos)),
+            IbOption::EOScmp => Ok(flag(desc.eos.bin)),
+            IbOption::EOSchar => Ok(desc.eos.eos_char.into()),
+            IbOption::BNA => Ok(desc.board),
+            IbOption::EndBitIsNormal => Ok(1),
+            _ => Err(driver_error(IbStatus::default(), IbError::ECAP)),
+        }
+    }
+
+    fn ibclr(&self, ud: c_int) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        let desc = state.descriptor(ud)?;
+        let (board, address) = (desc.board, desc.address);
+        if let Some(slot) = state.board(board)?.device(address) {
+            slot.clear();
+        }
+        self.event.notify_all();
+        Ok(())
+    }
+
+    fn ibconfig(&self, ud: c_int, option: IbOption, setting: c_int) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        if ud < FIRST_DEVICE_DESCRIPTOR {
+            let board = state.board(ud)?;
+            return match option {
+                IbOption::TMO => {
+                    board.timeout = IbTimeout::from_timeout(setting)?;
+                    Ok(())
+                }
+                IbOption::SRE => {
+                    board.remote_enable = setting != 0;
+                    Ok(())
+                }
+                _ => Err(driver_error(IbStatus::default(), IbError::ECAP)),
+            };
+        }
+        let desc = state.descriptor(ud)?;
+        match option {
+            IbOption::TMO => desc.timeout = IbTimeout::from_timeout(setting)?,
+            IbOption::EOT => {
+                desc.send_eoi = if setting == 0 {
+                    IbSendEOI::Disabled
+                } else {
+                    IbSendEOI::Enabled(setting)
+                }
+            }
+            IbOption::EOSrd => desc.eos.reos = setting != 0,
+            IbOption::EOSwrt => desc.eos.xeos = setting != 0,
+            IbOption::EOScmp => desc.eos.bin = setting != 0,
+            IbOption::EOSchar => desc.eos.eos_char = setting.try_into()?,
+            _ => return Err(driver_error(IbStatus::default(), IbError::ECAP)),
+        }
+        Ok(())
+    }
+
+    fn ibdev(
+        &self,
+        board_index: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+        timeout: IbTimeout,
+        send_eoi: IbSendEOI,
+        eos: IbEosMode,
+    ) -> Result<c_int, GpibError> {
+        let address = Addr4882::new(primary_address, secondary_address)?;
+        let mut state = self.lock()?;
+        state.board(board_index)?;
+        let ud = state.next_descriptor;
+        state.next_descriptor += 1;
+        state.descriptors.insert(
+            ud,
+            Descriptor {
+                board: board_index,
+                address,
+                timeout,
+                send_eoi,
+                eos,
+                pending: None,
+                transfer: 0,
+                completed: None,
+            },
+        );
+        log::debug!("sim ibdev({}, {}) -> {}", board_index, address, ud);
+        Ok(ud)
+    }
+
+    fn ibeos(&self, ud: c_int, eosmod: IbEosMode) -> Result<(), GpibError> {
+        self.lock()?.descriptor(ud)?.eos = eosmod;
+        Ok(())
+    }
+
+    fn ibeot(&self, ud: c_int, send_eoi: IbSendEOI) -> Result<(), GpibError> {
+        self.lock()?.descriptor(ud)?.send_eoi = send_eoi;
+        Ok(())
+    }
+
+    fn iblines(&self, ud: c_int) -> Result<IbLineStatus, GpibError> {
+        let mut state = self.lock()?;
+        let board = state.board(ud)?;
+        let mut line_status: c_short = 0xff;
+        if board.remote_enable {
+            line_status |= 0x1000;
+        }
+        if board.srq() {
+            line_status |= 0x2000;
+        }
+        Ok(IbLineStatus::from_line_status(line_status))
+    }
+
+    fn ibln(
+        &self,
+        ud: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+    ) -> Result<bool, GpibError> {
+        let address = Addr4882::new(primary_address, secondary_address)?;
+        let mut state = self.lock()?;
+        let board = if ud < FIRST_DEVICE_DESCRIPTOR {
+            ud
+        } else {
+            state.descriptor(ud)?.board
+        };
+        Ok(state.board(board)?.device(address).is_some())
+    }
+
+    fn ibloc(&self, ud: c_int) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        if ud < FIRST_DEVICE_DESCRIPTOR {
+            state.board(ud)?;
+            return Ok(());
+        }
+        let desc = state.descriptor(ud)?;
+        let (board, address) = (desc.board, desc.address);
+        if let Some(slot) = state.board(board)?.device(address)
+            && !slot.lockout
+        {
+            slot.remote = false;
+        }
+        Ok(())
+    }
+
+    fn ibonl(&self, ud: c_int, online: IbOnline) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        if ud < FIRST_DEVICE_DESCRIPTOR {
+            state.board(ud)?;
+            return Ok(());
+        }
+        match online {
+            IbOnline::Close => {
+                state
+                    .descriptors
+                    .remove(&ud)
+                    .ok_or_else(|| driver_error(IbStatus::default(), IbError::EDVR(0)))?;
+            }
+            IbOnline::Reset(_) => {
+                state.descriptor(ud)?.pending = None;
+            }
+        }
+        self.event.notify_all();
+        Ok(())
+    }
+
+    fn ibrd(&self, ud: c_int, buffer: &mut [u8]) -> Result<(IbStatus, usize), GpibError> {
+        let (board, address, timeout, eos) = {
+            let mut state = self.lock()?;
+            let desc = state.descriptor(ud)?;
+            if desc.pending.is_some() {
+                return Err(driver_error(IbStatus::default(), IbError::EOIP));
+            }
+            (desc.board, desc.address, desc.timeout, desc.read_eos())
+        };
+        let result = self.talk(board, address, timeout, eos, buffer);
+        log::debug!("sim ibrd({}, count = {}) -> {:?}", ud, buffer.len(), result);
+        result
+    }
+
+    unsafe fn ibrda(&self, ud: c_int, buffer: &mut [u8]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        let desc = state.descriptor(ud)?;
+        if desc.pending.is_some() {
+            return Err(driver_error(IbStatus::default(), IbError::EOIP));
+        }
+        desc.transfer += 1;
+        desc.completed = None;
+        desc.pending = Some(AsyncIo::Read(ReadBuffer {
+            pointer: buffer.as_mut_ptr(),
+            length: buffer.len(),
+        }));
+        Ok(())
+    }
+
+    fn ibrsp(&self, ud: c_int) -> Result<StatusByte, GpibError> {
+        let mut state = self.lock()?;
+        let desc = state.descriptor(ud)?;
+        let (board, address) = (desc.board, desc.address);
+        let slot = state
+            .board(board)?
+            .device(address)
+            .ok_or_else(timeout_error)?;
+        let status_byte = slot.serial_poll();
+        self.event.notify_all();
+        Ok(StatusByte::from_bits(status_byte))
+    }
+
+    fn ibsic(&self, ud: c_int) -> Result<(), GpibError> {
+        self.SendIFC(ud)
+    }
+
+    fn ibsre(&self, ud: c_int, enable: c_int) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        let board = state.board(ud)?;
+        board.remote_enable = enable != 0;
+        if !board.remote_enable {
+            for slot in board.devices.values_mut() {
+                slot.remote = false;
+                slot.lockout = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn ibstop(&self, ud: c_int) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        if ud >= FIRST_DEVICE_DESCRIPTOR {
+            let desc = state.descriptor(ud)?;
+            desc.pending = None;
+            desc.completed = None;
+        }
+        self.event.notify_all();
+        Ok(())
+    }
+
+    fn ibtmo(&self, ud: c_int, timeout: IbTimeout) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        if ud < FIRST_DEVICE_DESCRIPTOR {
+            state.board(ud)?.timeout = timeout;
+        } else {
+            state.descriptor(ud)?.timeout = timeout;
+        }
+        Ok(())
+    }
+
+    fn ibtrg(&self, ud: c_int) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        let desc = state.descriptor(ud)?;
+        let (board, address) = (desc.board, desc.address);
+        let slot = state
+            .board(board)?
+            .device(address)
+            .ok_or_else(|| driver_error(IbStatus::default().with_cmpl(true), IbError::ENOL))?;
+        slot.device.trigger();
+        self.event.notify_all();
+        Ok(())
+    }
+
+    fn ibwait(&self, ud: c_int, status_mask: IbStatus) -> Result<(IbStatus, usize), GpibError> {
+        if ud < FIRST_DEVICE_DESCRIPTOR {
+            let timeout = self.lock()?.board(ud)?.timeout;
+            let srq = if !status_mask.srqi {
+                let mut state = self.lock()?;
+                Some(state.board(ud)?.srq())
+            } else {
+                let timeout = if status_mask.timo {
+                    timeout
+                } else {
+                    IbTimeout::TNone
+                };
+                self.wait_for(timeout, |state, now| {
+                    Ok(if state.board(ud)?.srq() {
+                        Progress::Done(true)
+                    } else {
+                        Progress::Pending(Some(now + SRQ_POLL_PERIOD))
+                    })
+                })?
+            };
+            let status = IbStatus::default().with_cic(true);
+            return Ok(match srq {
+                Some(srqi) => (status.with_srqi(srqi), 0),
+                None => (status.with_timo(true), 0),
+            });
+        }
+
+        if status_mask.as_ibsta() == 0 {
+            return self.poll_status(ud);
+        }
+        let (status, count) = self.resynchronize(ud)?;
+        if status.timo || !status_mask.rqs || status_mask.cmpl || status_mask.end {
+            return Ok((status, count));
+        }
+        let (board, address, timeout) = {
+            let mut state = self.lock()?;
+            let desc = state.descriptor(ud)?;
+            (desc.board, desc.address, desc.timeout)
+        };
+        let timeout = if status_mask.timo {
+            timeout
+        } else {
+            IbTimeout::TNone
+        };
+        let rqs = self.wait_for(timeout, |state, now| {
+            let requesting = state
+                .board(board)?
+                .device(address)
+                .is_some_and(|slot| slot.requesting_service());
+            Ok(if requesting {
+                Progress::Done(())
+            } else {
+                Progress::Pending(Some(now + SRQ_POLL_PERIOD))
+            })
+        })?;
+        Ok(match rqs {
+            Some(()) => (status.with_rqs(true), count),
+            None => (status.with_timo(true), count),
+        })
+    }
+
+    fn ibwrt(&self, ud: c_int, data: &[u8]) -> Result<usize, GpibError> {
+        let mut state = self.lock()?;
+        let desc = state.descriptor(ud)?;
+        if desc.pending.is_some() {
+            return Err(driver_error(IbStatus::default(), IbError::EOIP));
+        }
+        let (board, address, eoi) = (desc.board, desc.address, desc.write_eoi(data));
+        let result = state.send(board, address, data, eoi);
+        self.event.notify_all();
+        log::debug!("sim ibwrt({}, {:?}) -> {:?}", ud, data, result);
+        result
+    }
+
+    unsafe fn ibwrta(&self, ud: c_int, data: &[u8]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        let desc = state.descriptor(ud)?;
+        if desc.pending.is_some() {
+            return Err(driver_error(IbStatus::default(), IbError::EOIP));
+        }
+        desc.transfer += 1;
+        desc.completed = None;
+        desc.pending = Some(AsyncIo::Write(data.to_vec()));
+        Ok(())
+    }
+
+    fn FindLstn(&self, board: c_int, padList: Vec<Addr4882>) -> Result<Vec<Addr4882>, GpibError> {
+        let mut state = self.lock()?;
+        let board = state.board(board)?;
+        if board.devices.is_empty() {
+            return Err(driver_error(IbStatus::default(), IbError::EBUS));
+        }
+        let mut result = Vec::new();
+        for pad in padList.iter().map(|a| a.pad()) {
+            let primary = Addr4882 {
+                addr: crate::lowlevel::utility::MakeAddr(pad, 0),
+            };
+            if board.devices.contains_key(&primary.addr) {
+                result.push(primary);
+            } else {
+                result.extend(
+                    board
+                        .devices
+                        .keys()
+                        .map(|&addr| Addr4882 { addr })
+                        .filter(|a| a.pad() == pad),
+                );
+            }
+        }
+        log::debug!("sim FindLstn({:?}) -> {:?}", padList, result);
+        Ok(result)
+    }
+
+    fn DevClear(&self, board: c_int, address: Addr4882) -> Result<(), GpibError> {
+        self.DevClearList(board, &[address])
+    }
+
+    fn DevClearList(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        for slot in state.devices(board, addresses)? {
+            slot.clear();
+        }
+        self.event.notify_all();
+        Ok(())
+    }
+
+    fn EnableLocal(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        if addresses.is_empty() {
+            state.board(board)?.remote_enable = false;
+        }
+        for slot in state.devices(board, addresses)? {
+            slot.remote = false;
+            if addresses.is_empty() {
+                slot.lockout = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn EnableRemote(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        state.board(board)?.remote_enable = true;
+        for slot in state.devices(board, addresses)? {
+            slot.remote = true;
+        }
+        Ok(())
+    }
+
+    fn FindRQS(
+        &self,
+        board: c_int,
+        addresses: &[Addr4882],
+    ) -> Result<(Addr4882, StatusByte), GpibError> {
+        let mut state = self.lock()?;
+        let sim_board = state.board(board)?;
+        for address in addresses {
+            if let Some(slot) = sim_board.device(*address)
+                && slot.requesting_service()
+            {
+                let status_byte = slot.serial_poll();
+                self.event.notify_all();
+                return Ok((*address, status_byte.into()));
+            }
+        }
+        Err(driver_error(IbStatus::default(), IbError::ETAB))
+    }
+
+    fn PPoll(&self, board: c_int) -> Result<c_short, GpibError> {
+        self.lock()?.board(board)?;
+        Ok(0)
+    }
+
+    fn ReadStatusByte(&self, board: c_int, address: Addr4882) -> Result<StatusByte, GpibError> {
+        let mut state = self.lock()?;
+        let slot = state
+            .board(board)?
+            .device(address)
+            .ok_or_else(timeout_error)?;
+        let status_byte = slot.serial_poll();
+        self.event.notify_all();
+        Ok(status_byte.into())
+    }
+
+    fn Receive(
+        &self,
+        board: c_int,
+        address: Addr4882,
+        buffer: &mut [u8],
+        termination: c_int,
+    ) -> Result<(IbStatus, usize), GpibError> {
+        let timeout = self.lock()?.board(board)?.timeout;
+        let eos = if termination == crate::sys::STOPend {
+            None
+        } else {
+            Some((termination.try_into()?, true))
+        };
+        let result = self.talk(board, address, timeout, eos, buffer);
+        log::debug!("sim Receive({}, {:?}) -> {:?}", board, address, result);
+        result
+    }
+
+    fn ResetSys(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        state.board(board)?.remote_enable = true;
+        for slot in state.devices(board, &[])? {
+            slot.clear();
+        }
+        for address in addresses {
+            state.send(board, *address, b"*RST\n", true)?;
+        }
+        self.event.notify_all();
+        Ok(())
+    }
+
+    fn Send(
+        &self,
+        board: c_int,
+        address: Addr4882,
+        buffer: &[u8],
+        eot_mode: IbSendEOI,
+    ) -> Result<(), GpibError> {
+        self.SendList(board, &[address], buffer, eot_mode)
+    }
+
+    fn SendIFC(&self, board: c_int) -> Result<(), GpibError> {
+        self.lock()?.board(board)?;
+        Ok(())
+    }
+
+    fn SendList(
+        &self,
+        board: c_int,
+        addresses: &[Addr4882],
+        buffer: &[u8],
+        eot_mode: IbSendEOI,
+    ) -> Result<(), GpibError> {
+        let eoi = matches!(eot_mode, IbSendEOI::Enabled(_));
+        let mut state = self.lock()?;
+        let mut listeners = 0;
+        for address in addresses {
+            match state.send(board, *address, buffer, eoi) {
+                Ok(_) => listeners += 1,
+                Err(GpibError::DriverError(_, IbError::ENOL)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.event.notify_all();
+        log::debug!("sim SendList({}, {:?}, {:?})", board, addresses, buffer);
+        if listeners == 0 {
+            Err(driver_error(
+                IbStatus::default().with_cmpl(true),
+                IbError::ENOL,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn SendLLO(&self, board: c_int) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        for slot in state.devices(board, &[])? {
+            slot.lockout = true;
+        }
+        Ok(())
+    }
+
+    fn SetRWLS(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        state.board(board)?.remote_enable = true;
+        for slot in state.devices(board, addresses)? {
+            slot.remote = true;
+            slot.lockout = true;
+        }
+        Ok(())
+    }
+
+    fn TestSRQ(&self, board: c_int) -> Result<bool, GpibError> {
+        Ok(self.lock()?.board(board)?.srq())
+    }
+
+    fn TestSys(&self, board: c_int, addresses: &[Addr4882]) -> Result<Vec<c_short>, GpibError> {
+        let mut results = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            self.Send(board, *address, b"*TST?\n", IbSendEOI::Enabled(1))?;
+            let mut buffer = [0u8; 64];
+            let (_, count) = self.Receive(board, *address, &mut buffer, crate::sys::STOPend)?;
+            let answer = std::str::from_utf8(&buffer[0..count])?;
+            results.push(answer.trim().parse::<c_short>().map_err(|e| {
+                GpibError::ValueError(format!("Invalid *TST? answer '{}' ({:?})", answer, e))
+            })?);
+        }
+        Ok(results)
+    }
+
+    fn Trigger(&self, board: c_int, address: Addr4882) -> Result<(), GpibError> {
+        self.TriggerList(board, &[address])
+    }
+
+    fn TriggerList(&self, board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
+        let mut state = self.lock()?;
+        for slot in state.devices(board, addresses)? {
+            slot.device.trigger();
+        }
+        self.event.notify_all();
+        Ok(())
+    }
+
+    fn WaitSRQ(&self, board: c_int) -> Result<c_short, GpibError> {
+        let timeout = self.lock()?.board(board)?.timeout;
+        let srq = self.wait_for(timeout, |state, now| {
+            Ok(if state.board(board)?.srq() {
+                Progress::Done(())
+            } else {
+                Progress::Pending(Some(now + SRQ_POLL_PERIOD))
+            })
+        })?;
+        Ok(if srq.is_some() { 1 } else { 0 })
+    }
+}
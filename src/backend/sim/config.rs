@@ -0,0 +1,500 @@
+//!
+//! Simulated instruments described in a YAML or TOML file (feature `sim-config`).
+//!
+//! The file declares device models and the resources where they are connected:
+//!
+//! ```yaml
+//! devices:
+//!   dmm:
+//!     delay_ms: 10
+//!     dialogues:
+//!       - q: "*IDN?"
+//!         r: "SIM,DMM,0,1.0"
+//!     patterns:
+//!       - q: "MEAS:(VOLT|CURR)\\?"
+//!         r: "${1} 0.0"
+//!     properties:
+//!       voltage:
+//!         default: "1.0"
+//!         getter: "VOLT?"
+//!         setter: "VOLT {}"
+//!         min: 0.0
+//!         max: 10.0
+//!     errors:
+//!       initial: ['-310,"System error"']
+//! resources:
+//!   "GPIB0::5::INSTR":
+//!     device: dmm
+//! ```
+//!
+//! Messages are split on `;` and each command is handled in turn, the replies being joined with
+//! `;`. For each command, the first match is used, in this order: error queue query, dialogues
+//! (exact match), property getters and setters, patterns (regular expressions matching the whole
+//! command, the reply may refer to capture groups as `$1` or `${name}`). Unless a dialogue
+//! defines them, `*CLS` clears the error queue and `*RST` restores the default values of the
+//! properties. Unknown commands and invalid property values push an error in the queue, when the
+//! `errors` section is present.
+//!
+//! [`SimulationConfig::install`] makes the simulated bus the default backend, so that
+//! `Instrument::from_visa_string("GPIB0::5::INSTR")` resolves to the simulated DMM.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::config::SimulationConfig;
+//! use linux_gpib_rs::instrument::Board;
+//! use std::sync::Arc;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let yaml = SimulationConfig::from_yaml_str(
+//!         r#"
+//! devices:
+//!   dmm:
+//!     dialogues:
+//!       - q: "*IDN?"
+//!         r: "SIM,DMM,0,1.0"
+//!     properties:
+//!       voltage:
+//!         default: "1.0"
+//!         getter: "VOLT?"
+//!         setter: "VOLT {}"
+//!         max: 10.0
+//!     errors: {}
+//! resources:
+//!   "GPIB0::5::INSTR":
+//!     device: dmm
+//! "#,
+//!     )?;
+//!     let board = Board::with_backend(0, Arc::new(yaml.build()?));
+//!     let dmm = board.find_listeners()?.remove(0);
+//!     assert_eq!(dmm.query("*IDN?\n")?, "SIM,DMM,0,1.0\n");
+//!     dmm.send(b"VOLT 20\n", Default::default())?;
+//!     assert_eq!(dmm.query("VOLT?;SYST:ERR?\n")?, "1.0;-222,\"Data out of range\"\n");
+//!
+//!     let toml = SimulationConfig::from_toml_str(
+//!         r#"
+//! [devices.source]
+//! patterns = [{ q = "SOUR(\\d):FREQ\\?", r = "1E3" }]
+//!
+//! [resources."GPIB0::7::INSTR"]
+//! device = "source"
+//! "#,
+//!     )?;
+//!     let board = Board::with_backend(0, Arc::new(toml.build()?));
+//!     let source = board.find_listeners()?.remove(0);
+//!     assert_eq!(source.query("SOUR2:FREQ?\n")?, "1E3\n");
+//!     Ok(())
+//! }
+//! ```
+//!
+
+use crate::backend::set_default_backend;
+use crate::backend::sim::{Reply, SimulatedBus, SimulatedDevice};
+use crate::error::GpibError;
+use crate::resource::ResourceName;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Status byte bit set while the error queue is not empty.
+const EAV: u8 = 0x04;
+
+/// Content of a simulation file
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulationConfig {
+    /// Device models, by name
+    #[serde(default)]
+    pub devices: BTreeMap<String, DeviceConfig>,
+    /// Device model connected at each resource, by VISA resource string
+    #[serde(default)]
+    pub resources: BTreeMap<String, ResourceConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceConfig {
+    pub device: String,
+}
+
+/// Model of a simulated device
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// Character ending incoming messages (empty for EOI only)
+    #[serde(default = "default_termination")]
+    pub read_termination: String,
+    /// Appended to every reply
+    #[serde(default = "default_termination")]
+    pub write_termination: String,
+    /// Default delay before replies are available, in milliseconds
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub dialogues: Vec<Dialogue>,
+    #[serde(default)]
+    pub patterns: Vec<Dialogue>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, PropertyConfig>,
+    pub errors: Option<ErrorQueueConfig>,
+}
+
+/// Command and optional reply
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dialogue {
+    pub q: String,
+    pub r: Option<String>,
+    /// Delay of the reply to this command, instead of the delay of the device. The reply to a
+    /// compound message is delayed as much as its slowest command.
+    pub delay_ms: Option<u64>,
+}
+
+/// Value which can be set and queried
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PropertyConfig {
+    pub default: String,
+    /// Query returning the current value
+    pub getter: String,
+    /// Command setting the value, `{}` standing for the value (e.g. `VOLT {}`)
+    pub setter: String,
+    /// Optional reply to the setter
+    pub setter_response: Option<String>,
+    /// Minimum value, for numeric properties
+    pub min: Option<f64>,
+    /// Maximum value, for numeric properties
+    pub max: Option<f64>,
+    /// Allowed values, for enumerated properties
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+/// SCPI-like error queue
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ErrorQueueConfig {
+    /// Query returning the oldest error
+    #[serde(default = "default_error_query")]
+    pub query: String,
+    /// Reply to the query when the queue is empty
+    #[serde(default = "default_no_error")]
+    pub no_error: String,
+    /// Error pushed on unknown commands
+    #[serde(default = "default_command_error")]
+    pub command_error: Option<String>,
+    /// Error pushed on invalid property values
+    #[serde(default = "default_invalid_value")]
+    pub invalid_value: Option<String>,
+    /// Errors in the queue at power on
+    #[serde(default)]
+    pub initial: Vec<String>,
+}
+
+fn default_termination() -> String {
+    "\n".to_owned()
+}
+
+fn default_error_query() -> String {
+    "SYST:ERR?".to_owned()
+}
+
+fn default_no_error() -> String {
+    "0,\"No error\"".to_owned()
+}
+
+fn default_command_error() -> Option<String> {
+    Some("-113,\"Undefined header\"".to_owned())
+}
+
+fn default_invalid_value() -> Option<String> {
+    Some("-222,\"Data out of range\"".to_owned())
+}
+
+impl SimulationConfig {
+    pub fn from_yaml_str(content: &str) -> Result<Self, GpibError> {
+        serde_yaml::from_str(content)
+            .map_err(|e| GpibError::ValueError(format!("Invalid simulation configuration ({})", e)))
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self, GpibError> {
+        toml::from_str(content)
+            .map_err(|e| GpibError::ValueError(format!("Invalid simulation configuration ({})", e)))
+    }
+
+    /// Loads a `.yaml`, `.yml` or `.toml` file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, GpibError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            GpibError::ValueError(format!("Unable to read {} ({})", path.display(), e))
+        })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => Self::from_yaml_str(&content),
+            Some("toml") => Self::from_toml_str(&content),
+            _ => Err(GpibError::ValueError(format!(
+                "Unknown simulation file format for {} (expected .yaml, .yml or .toml).",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Builds a simulated bus with a device at each resource
+    pub fn build(&self) -> Result<SimulatedBus, GpibError> {
+        let bus = SimulatedBus::new();
+        for (resource, resource_config) in &self.resources {
+            let device_config = self.devices.get(&resource_config.device).ok_or_else(|| {
+                GpibError::ValueError(format!(
+                    "Resource {} refers to unknown device '{}'.",
+                    resource, resource_config.device
+                ))
+            })?;
+            let resource: ResourceName = resource.parse()?;
+            let (Some(primary_address), Some(secondary_address)) =
+                (resource.primary_address(), resource.secondary_address())
+            else {
+                return Err(GpibError::ValueError(format!(
+                    "Simulated devices must be connected to INSTR resources (got {}).",
+                    resource
+                )));
+            };
+            bus.add_device(
+                resource.board(),
+                primary_address,
+                secondary_address,
+                Box::new(ConfiguredDevice::new(device_config)?),
+            );
+        }
+        Ok(bus)
+    }
+
+    /// Builds the simulated bus, and installs it as default backend
+    pub fn install(&self) -> Result<Arc<SimulatedBus>, GpibError> {
+        let bus = Arc::new(self.build()?);
+        set_default_backend(bus.clone());
+        Ok(bus)
+    }
+}
+
+fn single_byte(name: &str, value: &str) -> Result<Option<u8>, GpibError> {
+    match value.as_bytes() {
+        [] => Ok(None),
+        [byte] => Ok(Some(*byte)),
+        _ => Err(GpibError::ValueError(format!(
+            "{} must be a single character ('{}').",
+            name, value
+        ))),
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, GpibError> {
+    Regex::new(pattern)
+        .map_err(|e| GpibError::ValueError(format!("Invalid pattern '{}' ({})", pattern, e)))
+}
+
+struct Response {
+    reply: Option<String>,
+    delay: Option<Duration>,
+}
+
+struct Property {
+    config: PropertyConfig,
+    setter: Regex,
+    value: String,
+}
+
+impl Property {
+    fn new(name: &str, config: &PropertyConfig) -> Result<Self, GpibError> {
+        let Some((before, after)) = config.setter.split_once("{}") else {
+            return Err(GpibError::ValueError(format!(
+                "Setter of property {} must contain {{}} ('{}').",
+                name, config.setter
+            )));
+        };
+        let setter = compile(&format!(
+            "^{}(.+?){}$",
+            regex::escape(before),
+            regex::escape(after)
+        ))?;
+        Ok(Self {
+            config: config.clone(),
+            setter,
+            value: config.default.clone(),
+        })
+    }
+
+    fn accepts(&self, value: &str) -> bool {
+        if !self.config.values.is_empty() {
+            return self.config.values.iter().any(|v| v == value);
+        }
+        if self.config.min.is_none() && self.config.max.is_none() {
+            return true;
+        }
+        match value.parse::<f64>() {
+            Ok(x) => {
+                self.config.min.is_none_or(|min| x >= min)
+                    && self.config.max.is_none_or(|max| x <= max)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Simulated device following a [`DeviceConfig`]
+pub struct ConfiguredDevice {
+    read_termination: Option<u8>,
+    write_termination: Vec<u8>,
+    delay: Duration,
+    dialogues: Vec<Dialogue>,
+    patterns: Vec<(Regex, Dialogue)>,
+    properties: Vec<Property>,
+    errors: Option<ErrorQueueConfig>,
+    error_queue: VecDeque<String>,
+}
+
+impl ConfiguredDevice {
+    pub fn new(config: &DeviceConfig) -> Result<Self, GpibError> {
+        let patterns = config
+            .patterns
+            .iter()
+            // the whole command must match, as dialogues and property setters
+            .map(|dialogue| Ok((compile(&format!("^(?:{})$", dialogue.q))?, dialogue.clone())))
+            .collect::<Result<Vec<_>, GpibError>>()?;
+        let properties = config
+            .properties
+            .iter()
+            .map(|(name, property)| Property::new(name, property))
+            .collect::<Result<Vec<_>, GpibError>>()?;
+        let error_queue = config
+            .errors
+            .as_ref()
+            .map(|errors| errors.initial.iter().cloned().collect())
+            .unwrap_or_default();
+        Ok(Self {
+            read_termination: single_byte("read_termination", &config.read_termination)?,
+            write_termination: config.write_termination.as_bytes().to_vec(),
+            delay: Duration::from_millis(config.delay_ms),
+            dialogues: config.dialogues.clone(),
+            patterns,
+            properties,
+            errors: config.errors.clone(),
+            error_queue,
+        })
+    }
+
+    fn push_error(&mut self, error: Option<String>) {
+        if let Some(error) = error {
+            self.error_queue.push_back(error);
+        }
+    }
+
+    fn handle_command(&mut self, command: &str) -> Option<Response> {
+        if let Some(errors) = &self.errors
+            && command == errors.query
+        {
+            let reply = self
+                .error_queue
+                .pop_front()
+                .unwrap_or_else(|| errors.no_error.clone());
+            return Some(Response {
+                reply: Some(reply),
+                delay: None,
+            });
+        }
+        if let Some(dialogue) = self.dialogues.iter().find(|d| d.q == command) {
+            return Some(Response {
+                reply: dialogue.r.clone(),
+                delay: dialogue.delay_ms.map(Duration::from_millis),
+            });
+        }
+        if let Some(property) = self.properties.iter().find(|p| p.config.getter == command) {
+            return Some(Response {
+                reply: Some(property.value.clone()),
+                delay: None,
+            });
+        }
+        let setter = self.properties.iter_mut().find_map(|property| {
+            let value = property.setter.captures(command)?.get(1)?.as_str().trim();
+            Some((property, value.to_owned()))
+        });
+        if let Some((property, value)) = setter {
+            let reply = property.config.setter_response.clone();
+            if property.accepts(&value) {
+                property.value = value;
+            } else {
+                let error = self.errors.as_ref().and_then(|e| e.invalid_value.clone());
+                self.push_error(error);
+            }
+            return Some(Response { reply, delay: None });
+        }
+        for (pattern, dialogue) in &self.patterns {
+            if let Some(captures) = pattern.captures(command) {
+                let reply = dialogue.r.as_ref().map(|r| {
+                    let mut expanded = String::new();
+                    captures.expand(r, &mut expanded);
+                    expanded
+                });
+                return Some(Response {
+                    reply,
+                    delay: dialogue.delay_ms.map(Duration::from_millis),
+                });
+            }
+        }
+        if self.errors.is_some() && command.eq_ignore_ascii_case("*CLS") {
+            self.error_queue.clear();
+            return Some(Response {
+                reply: None,
+                delay: None,
+            });
+        }
+        if command.eq_ignore_ascii_case("*RST") {
+            for property in self.properties.iter_mut() {
+                property.value = property.config.default.clone();
+            }
+            return Some(Response {
+                reply: None,
+                delay: None,
+            });
+        }
+        None
+    }
+}
+
+impl SimulatedDevice for ConfiguredDevice {
+    fn handle_message(&mut self, message: &[u8]) -> Option<Reply> {
+        let message = String::from_utf8_lossy(message);
+        let mut replies = Vec::new();
+        let mut delay = None;
+        for command in message.split(';').map(str::trim).filter(|c| !c.is_empty()) {
+            match self.handle_command(command) {
+                Some(response) => {
+                    let command_delay = response.delay.unwrap_or(self.delay);
+                    delay = Some(delay.map_or(command_delay, |d: Duration| d.max(command_delay)));
+                    replies.extend(response.reply);
+                }
+                None => {
+                    log::debug!("Simulated device: unknown command '{}'", command);
+                    let error = self.errors.as_ref().and_then(|e| e.command_error.clone());
+                    self.push_error(error);
+                }
+            }
+        }
+        if replies.is_empty() {
+            None
+        } else {
+            Some(Reply::from(replies.join(";")).with_delay(delay.unwrap_or(self.delay)))
+        }
+    }
+
+    fn status_byte(&self) -> u8 {
+        if self.error_queue.is_empty() { 0 } else { EAV }
+    }
+
+    fn read_termination(&self) -> Option<u8> {
+        self.read_termination
+    }
+
+    fn write_termination(&self) -> Vec<u8> {
+        self.write_termination.clone()
+    }
+}
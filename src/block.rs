@@ -0,0 +1,203 @@
+//!
+//! IEEE 488.2 arbitrary block data.
+//!
+//! Definite length blocks are sent as `#<n><length><data>`, where `<n>` is the number of digits
+//! of `<length>`. Indefinite length blocks are sent as `#0<data>`, and end with a newline sent
+//! together with EOI.
+//!
+//! ```rust
+//! use linux_gpib_rs::block;
+//!
+//! let message = block::encode(b"\x01\n\x02");
+//! assert_eq!(message, b"#13\x01\n\x02");
+//! assert_eq!(block::decode(&message).unwrap(), b"\x01\n\x02");
+//! assert_eq!(block::decode(b"#0abc\n").unwrap(), b"abc");
+//! ```
+//!
+
+use crate::error::GpibError;
+
+/// Number of bytes requested at once for the payload.
+const CHUNK_SIZE: usize = 65536;
+
+/// Number of bytes requested at once for the trailing terminator.
+const TERMINATOR_CHUNK_SIZE: usize = 16;
+
+/// Maximum number of bytes reserved from the block header. Larger payloads grow the buffer as
+/// they arrive, so that a garbled length does not allocate up to 1 GB upfront.
+const MAX_RESERVE: usize = 4 * 1024 * 1024;
+
+/// Encodes data as a definite length block
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let length = data.len().to_string();
+    let mut message = Vec::with_capacity(2 + length.len() + data.len());
+    message.push(b'#');
+    message.extend(length.len().to_string().as_bytes());
+    message.extend(length.as_bytes());
+    message.extend(data);
+    message
+}
+
+/// Decodes a complete message holding a definite or indefinite length block.
+/// Bytes before the `#` (for example a command header echoed by the instrument), and the
+/// terminator after the block, are discarded.
+pub fn decode(message: &[u8]) -> Result<Vec<u8>, GpibError> {
+    let mut reader = BlockReader::new();
+    let mut remaining = message;
+    while let Some(wanted) = reader.wanted() {
+        if remaining.is_empty() {
+            return Err(reader.truncated());
+        }
+        let count = wanted.min(remaining.len());
+        let (chunk, rest) = remaining.split_at(count);
+        remaining = rest;
+        reader.feed(chunk, remaining.is_empty())?;
+    }
+    Ok(reader.into_data())
+}
+
+enum State {
+    Hash,
+    DigitCount,
+    Length { digits: usize, length: Vec<u8> },
+    Definite { remaining: usize },
+    Indefinite,
+    Terminator,
+    Done,
+}
+
+/// Incremental block parser, fed with the data read from the instrument.
+///
+/// `wanted` gives the maximum number of bytes to read next, so that no byte following the block
+/// is consumed. Reads may return fewer bytes, for instance when an EOS character is received.
+pub(crate) struct BlockReader {
+    state: State,
+    data: Vec<u8>,
+}
+
+impl BlockReader {
+    pub(crate) fn new() -> Self {
+        BlockReader {
+            state: State::Hash,
+            data: Vec::new(),
+        }
+    }
+
+    /// Maximum number of bytes to read next, or None if the block is complete
+    pub(crate) fn wanted(&self) -> Option<usize> {
+        match &self.state {
+            State::Hash | State::DigitCount => Some(1),
+            State::Length { digits, length } => Some(digits - length.len()),
+            State::Definite { remaining } => Some((*remaining).min(CHUNK_SIZE)),
+            State::Indefinite => Some(CHUNK_SIZE),
+            State::Terminator => Some(TERMINATOR_CHUNK_SIZE),
+            State::Done => None,
+        }
+    }
+
+    /// Handles bytes read from the instrument. `end` is true if the read ended with EOI.
+    pub(crate) fn feed(&mut self, bytes: &[u8], end: bool) -> Result<(), GpibError> {
+        let mut i = 0;
+        while i < bytes.len() {
+            let byte = bytes[i];
+            i += 1;
+            match &mut self.state {
+                State::Hash => {
+                    if byte == b'#' {
+                        self.state = State::DigitCount;
+                    } else {
+                        log::debug!("Skipping byte {:#04x} before block header", byte);
+                    }
+                }
+                State::DigitCount => {
+                    self.state = match byte {
+                        b'0' => State::Indefinite,
+                        b'1'..=b'9' => State::Length {
+                            digits: (byte - b'0').into(),
+                            length: Vec::new(),
+                        },
+                        _ => {
+                            return Err(GpibError::ValueError(format!(
+                                "Invalid block header: expected a digit after '#', got {:#04x}.",
+                                byte
+                            )));
+                        }
+                    };
+                }
+                State::Length { digits, length } => {
+                    if !byte.is_ascii_digit() {
+                        return Err(GpibError::ValueError(format!(
+                            "Invalid block header: expected a digit in the block length, got {:#04x}.",
+                            byte
+                        )));
+                    }
+                    length.push(byte);
+                    if length.len() == *digits {
+                        let remaining: usize =
+                            std::str::from_utf8(length)?.parse().map_err(|e| {
+                                GpibError::ValueError(format!("Invalid block length ({:?})", e))
+                            })?;
+                        self.data.reserve(remaining.min(MAX_RESERVE));
+                        self.state = if remaining == 0 {
+                            State::Terminator
+                        } else {
+                            State::Definite { remaining }
+                        };
+                    }
+                }
+                State::Definite { remaining } => {
+                    // copy the rest of the payload at once
+                    let start = i - 1;
+                    let count = (*remaining).min(bytes.len() - start);
+                    self.data.extend(&bytes[start..start + count]);
+                    i = start + count;
+                    *remaining -= count;
+                    if *remaining == 0 {
+                        self.state = State::Terminator;
+                    }
+                }
+                State::Indefinite => {
+                    self.data.extend(&bytes[i - 1..]);
+                    break;
+                }
+                State::Terminator | State::Done => {
+                    break;
+                }
+            }
+        }
+        if end {
+            match self.state {
+                State::Indefinite => {
+                    if self.data.last() == Some(&b'\n') {
+                        self.data.pop();
+                    }
+                    self.state = State::Done;
+                }
+                State::Terminator | State::Done => self.state = State::Done,
+                _ => return Err(self.truncated()),
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn truncated(&self) -> GpibError {
+        match &self.state {
+            State::Definite { remaining } => GpibError::ValueError(format!(
+                "Block truncated: {} bytes received, {} bytes missing.",
+                self.data.len(),
+                remaining
+            )),
+            State::Indefinite => {
+                GpibError::ValueError("Indefinite block not terminated with EOI.".to_owned())
+            }
+            State::Terminator | State::Done => {
+                GpibError::ValueError("Block not terminated with EOI.".to_owned())
+            }
+            _ => GpibError::ValueError("Incomplete block header.".to_owned()),
+        }
+    }
+
+    pub(crate) fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
@@ -1,98 +1,249 @@
-use linux_gpib_rs::error::GpibError;
-use linux_gpib_rs::instrument::{Board, Parameters};
-use linux_gpib_rs::lowlevel::traditional::{ibdev, ibrd, ibwrt};
+//!
+//! scan_gpib: command line tool to inspect and debug a GPIB bus.
+//!
+//! Instruments are designated by a VISA resource name (e.g. `GPIB0::5::INSTR`), or by an alias
+//! defined in the file given with `--aliases`.
+//!
+
+use clap::{Parser, Subcommand};
+use linux_gpib_rs::error::{GpibError, IbError};
+use linux_gpib_rs::instrument::{Board, Instrument};
+use linux_gpib_rs::resource::ResourceManager;
 use linux_gpib_rs::types::IbSendEOI;
-use linux_gpib_rs::types::{IbEosMode, IbTimeout, PrimaryAddress, SecondaryAddress};
-#[cfg(feature = "async-tokio")]
-use tokio::task::JoinSet;
-
-fn lowlevel_query() -> Result<(), GpibError> {
-    let ud = ibdev(
-        0,
-        PrimaryAddress::new(1)?,
-        SecondaryAddress::new(0)?,
-        IbTimeout::T1s,
-        IbSendEOI::default(),
-        IbEosMode::default(),
-    )?;
-    ibwrt(ud, b"*IDN?\n")?;
-    let mut buffer: [u8; 256] = [0; 256];
-    ibrd(ud, &mut buffer)?;
-    let iden = String::from_utf8(buffer.to_vec())?;
-    println!("{iden}");
-    Ok(())
+use std::fmt;
+use std::io::Write;
+use std::os::raw::c_int;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const EXIT_CODES: &str = "Exit codes:
+  0  success
+  1  GPIB error reported by the driver, or any other error
+  2  invalid arguments (resource name, aliases or simulation file)
+  3  timeout
+  4  error reported by the instrument";
+
+#[derive(Parser)]
+#[command(
+    name = "scan_gpib",
+    version,
+    about = "Inspect and debug a GPIB bus",
+    after_help = EXIT_CODES
+)]
+struct Cli {
+    /// File with one `alias = resource` definition per line
+    #[arg(long, global = true, value_name = "FILE")]
+    aliases: Option<PathBuf>,
+
+    /// Simulation file (.yaml, .yml or .toml) used instead of the GPIB boards
+    #[cfg(feature = "sim-config")]
+    #[arg(long, global = true, value_name = "FILE")]
+    sim: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn multidevice_query() -> Result<(), GpibError> {
-    let board = Board::with_board_number(0);
-    let instruments = board.find_listeners()?;
-    board.send_list(&instruments, b"*IDN?\n", IbSendEOI::default())?;
-    for instr in instruments {
-        let iden = instr.receive()?;
-        println!("{:>20} {}", instr.visa_string(), iden.trim());
+#[derive(Subcommand)]
+enum Command {
+    /// Lists the listeners on the board, with their identification (*IDN?)
+    Scan {
+        #[arg(long, default_value_t = 0)]
+        board: c_int,
+        /// Probes the secondary addresses of the listeners as well
+        #[arg(long)]
+        secondary: bool,
+    },
+    /// Sends a command followed by a newline, and prints the reply
+    Query { resource: String, command: String },
+    /// Sends a command followed by a newline
+    Write { resource: String, command: String },
+    /// Reads until EOI, and prints the data
+    Read {
+        resource: String,
+        /// Writes the bytes to stdout as received, instead of decoding them as UTF-8
+        #[arg(long)]
+        binary: bool,
+    },
+    /// Serial polls the instrument, and prints its status byte
+    Spoll { resource: String },
+    /// Clears the instrument (selected device clear)
+    Clear { resource: String },
+    /// Triggers the instrument (group execute trigger)
+    Trigger { resource: String },
+    /// Performs interface clear, the board becomes controller-in-charge
+    Ifc {
+        #[arg(long, default_value_t = 0)]
+        board: c_int,
+    },
+    /// Prints the state of the bus lines
+    Lines {
+        #[arg(long, default_value_t = 0)]
+        board: c_int,
+    },
+    /// Returns the instrument to local mode
+    Local { resource: String },
+    /// Puts the instrument in remote mode
+    Remote { resource: String },
+}
+
+/// Error of the command line, with the exit code of its kind
+enum CliError {
+    /// Invalid resource name, aliases or simulation file
+    Usage(GpibError),
+    /// Failure of the command itself
+    Command(GpibError),
+}
+
+impl From<GpibError> for CliError {
+    fn from(error: GpibError) -> Self {
+        CliError::Command(error)
     }
-    Ok(())
 }
 
-#[cfg(feature = "async-tokio")]
-async fn asynchronous_simple() -> Result<(), GpibError> {
-    let board = Board::with_board_number(0);
-    let instruments = board.find_listeners()?;
-    for instr in instruments {
-        let handle = instr.open(Parameters::default())?;
-        let visa_string = instr.visa_string();
-        let iden = handle.query("*IDN?\n").await?;
-        println!("{:>20} {}", visa_string, iden.trim());
+impl fmt::Debug for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(error) | CliError::Command(error) => write!(f, "{:?}", error),
+        }
     }
-    Ok(())
 }
 
-#[cfg(feature = "async-tokio")]
-async fn asynchronous_bis() -> Result<(), GpibError> {
-    let board = Board::with_board_number(0);
-    let instruments = board.find_listeners()?;
-    let mut set = JoinSet::<Result<(String, String), GpibError>>::new();
-    for instr in instruments {
-        let handle = instr.open(Parameters::default())?;
-        let visa_string = instr.visa_string();
-        set.spawn(async move {
-            let iden = handle.query("*IDN?\n").await?;
-            Ok((visa_string, iden))
-        });
+fn resource_manager(cli: &Cli) -> Result<ResourceManager, GpibError> {
+    #[cfg(feature = "sim-config")]
+    let mut rm = match &cli.sim {
+        Some(path) => {
+            let config = linux_gpib_rs::backend::sim::config::SimulationConfig::from_file(path)?;
+            ResourceManager::with_backend(std::sync::Arc::new(config.build()?))
+        }
+        None => ResourceManager::new(),
+    };
+    #[cfg(not(feature = "sim-config"))]
+    let mut rm = ResourceManager::new();
+    if let Some(path) = &cli.aliases {
+        rm.load_aliases(path)?;
     }
-    while let Some(Ok(val)) = set.join_next().await {
-        if let Ok((visa_string, iden)) = val {
-            println!("{:>20} {}", visa_string, iden.trim());
+    Ok(rm)
+}
+
+fn scan(board: &Board, secondary: bool) -> Result<(), GpibError> {
+    let mut instruments = match board.find_listeners() {
+        Ok(instruments) => instruments,
+        Err(GpibError::DriverError(_, IbError::ENOL | IbError::EBUS | IbError::ETAB)) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    if secondary {
+        let mut primary_addresses = Vec::new();
+        for instr in &instruments {
+            let primary_address = instr.resource_name()?.primary_address();
+            if !primary_addresses.contains(&primary_address) {
+                primary_addresses.push(primary_address);
+            }
+        }
+        for primary_address in primary_addresses.into_iter().flatten() {
+            for instr in board.find_secondary_listeners(primary_address)? {
+                if !instruments
+                    .iter()
+                    .any(|other| other.visa_string() == instr.visa_string())
+                {
+                    instruments.push(instr);
+                }
+            }
+        }
+    }
+    if instruments.is_empty() {
+        eprintln!("No listener on {}.", board);
+    }
+    for instr in instruments {
+        match instr.query("*IDN?\n") {
+            Ok(iden) => println!("{:>20} {}", instr.visa_string(), iden.trim()),
+            Err(e) => println!("{:>20} (no identification: {})", instr.visa_string(), e),
         }
     }
     Ok(())
 }
 
-#[cfg(feature = "async-tokio")]
-#[tokio::main]
-async fn main() {
-    env_logger::init();
-
-    println!("*** lowlevel_query ***");
-    lowlevel_query().unwrap();
+fn read(instr: &Instrument, binary: bool) -> Result<(), GpibError> {
+    if binary {
+        let data = instr.receive_raw()?;
+        let mut stdout = std::io::stdout();
+        stdout
+            .write_all(&data)
+            .and_then(|_| stdout.flush())
+            .map_err(|e| GpibError::ValueError(format!("Unable to write to stdout ({})", e)))
+    } else {
+        print!("{}", instr.receive()?);
+        Ok(())
+    }
+}
 
-    println!("*** Multidevice query ***");
-    multidevice_query().unwrap();
+fn lines(board: &Board) -> Result<(), GpibError> {
+    let status = board.lines()?;
+    for (name, valid, asserted) in [
+        ("DAV", status.valid_dav, status.bus_dav),
+        ("NDAC", status.valid_ndac, status.bus_ndac),
+        ("NRFD", status.valid_nrfd, status.bus_nrfd),
+        ("IFC", status.valid_ifc, status.bus_ifc),
+        ("REN", status.valid_ren, status.bus_ren),
+        ("SRQ", status.valid_srq, status.bus_srq),
+        ("ATN", status.valid_atn, status.bus_atn),
+        ("EOI", status.valid_eoi, status.bus_eoi),
+    ] {
+        let state = match (valid, asserted) {
+            (false, _) => "not monitored",
+            (true, true) => "asserted",
+            (true, false) => "unasserted",
+        };
+        println!("{:<5} {}", name, state);
+    }
+    Ok(())
+}
 
-    println!("*** Asynchronous simple ***");
-    asynchronous_simple().await.unwrap();
+fn run(cli: &Cli) -> Result<(), CliError> {
+    let rm = resource_manager(cli).map_err(CliError::Usage)?;
+    let open = |resource: &str| rm.open(resource).map_err(CliError::Usage);
+    match &cli.command {
+        Command::Scan { board, secondary } => Ok(scan(&rm.board(*board), *secondary)?),
+        Command::Query { resource, command } => {
+            let reply = open(resource)?.query(&format!("{}\n", command))?;
+            println!("{}", reply.trim_end());
+            Ok(())
+        }
+        Command::Write { resource, command } => {
+            Ok(open(resource)?.send(format!("{}\n", command).as_bytes(), IbSendEOI::default())?)
+        }
+        Command::Read { resource, binary } => Ok(read(&open(resource)?, *binary)?),
+        Command::Spoll { resource } => {
+            let status_byte = open(resource)?.serial_poll()?;
+            println!("{:#04x} {:?}", status_byte.bits(), status_byte);
+            Ok(())
+        }
+        Command::Clear { resource } => Ok(open(resource)?.clear()?),
+        Command::Trigger { resource } => Ok(open(resource)?.trigger()?),
+        Command::Ifc { board } => Ok(rm.board(*board).interface_clear()?),
+        Command::Lines { board } => Ok(lines(&rm.board(*board))?),
+        Command::Local { resource } => Ok(open(resource)?.enable_local()?),
+        Command::Remote { resource } => Ok(open(resource)?.enable_remote()?),
+    }
+}
 
-    println!("**** Asynchronous bis ***");
-    asynchronous_bis().await.unwrap();
+fn exit_code(error: &CliError) -> u8 {
+    match error {
+        CliError::Usage(_) => 2,
+        CliError::Command(GpibError::DriverError(_, IbError::EABO) | GpibError::Timeout) => 3,
+        CliError::Command(GpibError::InstrumentError(_)) => 4,
+        CliError::Command(_) => 1,
+    }
 }
 
-#[cfg(not(feature = "async-tokio"))]
-fn main() {
+fn main() -> ExitCode {
     env_logger::init();
-
-    println!("*** lowlevel_query ***");
-    lowlevel_query().unwrap();
-
-    println!("*** Multidevice query ***");
-    multidevice_query().unwrap();
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("scan_gpib: {:?}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
 }
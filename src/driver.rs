@@ -0,0 +1,249 @@
+//!
+//! Instrument drivers, selected from the identification of the instruments.
+//!
+//! A [`DriverRegistry`] associates manufacturer and model patterns with driver constructors.
+//! [`DriverRegistry::scan`] identifies the listeners of a board with `*IDN?`, and returns a
+//! driver for each recognized instrument. The registry is generic over the type of driver it
+//! returns, so that a registry of `dyn Multimeter` drivers hands back `Box<dyn Multimeter>`.
+//!
+//! The instrument classes ([`multimeter`], [`power_supply`], [`function_generator`] and
+//! [`oscilloscope`]) are traits extending [`InstrumentDriver`], each with an implementation
+//! using standard SCPI commands, so that instruments of the same class are interchangeable.
+//! Instruments with their own command sets have dedicated drivers ([`lockin`],
+//! [`lakeshore`]). Each module has a `registry()` of the instruments it supports, so that these
+//! come out of a scan as ready drivers (e.g. [`multimeter::registry`]).
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::driver::{Detected, DriverRegistry, InstrumentDriver};
+//! use linux_gpib_rs::ieee4882::Identity;
+//! use linux_gpib_rs::instrument::{Board, Instrument};
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! struct SimDmm {
+//!     instrument: Instrument,
+//!     identity: Identity,
+//! }
+//!
+//! impl InstrumentDriver for SimDmm {
+//!     fn identity(&self) -> &Identity {
+//!         &self.identity
+//!     }
+//! }
+//!
+//! let bus = SimulatedBus::new()
+//!     .with_device(0, PrimaryAddress::new(1).unwrap(), SecondaryAddress::default(), |_: &[u8]| {
+//!         Some(Reply::from("SIM,DMM-100,1234,1.0"))
+//!     })
+//!     .with_device(0, PrimaryAddress::new(2).unwrap(), SecondaryAddress::default(), |_: &[u8]| {
+//!         Some(Reply::from("OTHER,PSU,0,1.0"))
+//!     });
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let registry = DriverRegistry::new().with_driver("sim", "DMM-*", |instrument, identity| {
+//!     Ok(Box::new(SimDmm { instrument, identity }) as Box<dyn InstrumentDriver>)
+//! });
+//! let detected = registry.scan(&board).unwrap();
+//! assert!(matches!(&detected[0], Detected::Driver(dmm) if dmm.identity().model == "DMM-100"));
+//! assert!(matches!(&detected[1], Detected::Unknown(_, identity) if identity.model == "PSU"));
+//! ```
+//!
+
+pub mod function_generator;
+pub mod lakeshore;
+pub mod lockin;
+pub mod multimeter;
+pub mod oscilloscope;
+pub mod power_supply;
+
+use crate::error::GpibError;
+use crate::ieee4882::{Identity, Ieee4882};
+use crate::instrument::{Board, Instrument};
+use std::fmt;
+
+/// Driver of an instrument
+pub trait InstrumentDriver {
+    /// Identification of the instrument, as returned by `*IDN?`
+    fn identity(&self) -> &Identity;
+}
+
+type Constructor<D> = Box<dyn Fn(Instrument, Identity) -> Result<Box<D>, GpibError> + Send + Sync>;
+
+struct Entry<D: ?Sized> {
+    manufacturer: String,
+    model: String,
+    constructor: Constructor<D>,
+}
+
+/// Driver constructors, selected by manufacturer and model
+pub struct DriverRegistry<D: ?Sized = dyn InstrumentDriver> {
+    entries: Vec<Entry<D>>,
+}
+
+impl<D: InstrumentDriver + ?Sized> DriverRegistry<D> {
+    pub fn new() -> Self {
+        DriverRegistry {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Registers a driver for the instruments whose manufacturer and model match the patterns.
+    /// Patterns are case-insensitive, and `*` matches any sequence of characters (e.g. `3446*`).
+    /// The first registered driver matching an instrument is used.
+    pub fn with_driver(
+        mut self,
+        manufacturer: &str,
+        model: &str,
+        constructor: impl Fn(Instrument, Identity) -> Result<Box<D>, GpibError> + Send + Sync + 'static,
+    ) -> Self {
+        self.register(manufacturer, model, constructor);
+        self
+    }
+
+    /// Registers a driver, as `with_driver`
+    pub fn register(
+        &mut self,
+        manufacturer: &str,
+        model: &str,
+        constructor: impl Fn(Instrument, Identity) -> Result<Box<D>, GpibError> + Send + Sync + 'static,
+    ) {
+        self.entries.push(Entry {
+            manufacturer: manufacturer.to_owned(),
+            model: model.to_owned(),
+            constructor: Box::new(constructor),
+        });
+    }
+
+    /// True if a driver is registered for the identity
+    pub fn supports(&self, identity: &Identity) -> bool {
+        self.find(identity).is_some()
+    }
+
+    /// Identifies the instrument, and returns its driver
+    pub fn open(&self, instrument: Instrument) -> Result<Box<D>, GpibError> {
+        let identity = instrument.identify()?;
+        self.open_identified(instrument, identity)
+    }
+
+    /// Returns the driver of an instrument already identified
+    pub fn open_identified(
+        &self,
+        instrument: Instrument,
+        identity: Identity,
+    ) -> Result<Box<D>, GpibError> {
+        match self.find(&identity) {
+            Some(entry) => (entry.constructor)(instrument, identity),
+            None => Err(GpibError::ValueError(format!(
+                "No driver for {} ({}).",
+                instrument, identity
+            ))),
+        }
+    }
+
+    /// Identifies the listeners of the board, and opens their drivers
+    pub fn scan(&self, board: &Board) -> Result<Vec<Detected<D>>, GpibError> {
+        Ok(board
+            .find_listeners()?
+            .into_iter()
+            .map(|instrument| self.detect(instrument))
+            .collect())
+    }
+
+    fn detect(&self, instrument: Instrument) -> Detected<D> {
+        let identity = match instrument.identify() {
+            Ok(identity) => identity,
+            Err(e) => return Detected::Failed(instrument, e),
+        };
+        match self.find(&identity) {
+            Some(entry) => match (entry.constructor)(instrument.clone(), identity) {
+                Ok(driver) => Detected::Driver(driver),
+                Err(e) => Detected::Failed(instrument, e),
+            },
+            None => Detected::Unknown(instrument, identity),
+        }
+    }
+
+    fn find(&self, identity: &Identity) -> Option<&Entry<D>> {
+        self.entries.iter().find(|entry| {
+            matches_pattern(&entry.manufacturer, &identity.manufacturer)
+                && matches_pattern(&entry.model, &identity.model)
+        })
+    }
+}
+
+impl<D: InstrumentDriver + ?Sized> Default for DriverRegistry<D> {
+    fn default() -> Self {
+        DriverRegistry::new()
+    }
+}
+
+impl<D: ?Sized> fmt::Debug for DriverRegistry<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let patterns: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| format!("{},{}", entry.manufacturer, entry.model))
+            .collect();
+        write!(f, "DriverRegistry({})", patterns.join("; "))
+    }
+}
+
+/// Result of the scan of an instrument
+pub enum Detected<D: ?Sized = dyn InstrumentDriver> {
+    /// Instrument with a registered driver
+    Driver(Box<D>),
+    /// Instrument identified, without a registered driver
+    Unknown(Instrument, Identity),
+    /// Instrument which could not be identified, or whose driver failed to open
+    Failed(Instrument, GpibError),
+}
+
+impl<D: InstrumentDriver + ?Sized> fmt::Debug for Detected<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Detected::Driver(driver) => write!(f, "Driver({:?})", driver.identity()),
+            Detected::Unknown(instrument, identity) => {
+                write!(f, "Unknown({}, {:?})", instrument, identity)
+            }
+            Detected::Failed(instrument, e) => write!(f, "Failed({}, {:?})", instrument, e),
+        }
+    }
+}
+
+/// Manufacturer patterns of the Keysight instruments, under their successive names
+pub(crate) const KEYSIGHT: [&str; 3] = ["HEWLETT-PACKARD", "AGILENT*", "KEYSIGHT*"];
+
+/// Checks that the channel is between 1 and the number of channels
+pub(crate) fn check_channel(channel: u8, channels: u8) -> Result<(), GpibError> {
+    if channel == 0 || channel > channels {
+        Err(GpibError::ValueError(format!(
+            "Invalid channel {}: expected 1 to {}.",
+            channel, channels
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Case-insensitive match, where `*` matches any sequence of characters
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no wildcard
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
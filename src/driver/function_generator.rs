@@ -0,0 +1,241 @@
+//!
+//! Function generators.
+//!
+//! [`ScpiFunctionGenerator`] implements [`FunctionGenerator`] with the `APPLy`, `FUNCtion`,
+//! `FREQuency`, `VOLTage` and `OUTPut` commands of the SCPI standard (e.g. Keysight 33500B and
+//! 33600A). On generators with several outputs, the channel is the numeric suffix of `SOURce`
+//! and `OUTPut`.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::driver::function_generator::{
+//!     FunctionGenerator, ScpiFunctionGenerator, StandardWaveform,
+//! };
+//! use linux_gpib_rs::ieee4882::Ieee4882;
+//! use linux_gpib_rs::instrument::{Board, Parameters};
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::{Arc, Mutex};
+//!
+//! let sent = Arc::new(Mutex::new(Vec::new()));
+//! let log = sent.clone();
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(10).unwrap(),
+//!     SecondaryAddress::default(),
+//!     move |message: &[u8]| {
+//!         log.lock().unwrap().push(String::from_utf8_lossy(message).into_owned());
+//!         match message {
+//!             b"*IDN?" => Some(Reply::from("Agilent Technologies,33522B,MY0000,4.00")),
+//!             b"SOUR2:FUNC?" => Some(Reply::from("SQU")),
+//!             _ => None,
+//!         }
+//!     },
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let handle = board.find_listeners().unwrap()[0].open(Parameters::default()).unwrap();
+//! let identity = handle.identify().unwrap();
+//! let generator = ScpiFunctionGenerator::new(handle, identity).with_channels(2);
+//! generator
+//!     .set_waveform(2, StandardWaveform::Square, 1e3, 2.0, 0.5)
+//!     .unwrap();
+//! assert_eq!(generator.waveform(2).unwrap(), StandardWaveform::Square);
+//! assert_eq!(sent.lock().unwrap()[1], "SOUR2:APPL:SQU 1000,2,0.5");
+//! ```
+//!
+
+use crate::driver::{DriverRegistry, InstrumentDriver, KEYSIGHT, check_channel};
+use crate::error::GpibError;
+use crate::ieee4882::Identity;
+use crate::instrument::{Instrument, InstrumentHandle, Parameters};
+use crate::scpi::ScpiQuery;
+use crate::scpi::command::ToScpi;
+use std::fmt;
+
+crate::scpi_enum! {
+    /// Standard waveform of a function generator
+    pub enum StandardWaveform {
+        Sine = "SINusoid",
+        Square = "SQUare",
+        Triangle = "TRIangle",
+        Ramp = "RAMP",
+        Pulse = "PULSe",
+        Noise = "NOISe",
+        Dc = "DC",
+    }
+}
+
+/// Function generator. Channels are numbered from 1.
+pub trait FunctionGenerator: InstrumentDriver {
+    /// Number of outputs
+    fn channels(&self) -> u8;
+
+    /// Sets the waveform, its frequency (Hz), amplitude (peak-to-peak, V) and offset (V)
+    fn set_waveform(
+        &self,
+        channel: u8,
+        waveform: StandardWaveform,
+        frequency: f64,
+        amplitude: f64,
+        offset: f64,
+    ) -> Result<(), GpibError>;
+
+    fn waveform(&self, channel: u8) -> Result<StandardWaveform, GpibError>;
+
+    /// Frequency in Hz
+    fn frequency(&self, channel: u8) -> Result<f64, GpibError>;
+
+    /// Peak-to-peak amplitude in V
+    fn amplitude(&self, channel: u8) -> Result<f64, GpibError>;
+
+    /// Offset in V
+    fn offset(&self, channel: u8) -> Result<f64, GpibError>;
+
+    fn set_output_enabled(&self, channel: u8, enabled: bool) -> Result<(), GpibError>;
+
+    fn output_enabled(&self, channel: u8) -> Result<bool, GpibError>;
+}
+
+/// Registry of the function generators known to accept the commands of
+/// [`ScpiFunctionGenerator`]: Keysight 33500B and 33600A series, with 2 channels for the models
+/// ending in 2 (e.g. 33522B)
+pub fn registry() -> DriverRegistry<dyn FunctionGenerator> {
+    let mut registry = DriverRegistry::<dyn FunctionGenerator>::new();
+    for manufacturer in KEYSIGHT {
+        for model in ["335*", "336*"] {
+            registry.register(manufacturer, model, |instrument, identity| {
+                let channels = if identity.model.trim_end_matches(['A', 'B']).ends_with('2') {
+                    2
+                } else {
+                    1
+                };
+                Ok(Box::new(
+                    ScpiFunctionGenerator::open(instrument, identity)?.with_channels(channels),
+                ))
+            });
+        }
+    }
+    registry
+}
+
+/// Function generator with the standard SCPI commands
+pub struct ScpiFunctionGenerator {
+    handle: InstrumentHandle,
+    identity: Identity,
+    channels: u8,
+}
+
+impl ScpiFunctionGenerator {
+    /// Single-output function generator (see `with_channels`)
+    pub fn new(handle: InstrumentHandle, identity: Identity) -> Self {
+        ScpiFunctionGenerator {
+            handle,
+            identity,
+            channels: 1,
+        }
+    }
+
+    /// Opens the instrument with the default parameters
+    pub fn open(instrument: Instrument, identity: Identity) -> Result<Self, GpibError> {
+        Ok(ScpiFunctionGenerator::new(
+            instrument.open(Parameters::default())?,
+            identity,
+        ))
+    }
+
+    /// Number of outputs, selected with the numeric suffix of the headers if more than one
+    pub fn with_channels(mut self, channels: u8) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+
+    pub fn into_inner(self) -> InstrumentHandle {
+        self.handle
+    }
+
+    /// Header with the channel suffix (`SOUR2`, `OUTP2`)
+    fn header(&self, node: &str, channel: u8) -> Result<String, GpibError> {
+        check_channel(channel, self.channels)?;
+        if self.channels > 1 {
+            Ok(format!("{}{}", node, channel))
+        } else {
+            Ok(node.to_owned())
+        }
+    }
+}
+
+impl InstrumentDriver for ScpiFunctionGenerator {
+    fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl FunctionGenerator for ScpiFunctionGenerator {
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn set_waveform(
+        &self,
+        channel: u8,
+        waveform: StandardWaveform,
+        frequency: f64,
+        amplitude: f64,
+        offset: f64,
+    ) -> Result<(), GpibError> {
+        self.handle.blocking_write(&format!(
+            "{}:APPL:{} {},{},{}\n",
+            self.header("SOUR", channel)?,
+            waveform.to_scpi(),
+            frequency.to_scpi(),
+            amplitude.to_scpi(),
+            offset.to_scpi()
+        ))
+    }
+
+    fn waveform(&self, channel: u8) -> Result<StandardWaveform, GpibError> {
+        self.handle
+            .query_as(&format!("{}:FUNC?", self.header("SOUR", channel)?))
+    }
+
+    fn frequency(&self, channel: u8) -> Result<f64, GpibError> {
+        self.handle
+            .query_f64(&format!("{}:FREQ?", self.header("SOUR", channel)?))
+    }
+
+    fn amplitude(&self, channel: u8) -> Result<f64, GpibError> {
+        self.handle
+            .query_f64(&format!("{}:VOLT?", self.header("SOUR", channel)?))
+    }
+
+    fn offset(&self, channel: u8) -> Result<f64, GpibError> {
+        self.handle
+            .query_f64(&format!("{}:VOLT:OFFS?", self.header("SOUR", channel)?))
+    }
+
+    fn set_output_enabled(&self, channel: u8, enabled: bool) -> Result<(), GpibError> {
+        self.handle.blocking_write(&format!(
+            "{} {}\n",
+            self.header("OUTP", channel)?,
+            enabled.to_scpi()
+        ))
+    }
+
+    fn output_enabled(&self, channel: u8) -> Result<bool, GpibError> {
+        self.handle
+            .query_bool(&format!("{}?", self.header("OUTP", channel)?))
+    }
+}
+
+impl fmt::Debug for ScpiFunctionGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ScpiFunctionGenerator({}, {:?}, {} channels)",
+            self.handle, self.identity, self.channels
+        )
+    }
+}
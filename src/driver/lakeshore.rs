@@ -0,0 +1,727 @@
+//!
+//! Lake Shore temperature controllers (models 331, 332, 335, 336 and 340).
+//!
+//! The controllers use the Lake Shore command set: readings with `KRDG?` (kelvin), `CRDG?`
+//! (Celsius) and `SRDG?` (sensor units), and control loops configured with `SETP`, `RAMP`,
+//! `RANGE` and `PID`. The loops are called outputs, numbered from 1. The differences between
+//! the models (heater ranges, control input of a loop) are handled by [`LakeShore`], from the
+//! model in the identification of the controller.
+//!
+//! With the `async` feature, `LakeShore::wait_until_stable` polls the temperature asynchronously
+//! until it stays within a tolerance of the setpoint for a given duration.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus, SimulatedDevice};
+//! use linux_gpib_rs::driver::lakeshore::{self, Input, Model, Pid};
+//! use linux_gpib_rs::instrument::Board;
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! /// Temperature halfway to the setpoint at each reading
+//! struct Cryostat {
+//!     setpoint: f64,
+//!     temperature: f64,
+//! }
+//!
+//! impl SimulatedDevice for Cryostat {
+//!     fn handle_message(&mut self, message: &[u8]) -> Option<Reply> {
+//!         let message = String::from_utf8_lossy(message);
+//!         match message.as_ref() {
+//!             "*IDN?" => Some(Reply::from("LSCI,MODEL336,1234567,2.9")),
+//!             "OUTMODE? 1" => Some(Reply::from("1,1,0")),
+//!             "RAMPST? 1" => Some(Reply::from("0")),
+//!             "SETP? 1" => Some(Reply::from(format!("{:+.3}", self.setpoint))),
+//!             "PID? 1" => Some(Reply::from("+50.0,+20.0,+0.0")),
+//!             "KRDG? A" => {
+//!                 self.temperature += (self.setpoint - self.temperature) / 2.0;
+//!                 Some(Reply::from(format!("{:+.4}", self.temperature)))
+//!             }
+//!             _ => {
+//!                 if let Some(setpoint) = message.strip_prefix("SETP 1,") {
+//!                     self.setpoint = setpoint.parse().unwrap();
+//!                 }
+//!                 None
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! let cryostat = Cryostat {
+//!     setpoint: 300.0,
+//!     temperature: 300.0,
+//! };
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(12).unwrap(),
+//!     SecondaryAddress::default(),
+//!     cryostat,
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let controller = lakeshore::registry()
+//!     .open(board.find_listeners().unwrap().remove(0))
+//!     .unwrap();
+//! assert_eq!(controller.model(), Model::Ls336);
+//! assert_eq!(controller.pid(1).unwrap(), Pid::new(50.0, 20.0, 0.0));
+//! assert_eq!(controller.control_input(1).unwrap(), Some(Input::A));
+//! controller.set_setpoint(1, 4.2).unwrap();
+//! assert_eq!(controller.temperature(Input::A).unwrap(), 152.1);
+//! ```
+//!
+
+use crate::driver::{DriverRegistry, InstrumentDriver, check_channel};
+use crate::error::GpibError;
+use crate::ieee4882::{Identity, Ieee4882};
+use crate::instrument::{Instrument, InstrumentHandle, Parameters};
+#[cfg(feature = "async")]
+use crate::scpi::FromScpi;
+use crate::scpi::ScpiQuery;
+use crate::scpi::command::ToScpi;
+use std::fmt;
+use std::time::Duration;
+#[cfg(feature = "async")]
+use std::time::Instant;
+
+/// Model of a Lake Shore temperature controller
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Model {
+    Ls331,
+    Ls332,
+    Ls335,
+    Ls336,
+    Ls340,
+}
+
+impl Model {
+    /// Model from the identification (e.g. `MODEL336`, `MODEL331S`)
+    pub fn from_identity(identity: &Identity) -> Result<Self, GpibError> {
+        let model = identity.model.trim_start_matches("MODEL");
+        if model.starts_with("331") {
+            Ok(Model::Ls331)
+        } else if model.starts_with("332") {
+            Ok(Model::Ls332)
+        } else if model.starts_with("335") {
+            Ok(Model::Ls335)
+        } else if model.starts_with("336") {
+            Ok(Model::Ls336)
+        } else if model.starts_with("340") {
+            Ok(Model::Ls340)
+        } else {
+            Err(GpibError::ValueError(format!(
+                "Unsupported Lake Shore model {:?}.",
+                identity.model
+            )))
+        }
+    }
+
+    /// Number of outputs (control loops)
+    pub fn outputs(&self) -> u8 {
+        match self {
+            Model::Ls336 => 4,
+            _ => 2,
+        }
+    }
+
+    /// The 335 and 336 have a heater range per output, the other models have one for output 1
+    fn has_range_per_output(&self) -> bool {
+        matches!(self, Model::Ls335 | Model::Ls336)
+    }
+
+    /// Highest heater range of an output, None if the output has no heater
+    fn max_heater_range(&self, output: u8) -> Option<u8> {
+        match (self, output) {
+            (Model::Ls335 | Model::Ls336, 1 | 2) => Some(3),
+            // outputs 3 and 4 of the 336 are on or off
+            (Model::Ls336, _) => Some(1),
+            (Model::Ls340, 1) => Some(5),
+            (_, 1) => Some(3),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Model::Ls331 => write!(f, "331"),
+            Model::Ls332 => write!(f, "332"),
+            Model::Ls335 => write!(f, "335"),
+            Model::Ls336 => write!(f, "336"),
+            Model::Ls340 => write!(f, "340"),
+        }
+    }
+}
+
+impl fmt::Debug for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Model({})", self)
+    }
+}
+
+/// Sensor input
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    A,
+    B,
+    /// Model 336, or option card
+    C,
+    /// Model 336, or option card
+    D,
+}
+
+impl Input {
+    fn from_letter(letter: &str) -> Option<Self> {
+        match letter.trim() {
+            "A" => Some(Input::A),
+            "B" => Some(Input::B),
+            "C" => Some(Input::C),
+            "D" => Some(Input::D),
+            _ => None,
+        }
+    }
+
+    /// Input numbered from 1, as returned by `OUTMODE?`
+    fn from_number(number: i64) -> Option<Self> {
+        match number {
+            1 => Some(Input::A),
+            2 => Some(Input::B),
+            3 => Some(Input::C),
+            4 => Some(Input::D),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::A => write!(f, "A"),
+            Input::B => write!(f, "B"),
+            Input::C => write!(f, "C"),
+            Input::D => write!(f, "D"),
+        }
+    }
+}
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Input({})", self)
+    }
+}
+
+/// Status of a sensor reading (`RDGST?`)
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ReadingStatus(u8);
+
+impl ReadingStatus {
+    /// Invalid reading
+    pub const INVALID: u8 = 0x01;
+    /// Temperature below the range of the curve
+    pub const TEMPERATURE_UNDERRANGE: u8 = 0x10;
+    /// Temperature above the range of the curve
+    pub const TEMPERATURE_OVERRANGE: u8 = 0x20;
+    /// Sensor units zero
+    pub const SENSOR_ZERO: u8 = 0x40;
+    /// Sensor units above the range of the input
+    pub const SENSOR_OVERRANGE: u8 = 0x80;
+
+    pub fn from_bits(bits: u8) -> Self {
+        ReadingStatus(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// True if the reading is valid and in range
+    pub fn is_ok(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn invalid(&self) -> bool {
+        self.0 & ReadingStatus::INVALID != 0
+    }
+
+    pub fn temperature_underrange(&self) -> bool {
+        self.0 & ReadingStatus::TEMPERATURE_UNDERRANGE != 0
+    }
+
+    pub fn temperature_overrange(&self) -> bool {
+        self.0 & ReadingStatus::TEMPERATURE_OVERRANGE != 0
+    }
+
+    pub fn sensor_zero(&self) -> bool {
+        self.0 & ReadingStatus::SENSOR_ZERO != 0
+    }
+
+    pub fn sensor_overrange(&self) -> bool {
+        self.0 & ReadingStatus::SENSOR_OVERRANGE != 0
+    }
+}
+
+impl fmt::Display for ReadingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut flags = Vec::new();
+        if self.invalid() {
+            flags.push("INVALID");
+        }
+        if self.temperature_underrange() {
+            flags.push("T.UNDER");
+        }
+        if self.temperature_overrange() {
+            flags.push("T.OVER");
+        }
+        if self.sensor_zero() {
+            flags.push("S.ZERO");
+        }
+        if self.sensor_overrange() {
+            flags.push("S.OVER");
+        }
+        if flags.is_empty() {
+            write!(f, "OK")
+        } else {
+            write!(f, "{}", flags.join(" "))
+        }
+    }
+}
+
+impl fmt::Debug for ReadingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ReadingStatus({})", self)
+    }
+}
+
+/// Heater range: 0 is off, then low (1), medium (2) and high (3). The 340 has ranges 1 to 5,
+/// and the outputs 3 and 4 of the 336 are off (0) or on (1).
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HeaterRange(u8);
+
+impl HeaterRange {
+    pub const OFF: HeaterRange = HeaterRange(0);
+    pub const LOW: HeaterRange = HeaterRange(1);
+    pub const MEDIUM: HeaterRange = HeaterRange(2);
+    pub const HIGH: HeaterRange = HeaterRange(3);
+
+    pub fn new(range: u8) -> Self {
+        HeaterRange(range)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl fmt::Display for HeaterRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            0 => write!(f, "Off"),
+            1 => write!(f, "Low"),
+            2 => write!(f, "Medium"),
+            3 => write!(f, "High"),
+            range => write!(f, "Range {}", range),
+        }
+    }
+}
+
+impl fmt::Debug for HeaterRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HeaterRange({})", self)
+    }
+}
+
+/// PID parameters of a control loop
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Pid {
+    /// Proportional gain
+    pub p: f64,
+    /// Integral (reset)
+    pub i: f64,
+    /// Derivative (rate)
+    pub d: f64,
+}
+
+impl Pid {
+    pub fn new(p: f64, i: f64, d: f64) -> Self {
+        Pid { p, i, d }
+    }
+}
+
+impl fmt::Display for Pid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "P = {}, I = {}, D = {}", self.p, self.i, self.d)
+    }
+}
+
+impl fmt::Debug for Pid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pid({})", self)
+    }
+}
+
+/// Setpoint ramp of a control loop
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Ramp {
+    pub enabled: bool,
+    /// Rate in K/min (or in sensor units per minute)
+    pub rate: f64,
+}
+
+impl Ramp {
+    pub fn new(enabled: bool, rate: f64) -> Self {
+        Ramp { enabled, rate }
+    }
+}
+
+impl fmt::Display for Ramp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.enabled {
+            write!(f, "{}/min", self.rate)
+        } else {
+            write!(f, "Off ({}/min)", self.rate)
+        }
+    }
+}
+
+impl fmt::Debug for Ramp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Ramp({})", self)
+    }
+}
+
+/// Registry of the temperature controllers of this module: models 331, 332, 335, 336 and 340
+pub fn registry() -> DriverRegistry<LakeShore> {
+    DriverRegistry::<LakeShore>::new()
+        .with_driver(MANUFACTURER, "MODEL33*", |instrument, identity| {
+            Ok(Box::new(LakeShore::open(instrument, identity)?))
+        })
+        .with_driver(MANUFACTURER, "MODEL340", |instrument, identity| {
+            Ok(Box::new(LakeShore::open(instrument, identity)?))
+        })
+}
+
+/// Manufacturer in the identification of the controllers
+const MANUFACTURER: &str = "LSCI";
+
+/// Lake Shore temperature controller
+pub struct LakeShore {
+    handle: InstrumentHandle,
+    identity: Identity,
+    model: Model,
+    poll_interval: Duration,
+}
+
+impl LakeShore {
+    /// Controller of the model in the identification
+    pub fn new(handle: InstrumentHandle, identity: Identity) -> Result<Self, GpibError> {
+        let model = Model::from_identity(&identity)?;
+        Ok(LakeShore {
+            handle,
+            identity,
+            model,
+            poll_interval: Duration::from_secs(1),
+        })
+    }
+
+    /// Opens the instrument with the default parameters
+    pub fn open(instrument: Instrument, identity: Identity) -> Result<Self, GpibError> {
+        LakeShore::new(instrument.open(Parameters::default())?, identity)
+    }
+
+    /// Interval between the readings of `wait_until_stable` (1 s by default)
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+
+    pub fn into_inner(self) -> InstrumentHandle {
+        self.handle
+    }
+
+    /// Temperature in K (`KRDG?`)
+    pub fn temperature(&self, input: Input) -> Result<f64, GpibError> {
+        self.handle.query_f64(&format!("KRDG? {}", input))
+    }
+
+    /// Temperature in °C (`CRDG?`)
+    pub fn celsius(&self, input: Input) -> Result<f64, GpibError> {
+        self.handle.query_f64(&format!("CRDG? {}", input))
+    }
+
+    /// Reading in sensor units, e.g. Ω or V (`SRDG?`)
+    pub fn sensor_reading(&self, input: Input) -> Result<f64, GpibError> {
+        self.handle.query_f64(&format!("SRDG? {}", input))
+    }
+
+    /// Status of the reading of an input (`RDGST?`)
+    pub fn reading_status(&self, input: Input) -> Result<ReadingStatus, GpibError> {
+        let bits = self.handle.query_i64(&format!("RDGST? {}", input))?;
+        u8::try_from(bits)
+            .map(ReadingStatus::from_bits)
+            .map_err(|_| GpibError::ValueError(format!("Invalid reading status {}.", bits)))
+    }
+
+    /// Setpoint, in the units of the control loop (K by default)
+    pub fn setpoint(&self, output: u8) -> Result<f64, GpibError> {
+        self.check_output(output)?;
+        self.handle.query_f64(&format!("SETP? {}", output))
+    }
+
+    pub fn set_setpoint(&self, output: u8, setpoint: f64) -> Result<(), GpibError> {
+        self.check_output(output)?;
+        self.write(&format!("SETP {},{}", output, setpoint.to_scpi()))
+    }
+
+    pub fn ramp(&self, output: u8) -> Result<Ramp, GpibError> {
+        self.check_output(output)?;
+        let values: Vec<f64> = self.handle.query_list(&format!("RAMP? {}", output))?;
+        match values[..] {
+            [enabled, rate] => Ok(Ramp::new(enabled != 0.0, rate)),
+            _ => Err(GpibError::ValueError(format!(
+                "Invalid RAMP? response {:?}: expected 2 values.",
+                values
+            ))),
+        }
+    }
+
+    /// Enables or disables the setpoint ramp. With the ramp enabled, a new setpoint is reached
+    /// at the given rate.
+    pub fn set_ramp(&self, output: u8, ramp: Ramp) -> Result<(), GpibError> {
+        self.check_output(output)?;
+        self.write(&format!(
+            "RAMP {},{},{}",
+            output,
+            ramp.enabled.to_scpi(),
+            ramp.rate.to_scpi()
+        ))
+    }
+
+    /// True while the setpoint is ramping (`RAMPST?`)
+    pub fn ramping(&self, output: u8) -> Result<bool, GpibError> {
+        self.check_output(output)?;
+        self.handle.query_bool(&format!("RAMPST? {}", output))
+    }
+
+    pub fn heater_range(&self, output: u8) -> Result<HeaterRange, GpibError> {
+        self.check_heater(output)?;
+        let query = if self.model.has_range_per_output() {
+            format!("RANGE? {}", output)
+        } else {
+            "RANGE?".to_owned()
+        };
+        let range = self.handle.query_i64(&query)?;
+        u8::try_from(range)
+            .map(HeaterRange)
+            .map_err(|_| GpibError::ValueError(format!("Invalid heater range {}.", range)))
+    }
+
+    pub fn set_heater_range(&self, output: u8, range: HeaterRange) -> Result<(), GpibError> {
+        let max = self.check_heater(output)?;
+        if range.0 > max {
+            return Err(GpibError::ValueError(format!(
+                "Invalid heater range {} for output {} of the model {}: expected 0 to {}.",
+                range.0, output, self.model, max
+            )));
+        }
+        if self.model.has_range_per_output() {
+            self.write(&format!("RANGE {},{}", output, range.0))
+        } else {
+            self.write(&format!("RANGE {}", range.0))
+        }
+    }
+
+    /// Heater output in % (`HTR?`, or `AOUT?` for the analog outputs)
+    pub fn heater_output(&self, output: u8) -> Result<f64, GpibError> {
+        self.check_output(output)?;
+        let query = match (self.model.has_range_per_output(), output) {
+            (true, 1 | 2) => format!("HTR? {}", output),
+            (true, _) => format!("AOUT? {}", output),
+            (false, 1) => "HTR?".to_owned(),
+            (false, _) => "AOUT?".to_owned(),
+        };
+        self.handle.query_f64(&query)
+    }
+
+    pub fn pid(&self, output: u8) -> Result<Pid, GpibError> {
+        self.check_output(output)?;
+        let values: Vec<f64> = self.handle.query_list(&format!("PID? {}", output))?;
+        match values[..] {
+            [p, i, d] => Ok(Pid::new(p, i, d)),
+            _ => Err(GpibError::ValueError(format!(
+                "Invalid PID? response {:?}: expected 3 values.",
+                values
+            ))),
+        }
+    }
+
+    pub fn set_pid(&self, output: u8, pid: Pid) -> Result<(), GpibError> {
+        self.check_output(output)?;
+        self.write(&format!(
+            "PID {},{},{},{}",
+            output,
+            pid.p.to_scpi(),
+            pid.i.to_scpi(),
+            pid.d.to_scpi()
+        ))
+    }
+
+    /// Input controlling the output, None if none (`OUTMODE?` on the 335 and 336, `CSET?` on
+    /// the other models)
+    pub fn control_input(&self, output: u8) -> Result<Option<Input>, GpibError> {
+        self.check_output(output)?;
+        let query = self.control_input_query(output);
+        let response = self.handle.query_response(&query)?;
+        self.parse_control_input(&response)
+    }
+
+    /// Waits until the temperature of the control input of the output stays within `tolerance`
+    /// of the setpoint for `duration`, with the setpoint not ramping, and returns the last
+    /// temperature. The temperature is read every poll interval (see `with_poll_interval`),
+    /// without blocking the async runtime. This waits indefinitely: drop the future (e.g. with
+    /// a timeout of the runtime) to give up.
+    ///
+    /// ```rust
+    /// use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+    /// use linux_gpib_rs::driver::lakeshore::LakeShore;
+    /// use linux_gpib_rs::ieee4882::Ieee4882;
+    /// use linux_gpib_rs::instrument::Board;
+    /// use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     pollster::block_on(async {
+    ///         let bus = SimulatedBus::new().with_device(
+    ///             0,
+    ///             PrimaryAddress::new(12)?,
+    ///             SecondaryAddress::default(),
+    ///             |message: &[u8]| match message {
+    ///                 b"*IDN?" => Some(Reply::from("LSCI,MODEL336,1234567,2.9")),
+    ///                 b"OUTMODE? 1" => Some(Reply::from("1,1,0")),
+    ///                 b"RAMPST? 1" => Some(Reply::from("0")),
+    ///                 b"SETP? 1" => Some(Reply::from("+4.200")),
+    ///                 b"KRDG? A" => Some(Reply::from("+4.2030")),
+    ///                 _ => None,
+    ///             },
+    ///         );
+    ///         let board = Board::with_backend(0, Arc::new(bus));
+    ///         let instrument = board.find_listeners()?.remove(0);
+    ///         let identity = instrument.identify()?;
+    ///         let controller = LakeShore::open(instrument, identity)?
+    ///             .with_poll_interval(Duration::from_millis(10));
+    ///         let temperature = controller
+    ///             .wait_until_stable(1, 0.01, Duration::from_millis(50))
+    ///             .await?;
+    ///         assert_eq!(temperature, 4.203);
+    ///         Ok(())
+    ///     })
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn wait_until_stable(
+        &self,
+        output: u8,
+        tolerance: f64,
+        duration: Duration,
+    ) -> Result<f64, GpibError> {
+        self.check_output(output)?;
+        let response: String = self.query(&self.control_input_query(output)).await?;
+        let input = self.parse_control_input(&response)?.ok_or_else(|| {
+            GpibError::ValueError(format!("No control input for output {}.", output))
+        })?;
+        let mut stable_since: Option<Instant> = None;
+        loop {
+            let setpoint: f64 = self.query(&format!("SETP? {}", output)).await?;
+            let ramping: bool = self.query(&format!("RAMPST? {}", output)).await?;
+            let temperature: f64 = self.query(&format!("KRDG? {}", input)).await?;
+            let now = Instant::now();
+            if !ramping && (temperature - setpoint).abs() <= tolerance {
+                let since = *stable_since.get_or_insert(now);
+                if now.duration_since(since) >= duration {
+                    return Ok(temperature);
+                }
+            } else {
+                stable_since = None;
+            }
+            self.handle.sleep(self.poll_interval).await?;
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn query<T: FromScpi>(&self, query: &str) -> Result<T, GpibError> {
+        self.handle.query_parsed(&format!("{}\n", query)).await
+    }
+
+    fn control_input_query(&self, output: u8) -> String {
+        if self.model.has_range_per_output() {
+            format!("OUTMODE? {}", output)
+        } else {
+            format!("CSET? {}", output)
+        }
+    }
+
+    /// Input from `OUTMODE?` (mode, input number, ...) or `CSET?` (input letter, ...)
+    fn parse_control_input(&self, response: &str) -> Result<Option<Input>, GpibError> {
+        let fields: Vec<&str> = response.trim().split(',').collect();
+        let invalid = || {
+            GpibError::ValueError(format!(
+                "Invalid control loop configuration {:?}.",
+                response.trim()
+            ))
+        };
+        if self.model.has_range_per_output() {
+            let number = fields
+                .get(1)
+                .and_then(|field| field.trim().parse().ok())
+                .ok_or_else(invalid)?;
+            Ok(Input::from_number(number))
+        } else {
+            Ok(Input::from_letter(fields[0]))
+        }
+    }
+
+    fn check_output(&self, output: u8) -> Result<(), GpibError> {
+        check_channel(output, self.model.outputs())
+    }
+
+    /// Checks that the output has a heater, and returns its highest range
+    fn check_heater(&self, output: u8) -> Result<u8, GpibError> {
+        self.check_output(output)?;
+        self.model.max_heater_range(output).ok_or_else(|| {
+            GpibError::ValueError(format!(
+                "Output {} of the model {} has no heater range.",
+                output, self.model
+            ))
+        })
+    }
+
+    fn write(&self, command: &str) -> Result<(), GpibError> {
+        self.handle.blocking_write(&format!("{}\n", command))
+    }
+}
+
+impl InstrumentDriver for LakeShore {
+    fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl fmt::Debug for LakeShore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "LakeShore({}, {:?}, {:?})",
+            self.handle, self.identity, self.model
+        )
+    }
+}
@@ -0,0 +1,879 @@
+//!
+//! Stanford Research Systems lock-in amplifiers.
+//!
+//! The SR830 (and SR810) and the SR860 (and SR865) use their own command sets rather than
+//! SCPI: settings are sent as indices (`SENS`, `SCAL`, `OFLT`), values are read with `OUTP?`,
+//! several values are read simultaneously with `SNAP?`, and the data buffer is transferred in
+//! binary with `TRCB?` and `TRCL?` (SR830), or as IEEE 488.2 blocks with `CAPTUREGET?` (SR860).
+//! [`Sr830`] and [`Sr860`] implement the common [`LockInAmplifier`] trait, with the typed
+//! [`Sensitivity`] and [`TimeConstant`] settings, and the buffer transfers of each model.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::driver::lockin::{self, Quantity, Sensitivity, TimeConstant};
+//! use linux_gpib_rs::instrument::Board;
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::{Arc, Mutex};
+//!
+//! let sensitivity = Arc::new(Mutex::new(20));
+//! let setting = sensitivity.clone();
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(8).unwrap(),
+//!     SecondaryAddress::default(),
+//!     move |message: &[u8]| match message {
+//!         b"*IDN?" => Some(Reply::from("Stanford_Research_Systems,SR830,s/n12345,ver1.07")),
+//!         b"SENS?" => Some(Reply::from(setting.lock().unwrap().to_string())),
+//!         b"OFLT?" => Some(Reply::from("10")),
+//!         b"SNAP? 1,2,9" => Some(Reply::from("1.234e-3,-5.6e-5,1000.0")),
+//!         _ => {
+//!             if let Some(index) = message.strip_prefix(b"SENS ") {
+//!                 *setting.lock().unwrap() = String::from_utf8_lossy(index).parse().unwrap();
+//!             }
+//!             None
+//!         }
+//!     },
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let lockin = lockin::registry()
+//!     .open(board.find_listeners().unwrap().remove(0))
+//!     .unwrap();
+//! assert_eq!(lockin.sensitivity().unwrap(), Sensitivity::Mv10);
+//! lockin.set_sensitivity(Sensitivity::Uv100).unwrap();
+//! assert_eq!(*sensitivity.lock().unwrap(), 14);
+//! assert_eq!(lockin.time_constant().unwrap(), TimeConstant::S1);
+//! let values = lockin
+//!     .snap(&[Quantity::X, Quantity::Y, Quantity::Frequency])
+//!     .unwrap();
+//! assert_eq!(values, vec![1.234e-3, -5.6e-5, 1000.0]);
+//! ```
+//!
+
+use crate::driver::{DriverRegistry, InstrumentDriver};
+use crate::error::GpibError;
+use crate::ieee4882::Identity;
+use crate::instrument::{Instrument, InstrumentHandle, Parameters};
+use crate::scpi::ScpiQuery;
+use crate::scpi::command::ToScpi;
+use crate::waveform::{self, ByteOrder};
+use std::fmt;
+use std::time::Duration;
+
+/// Full scale sensitivity, from 1 nV to 1 V. In current input mode, the full scale current is
+/// the sensitivity multiplied by 1 µA/V.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Sensitivity {
+    /// 1 nV (SR860 only)
+    Nv1,
+    Nv2,
+    Nv5,
+    Nv10,
+    Nv20,
+    Nv50,
+    Nv100,
+    Nv200,
+    Nv500,
+    Uv1,
+    Uv2,
+    Uv5,
+    Uv10,
+    Uv20,
+    Uv50,
+    Uv100,
+    Uv200,
+    Uv500,
+    Mv1,
+    Mv2,
+    Mv5,
+    Mv10,
+    Mv20,
+    Mv50,
+    Mv100,
+    Mv200,
+    Mv500,
+    V1,
+}
+
+impl Sensitivity {
+    /// Sensitivities in increasing order
+    pub const ALL: [Sensitivity; 28] = [
+        Sensitivity::Nv1,
+        Sensitivity::Nv2,
+        Sensitivity::Nv5,
+        Sensitivity::Nv10,
+        Sensitivity::Nv20,
+        Sensitivity::Nv50,
+        Sensitivity::Nv100,
+        Sensitivity::Nv200,
+        Sensitivity::Nv500,
+        Sensitivity::Uv1,
+        Sensitivity::Uv2,
+        Sensitivity::Uv5,
+        Sensitivity::Uv10,
+        Sensitivity::Uv20,
+        Sensitivity::Uv50,
+        Sensitivity::Uv100,
+        Sensitivity::Uv200,
+        Sensitivity::Uv500,
+        Sensitivity::Mv1,
+        Sensitivity::Mv2,
+        Sensitivity::Mv5,
+        Sensitivity::Mv10,
+        Sensitivity::Mv20,
+        Sensitivity::Mv50,
+        Sensitivity::Mv100,
+        Sensitivity::Mv200,
+        Sensitivity::Mv500,
+        Sensitivity::V1,
+    ];
+
+    /// Full scale in V
+    pub fn volts(&self) -> f64 {
+        let index = *self as i32;
+        [1.0, 2.0, 5.0][index as usize % 3] * 10f64.powi(index / 3 - 9)
+    }
+
+    /// Smallest sensitivity with a full scale of at least `volts`, None if above 1 V
+    pub fn at_least(volts: f64) -> Option<Self> {
+        // tolerance for the rounding of the full scale values
+        Sensitivity::ALL
+            .into_iter()
+            .find(|sensitivity| sensitivity.volts() * (1.0 + 1e-9) >= volts.abs())
+    }
+}
+
+impl fmt::Display for Sensitivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let index = *self as u32;
+        let value = [1, 2, 5][index as usize % 3] * 10u32.pow(index % 9 / 3);
+        let unit = ["nV", "µV", "mV", "V"][index as usize / 9];
+        write!(f, "{} {}", value, unit)
+    }
+}
+
+impl fmt::Debug for Sensitivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sensitivity({})", self)
+    }
+}
+
+/// Time constant of the output filter, from 1 µs to 30 ks
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimeConstant {
+    /// 1 µs (SR860 only)
+    Us1,
+    /// 3 µs (SR860 only)
+    Us3,
+    Us10,
+    Us30,
+    Us100,
+    Us300,
+    Ms1,
+    Ms3,
+    Ms10,
+    Ms30,
+    Ms100,
+    Ms300,
+    S1,
+    S3,
+    S10,
+    S30,
+    S100,
+    S300,
+    Ks1,
+    Ks3,
+    Ks10,
+    Ks30,
+}
+
+impl TimeConstant {
+    /// Time constants in increasing order
+    pub const ALL: [TimeConstant; 22] = [
+        TimeConstant::Us1,
+        TimeConstant::Us3,
+        TimeConstant::Us10,
+        TimeConstant::Us30,
+        TimeConstant::Us100,
+        TimeConstant::Us300,
+        TimeConstant::Ms1,
+        TimeConstant::Ms3,
+        TimeConstant::Ms10,
+        TimeConstant::Ms30,
+        TimeConstant::Ms100,
+        TimeConstant::Ms300,
+        TimeConstant::S1,
+        TimeConstant::S3,
+        TimeConstant::S10,
+        TimeConstant::S30,
+        TimeConstant::S100,
+        TimeConstant::S300,
+        TimeConstant::Ks1,
+        TimeConstant::Ks3,
+        TimeConstant::Ks10,
+        TimeConstant::Ks30,
+    ];
+
+    pub fn seconds(&self) -> f64 {
+        let index = *self as i32;
+        [1.0, 3.0][index as usize % 2] * 10f64.powi(index / 2 - 6)
+    }
+
+    pub fn duration(&self) -> Duration {
+        let index = *self as u32;
+        Duration::from_micros([1, 3][index as usize % 2] * 10u64.pow(index / 2))
+    }
+}
+
+impl fmt::Display for TimeConstant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let index = *self as u32;
+        let value = [1, 3][index as usize % 2] * 10u32.pow(index % 6 / 2);
+        let unit = ["µs", "ms", "s", "ks"][index as usize / 6];
+        write!(f, "{} {}", value, unit)
+    }
+}
+
+impl fmt::Debug for TimeConstant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TimeConstant({})", self)
+    }
+}
+
+/// Quantity measured by a lock-in amplifier
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quantity {
+    /// In-phase component
+    X,
+    /// Quadrature component
+    Y,
+    /// Magnitude
+    R,
+    /// Phase, in degrees
+    Theta,
+    /// Auxiliary input 1, in V
+    AuxIn1,
+    AuxIn2,
+    AuxIn3,
+    AuxIn4,
+    /// Reference frequency, in Hz
+    Frequency,
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Quantity::X => write!(f, "X"),
+            Quantity::Y => write!(f, "Y"),
+            Quantity::R => write!(f, "R"),
+            Quantity::Theta => write!(f, "Theta"),
+            Quantity::AuxIn1 => write!(f, "AuxIn1"),
+            Quantity::AuxIn2 => write!(f, "AuxIn2"),
+            Quantity::AuxIn3 => write!(f, "AuxIn3"),
+            Quantity::AuxIn4 => write!(f, "AuxIn4"),
+            Quantity::Frequency => write!(f, "Frequency"),
+        }
+    }
+}
+
+impl fmt::Debug for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Quantity({})", self)
+    }
+}
+
+/// Lock-in amplifier
+pub trait LockInAmplifier: InstrumentDriver {
+    fn sensitivity(&self) -> Result<Sensitivity, GpibError>;
+
+    fn set_sensitivity(&self, sensitivity: Sensitivity) -> Result<(), GpibError>;
+
+    fn time_constant(&self) -> Result<TimeConstant, GpibError>;
+
+    fn set_time_constant(&self, time_constant: TimeConstant) -> Result<(), GpibError>;
+
+    /// Reads a single value
+    fn output(&self, quantity: Quantity) -> Result<f64, GpibError>;
+
+    /// Reads several values at the same instant (`SNAP?`)
+    fn snap(&self, quantities: &[Quantity]) -> Result<Vec<f64>, GpibError>;
+
+    /// Reference frequency, in Hz
+    fn frequency(&self) -> Result<f64, GpibError>;
+
+    /// Sets the frequency of the internal reference, in Hz
+    fn set_frequency(&self, frequency: f64) -> Result<(), GpibError>;
+
+    /// Amplitude of the sine output, in V
+    fn amplitude(&self) -> Result<f64, GpibError>;
+
+    fn set_amplitude(&self, amplitude: f64) -> Result<(), GpibError>;
+
+    /// Reference phase shift, in degrees
+    fn phase(&self) -> Result<f64, GpibError>;
+
+    fn set_phase(&self, phase: f64) -> Result<(), GpibError>;
+
+    /// X and Y, read at the same instant
+    fn xy(&self) -> Result<(f64, f64), GpibError> {
+        let values = self.snap(&[Quantity::X, Quantity::Y])?;
+        Ok((values[0], values[1]))
+    }
+
+    /// R and θ (in degrees), read at the same instant
+    fn r_theta(&self) -> Result<(f64, f64), GpibError> {
+        let values = self.snap(&[Quantity::R, Quantity::Theta])?;
+        Ok((values[0], values[1]))
+    }
+}
+
+/// Registry of the lock-in amplifiers of this module: SR810, SR830, SR860 and SR865(A)
+pub fn registry() -> DriverRegistry<dyn LockInAmplifier> {
+    DriverRegistry::<dyn LockInAmplifier>::new()
+        .with_driver(MANUFACTURER, "SR810", |instrument, identity| {
+            Ok(Box::new(Sr830::open(instrument, identity)?))
+        })
+        .with_driver(MANUFACTURER, "SR830", |instrument, identity| {
+            Ok(Box::new(Sr830::open(instrument, identity)?))
+        })
+        .with_driver(MANUFACTURER, "SR86*", |instrument, identity| {
+            Ok(Box::new(Sr860::open(instrument, identity)?))
+        })
+}
+
+/// Manufacturer in the identification of the instruments
+const MANUFACTURER: &str = "Stanford_Research_Systems";
+
+/// Sample rate of the SR830 data buffer, from 62.5 mHz to 512 Hz, or one sample per trigger
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SampleRate(u8);
+
+impl SampleRate {
+    /// One sample per trigger
+    pub const TRIGGER: SampleRate = SampleRate(14);
+
+    /// Sample rate in Hz, 62.5 mHz times a power of 2 up to 512 Hz
+    pub fn from_hz(hz: f64) -> Result<Self, GpibError> {
+        (0..14)
+            .map(SampleRate)
+            .find(|rate| rate.hz() == Some(hz))
+            .ok_or_else(|| {
+                GpibError::ValueError(format!(
+                    "Invalid sample rate {} Hz: expected 62.5 mHz times a power of 2, up to 512 Hz.",
+                    hz
+                ))
+            })
+    }
+
+    /// Sample rate in Hz, None if triggered
+    pub fn hz(&self) -> Option<f64> {
+        if *self == SampleRate::TRIGGER {
+            None
+        } else {
+            Some(0.0625 * f64::from(1u16 << self.0))
+        }
+    }
+}
+
+impl fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.hz() {
+            Some(hz) => write!(f, "{} Hz", hz),
+            None => write!(f, "Trigger"),
+        }
+    }
+}
+
+impl fmt::Debug for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SampleRate({})", self)
+    }
+}
+
+/// SR830 (and SR810) lock-in amplifier
+pub struct Sr830 {
+    handle: InstrumentHandle,
+    identity: Identity,
+}
+
+impl Sr830 {
+    pub fn new(handle: InstrumentHandle, identity: Identity) -> Self {
+        Sr830 { handle, identity }
+    }
+
+    /// Opens the instrument with the default parameters, and directs the responses to the GPIB
+    /// interface (`OUTX 1`)
+    pub fn open(instrument: Instrument, identity: Identity) -> Result<Self, GpibError> {
+        let lockin = Sr830::new(instrument.open(Parameters::default())?, identity);
+        lockin.write("OUTX 1")?;
+        Ok(lockin)
+    }
+
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+
+    pub fn into_inner(self) -> InstrumentHandle {
+        self.handle
+    }
+
+    /// Sample rate of the data buffer (`SRAT`)
+    pub fn set_sample_rate(&self, rate: SampleRate) -> Result<(), GpibError> {
+        self.write(&format!("SRAT {}", rate.0))
+    }
+
+    /// Loop mode of the data buffer (`SEND`): when full, the buffer either stops, or
+    /// overwrites the oldest points
+    pub fn set_buffer_loop(&self, enabled: bool) -> Result<(), GpibError> {
+        self.write(&format!("SEND {}", enabled.to_scpi()))
+    }
+
+    /// Starts or resumes the storage of the displays in the buffer (`STRT`)
+    pub fn start_buffer(&self) -> Result<(), GpibError> {
+        self.write("STRT")
+    }
+
+    /// Pauses the storage (`PAUS`)
+    pub fn pause_buffer(&self) -> Result<(), GpibError> {
+        self.write("PAUS")
+    }
+
+    /// Clears the buffer (`REST`)
+    pub fn reset_buffer(&self) -> Result<(), GpibError> {
+        self.write("REST")
+    }
+
+    /// Number of points stored in the buffer (`SPTS?`)
+    pub fn buffer_points(&self) -> Result<usize, GpibError> {
+        let points = self.handle.query_i64("SPTS?")?;
+        usize::try_from(points).map_err(|_| {
+            GpibError::ValueError(format!("Invalid number of buffer points {}.", points))
+        })
+    }
+
+    /// Transfers `count` points of the buffer of a display (1 or 2) from bin `start`, as IEEE
+    /// 754 floats (`TRCB?`)
+    pub fn read_buffer(
+        &self,
+        display: u8,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<f32>, GpibError> {
+        let payload = self.read_binary("TRCB?", display, start, count)?;
+        waveform::decode(&payload, ByteOrder::LittleEndian)
+    }
+
+    /// Transfers points of the buffer, as `read_buffer`, in the faster non-normalized format
+    /// of `TRCL?`: a 16-bit mantissa `m` and a 16-bit exponent `e`, for `m * 2^(e - 124)`
+    pub fn read_buffer_compact(
+        &self,
+        display: u8,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<f64>, GpibError> {
+        let payload = self.read_binary("TRCL?", display, start, count)?;
+        let words: Vec<i16> = waveform::decode(&payload, ByteOrder::LittleEndian)?;
+        Ok(words
+            .chunks_exact(2)
+            .map(|point| f64::from(point[0]) * 2f64.powi(i32::from(point[1]) - 124))
+            .collect())
+    }
+
+    fn read_binary(
+        &self,
+        header: &str,
+        display: u8,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<u8>, GpibError> {
+        if display != 1 && display != 2 {
+            return Err(GpibError::ValueError(format!(
+                "Invalid display {}: expected 1 or 2.",
+                display
+            )));
+        }
+        let payload = self
+            .handle
+            .blocking_query_binary(&format!("{} {},{},{}\n", header, display, start, count))?;
+        if payload.len() != 4 * count {
+            return Err(GpibError::ValueError(format!(
+                "Invalid buffer transfer: received {} bytes, expected {}.",
+                payload.len(),
+                4 * count
+            )));
+        }
+        Ok(payload)
+    }
+
+    /// Parameter code of `SNAP?`
+    fn snap_code(quantity: Quantity) -> u8 {
+        match quantity {
+            Quantity::X => 1,
+            Quantity::Y => 2,
+            Quantity::R => 3,
+            Quantity::Theta => 4,
+            Quantity::AuxIn1 => 5,
+            Quantity::AuxIn2 => 6,
+            Quantity::AuxIn3 => 7,
+            Quantity::AuxIn4 => 8,
+            Quantity::Frequency => 9,
+        }
+    }
+
+    fn write(&self, command: &str) -> Result<(), GpibError> {
+        self.handle.blocking_write(&format!("{}\n", command))
+    }
+}
+
+impl InstrumentDriver for Sr830 {
+    fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl LockInAmplifier for Sr830 {
+    fn sensitivity(&self) -> Result<Sensitivity, GpibError> {
+        // 0 is 2 nV
+        setting(&Sensitivity::ALL[1..], self.handle.query_i64("SENS?")?)
+    }
+
+    fn set_sensitivity(&self, sensitivity: Sensitivity) -> Result<(), GpibError> {
+        if sensitivity == Sensitivity::Nv1 {
+            return Err(unsupported(&self.identity, sensitivity));
+        }
+        self.write(&format!("SENS {}", sensitivity as u8 - 1))
+    }
+
+    fn time_constant(&self) -> Result<TimeConstant, GpibError> {
+        // 0 is 10 µs
+        setting(&TimeConstant::ALL[2..], self.handle.query_i64("OFLT?")?)
+    }
+
+    fn set_time_constant(&self, time_constant: TimeConstant) -> Result<(), GpibError> {
+        if time_constant < TimeConstant::Us10 {
+            return Err(unsupported(&self.identity, time_constant));
+        }
+        self.write(&format!("OFLT {}", time_constant as u8 - 2))
+    }
+
+    fn output(&self, quantity: Quantity) -> Result<f64, GpibError> {
+        match quantity {
+            Quantity::X | Quantity::Y | Quantity::R | Quantity::Theta => self
+                .handle
+                .query_f64(&format!("OUTP? {}", Sr830::snap_code(quantity))),
+            Quantity::AuxIn1 | Quantity::AuxIn2 | Quantity::AuxIn3 | Quantity::AuxIn4 => self
+                .handle
+                .query_f64(&format!("OAUX? {}", Sr830::snap_code(quantity) - 4)),
+            Quantity::Frequency => self.frequency(),
+        }
+    }
+
+    /// 2 to 6 values
+    fn snap(&self, quantities: &[Quantity]) -> Result<Vec<f64>, GpibError> {
+        let codes: Vec<String> = quantities
+            .iter()
+            .map(|quantity| Sr830::snap_code(*quantity).to_string())
+            .collect();
+        snap(&self.handle, &codes, 6)
+    }
+
+    fn frequency(&self) -> Result<f64, GpibError> {
+        self.handle.query_f64("FREQ?")
+    }
+
+    fn set_frequency(&self, frequency: f64) -> Result<(), GpibError> {
+        self.write(&format!("FREQ {}", frequency.to_scpi()))
+    }
+
+    fn amplitude(&self) -> Result<f64, GpibError> {
+        self.handle.query_f64("SLVL?")
+    }
+
+    fn set_amplitude(&self, amplitude: f64) -> Result<(), GpibError> {
+        self.write(&format!("SLVL {}", amplitude.to_scpi()))
+    }
+
+    fn phase(&self) -> Result<f64, GpibError> {
+        self.handle.query_f64("PHAS?")
+    }
+
+    fn set_phase(&self, phase: f64) -> Result<(), GpibError> {
+        self.write(&format!("PHAS {}", phase.to_scpi()))
+    }
+}
+
+impl fmt::Debug for Sr830 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sr830({}, {:?})", self.handle, self.identity)
+    }
+}
+
+/// Quantities stored in the SR860 capture buffer, for each sample
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaptureConfig {
+    X,
+    XY,
+    RTheta,
+    XYRTheta,
+}
+
+impl CaptureConfig {
+    /// Number of values per sample
+    pub fn values_per_sample(&self) -> usize {
+        match self {
+            CaptureConfig::X => 1,
+            CaptureConfig::XY | CaptureConfig::RTheta => 2,
+            CaptureConfig::XYRTheta => 4,
+        }
+    }
+}
+
+impl fmt::Debug for CaptureConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureConfig::X => write!(f, "CaptureConfig(X)"),
+            CaptureConfig::XY => write!(f, "CaptureConfig(XY)"),
+            CaptureConfig::RTheta => write!(f, "CaptureConfig(RTheta)"),
+            CaptureConfig::XYRTheta => write!(f, "CaptureConfig(XYRTheta)"),
+        }
+    }
+}
+
+/// Start of the SR860 capture
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaptureStart {
+    /// Immediately
+    Immediate,
+    /// On a hardware trigger
+    Trigger,
+    /// One sample per hardware trigger
+    SamplePerTrigger,
+}
+
+impl fmt::Debug for CaptureStart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureStart::Immediate => write!(f, "CaptureStart(Immediate)"),
+            CaptureStart::Trigger => write!(f, "CaptureStart(Trigger)"),
+            CaptureStart::SamplePerTrigger => write!(f, "CaptureStart(SamplePerTrigger)"),
+        }
+    }
+}
+
+/// SR860 (and SR865) lock-in amplifier
+pub struct Sr860 {
+    handle: InstrumentHandle,
+    identity: Identity,
+}
+
+impl Sr860 {
+    pub fn new(handle: InstrumentHandle, identity: Identity) -> Self {
+        Sr860 { handle, identity }
+    }
+
+    /// Opens the instrument with the default parameters
+    pub fn open(instrument: Instrument, identity: Identity) -> Result<Self, GpibError> {
+        Ok(Sr860::new(
+            instrument.open(Parameters::default())?,
+            identity,
+        ))
+    }
+
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+
+    pub fn into_inner(self) -> InstrumentHandle {
+        self.handle
+    }
+
+    /// Quantities stored in the capture buffer (`CAPTURECFG`)
+    pub fn set_capture_config(&self, config: CaptureConfig) -> Result<(), GpibError> {
+        self.write(&format!("CAPTURECFG {}", config as u8))
+    }
+
+    /// Length of the capture buffer, in kB (`CAPTURELEN`)
+    pub fn set_capture_length(&self, kilobytes: u32) -> Result<(), GpibError> {
+        self.write(&format!("CAPTURELEN {}", kilobytes))
+    }
+
+    /// Capture rate, the maximum rate divided by `2^divider` (`CAPTURERATE`)
+    pub fn set_capture_rate(&self, divider: u8) -> Result<(), GpibError> {
+        self.write(&format!("CAPTURERATE {}", divider))
+    }
+
+    /// Starts a capture (`CAPTURESTART`), once or continuously
+    pub fn start_capture(&self, continuous: bool, start: CaptureStart) -> Result<(), GpibError> {
+        self.write(&format!(
+            "CAPTURESTART {},{}",
+            continuous.to_scpi(),
+            start as u8
+        ))
+    }
+
+    /// Stops the capture (`CAPTURESTOP`)
+    pub fn stop_capture(&self) -> Result<(), GpibError> {
+        self.write("CAPTURESTOP")
+    }
+
+    /// Number of bytes captured (`CAPTUREBYTES?`)
+    pub fn captured_bytes(&self) -> Result<usize, GpibError> {
+        let bytes = self.handle.query_i64("CAPTUREBYTES?")?;
+        usize::try_from(bytes)
+            .map_err(|_| GpibError::ValueError(format!("Invalid number of bytes {}.", bytes)))
+    }
+
+    /// Transfers `length` kB of the capture buffer from `offset` kB (`CAPTUREGET?`, at most
+    /// 64 kB at once). The values of the samples follow each other, as configured with
+    /// `set_capture_config`.
+    pub fn read_capture(&self, offset: u32, length: u32) -> Result<Vec<f32>, GpibError> {
+        let payload = self
+            .handle
+            .blocking_query_block(&format!("CAPTUREGET? {},{}\n", offset, length))?;
+        waveform::decode(&payload, ByteOrder::LittleEndian)
+    }
+
+    /// Parameter code of `SNAP?`
+    fn snap_code(quantity: Quantity) -> u8 {
+        match quantity {
+            Quantity::X => 0,
+            Quantity::Y => 1,
+            Quantity::R => 2,
+            Quantity::Theta => 3,
+            Quantity::AuxIn1 => 4,
+            Quantity::AuxIn2 => 5,
+            Quantity::AuxIn3 => 6,
+            Quantity::AuxIn4 => 7,
+            // FInt, see `frequency_snap_code`
+            Quantity::Frequency => 15,
+        }
+    }
+
+    /// Parameter code of the reference frequency in `SNAP?`, which depends on the reference
+    /// source (`RSRC?`): FInt with the internal reference, FExt otherwise
+    fn frequency_snap_code(&self) -> Result<u8, GpibError> {
+        match self.handle.query_i64("RSRC?")? {
+            0 => Ok(15),
+            _ => Ok(16),
+        }
+    }
+
+    fn write(&self, command: &str) -> Result<(), GpibError> {
+        self.handle.blocking_write(&format!("{}\n", command))
+    }
+}
+
+impl InstrumentDriver for Sr860 {
+    fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl LockInAmplifier for Sr860 {
+    fn sensitivity(&self) -> Result<Sensitivity, GpibError> {
+        // 0 is 1 V
+        let index = self.handle.query_i64("SCAL?")?;
+        setting(&Sensitivity::ALL, 27 - index)
+    }
+
+    fn set_sensitivity(&self, sensitivity: Sensitivity) -> Result<(), GpibError> {
+        self.write(&format!("SCAL {}", 27 - sensitivity as u8))
+    }
+
+    fn time_constant(&self) -> Result<TimeConstant, GpibError> {
+        setting(&TimeConstant::ALL, self.handle.query_i64("OFLT?")?)
+    }
+
+    fn set_time_constant(&self, time_constant: TimeConstant) -> Result<(), GpibError> {
+        self.write(&format!("OFLT {}", time_constant as u8))
+    }
+
+    fn output(&self, quantity: Quantity) -> Result<f64, GpibError> {
+        match quantity {
+            Quantity::X | Quantity::Y | Quantity::R | Quantity::Theta => self
+                .handle
+                .query_f64(&format!("OUTP? {}", Sr860::snap_code(quantity))),
+            Quantity::AuxIn1 | Quantity::AuxIn2 | Quantity::AuxIn3 | Quantity::AuxIn4 => self
+                .handle
+                .query_f64(&format!("OAUX? {}", Sr860::snap_code(quantity) - 4)),
+            Quantity::Frequency => self.frequency(),
+        }
+    }
+
+    /// 2 or 3 values
+    fn snap(&self, quantities: &[Quantity]) -> Result<Vec<f64>, GpibError> {
+        let codes: Vec<String> = quantities
+            .iter()
+            .map(|quantity| match quantity {
+                Quantity::Frequency => self.frequency_snap_code(),
+                _ => Ok(Sr860::snap_code(*quantity)),
+            })
+            .map(|code| code.map(|code| code.to_string()))
+            .collect::<Result<_, _>>()?;
+        snap(&self.handle, &codes, 3)
+    }
+
+    fn frequency(&self) -> Result<f64, GpibError> {
+        self.handle.query_f64("FREQ?")
+    }
+
+    fn set_frequency(&self, frequency: f64) -> Result<(), GpibError> {
+        self.write(&format!("FREQ {}", frequency.to_scpi()))
+    }
+
+    fn amplitude(&self) -> Result<f64, GpibError> {
+        self.handle.query_f64("SLVL?")
+    }
+
+    fn set_amplitude(&self, amplitude: f64) -> Result<(), GpibError> {
+        self.write(&format!("SLVL {}", amplitude.to_scpi()))
+    }
+
+    fn phase(&self) -> Result<f64, GpibError> {
+        self.handle.query_f64("PHAS?")
+    }
+
+    fn set_phase(&self, phase: f64) -> Result<(), GpibError> {
+        self.write(&format!("PHAS {}", phase.to_scpi()))
+    }
+}
+
+impl fmt::Debug for Sr860 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sr860({}, {:?})", self.handle, self.identity)
+    }
+}
+
+/// Setting from the index returned by the instrument
+fn setting<T: Copy>(settings: &[T], index: i64) -> Result<T, GpibError> {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| settings.get(index).copied())
+        .ok_or_else(|| GpibError::ValueError(format!("Invalid setting index {}.", index)))
+}
+
+fn unsupported(identity: &Identity, setting: impl fmt::Debug) -> GpibError {
+    GpibError::ValueError(format!(
+        "{:?} not supported by {}.",
+        setting, identity.model
+    ))
+}
+
+/// Sends `SNAP?` with 2 to `max` parameter codes, and parses the values
+fn snap(handle: &InstrumentHandle, codes: &[String], max: usize) -> Result<Vec<f64>, GpibError> {
+    if codes.len() < 2 || codes.len() > max {
+        return Err(GpibError::ValueError(format!(
+            "Invalid number of SNAP? values {}: expected 2 to {}.",
+            codes.len(),
+            max
+        )));
+    }
+    let values: Vec<f64> = handle.query_list(&format!("SNAP? {}", codes.join(",")))?;
+    if values.len() != codes.len() {
+        return Err(GpibError::ValueError(format!(
+            "Invalid SNAP? response: {} values, expected {}.",
+            values.len(),
+            codes.len()
+        )));
+    }
+    Ok(values)
+}
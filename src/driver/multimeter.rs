@@ -0,0 +1,192 @@
+//!
+//! Digital multimeters.
+//!
+//! [`ScpiMultimeter`] implements [`Multimeter`] with the `MEASure`, `CONFigure` and `READ`
+//! commands of the SCPI standard, as supported e.g. by the Keysight 344xxA and the Keithley 2000
+//! series.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::driver::multimeter::{self, Multimeter};
+//! use linux_gpib_rs::instrument::Board;
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(22).unwrap(),
+//!     SecondaryAddress::default(),
+//!     |message: &[u8]| match message {
+//!         b"*IDN?" => Some(Reply::from("KEITHLEY INSTRUMENTS INC.,MODEL 2000,1234,A20")),
+//!         b"MEAS:VOLT:DC?" => Some(Reply::from("+1.23456789E+00")),
+//!         _ => None,
+//!     },
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let dmm = multimeter::registry()
+//!     .open(board.find_listeners().unwrap().remove(0))
+//!     .unwrap();
+//! assert_eq!(dmm.measure_dc_voltage().unwrap(), 1.23456789);
+//! ```
+//!
+
+use crate::driver::{DriverRegistry, InstrumentDriver, KEYSIGHT};
+use crate::error::GpibError;
+use crate::ieee4882::Identity;
+use crate::instrument::{Instrument, InstrumentHandle, Parameters};
+use crate::scpi::ScpiQuery;
+use crate::scpi::command::{Limit, ToScpi};
+use std::fmt;
+
+/// Measurement function of a multimeter
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeasurementFunction {
+    DcVoltage,
+    AcVoltage,
+    DcCurrent,
+    AcCurrent,
+    /// 2-wire resistance
+    Resistance,
+    /// 4-wire resistance
+    FourWireResistance,
+    Frequency,
+}
+
+impl MeasurementFunction {
+    /// SCPI header of the function, following `MEAS` or `CONF`
+    pub fn header(&self) -> &'static str {
+        match self {
+            MeasurementFunction::DcVoltage => "VOLT:DC",
+            MeasurementFunction::AcVoltage => "VOLT:AC",
+            MeasurementFunction::DcCurrent => "CURR:DC",
+            MeasurementFunction::AcCurrent => "CURR:AC",
+            MeasurementFunction::Resistance => "RES",
+            MeasurementFunction::FourWireResistance => "FRES",
+            MeasurementFunction::Frequency => "FREQ",
+        }
+    }
+}
+
+impl fmt::Display for MeasurementFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.header())
+    }
+}
+
+impl fmt::Debug for MeasurementFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MeasurementFunction({})", self)
+    }
+}
+
+/// Digital multimeter
+pub trait Multimeter: InstrumentDriver {
+    /// Configures the function with default settings, triggers a measurement and returns it
+    fn measure(&self, function: MeasurementFunction) -> Result<f64, GpibError>;
+
+    /// Configures the function and its range (`Limit::Default` for autorange)
+    fn configure(&self, function: MeasurementFunction, range: Limit<f64>) -> Result<(), GpibError>;
+
+    /// Triggers a measurement with the current configuration, and returns it
+    fn read(&self) -> Result<f64, GpibError>;
+
+    fn measure_dc_voltage(&self) -> Result<f64, GpibError> {
+        self.measure(MeasurementFunction::DcVoltage)
+    }
+
+    fn measure_ac_voltage(&self) -> Result<f64, GpibError> {
+        self.measure(MeasurementFunction::AcVoltage)
+    }
+
+    fn measure_dc_current(&self) -> Result<f64, GpibError> {
+        self.measure(MeasurementFunction::DcCurrent)
+    }
+
+    fn measure_ac_current(&self) -> Result<f64, GpibError> {
+        self.measure(MeasurementFunction::AcCurrent)
+    }
+
+    fn measure_resistance(&self) -> Result<f64, GpibError> {
+        self.measure(MeasurementFunction::Resistance)
+    }
+
+    fn measure_four_wire_resistance(&self) -> Result<f64, GpibError> {
+        self.measure(MeasurementFunction::FourWireResistance)
+    }
+
+    fn measure_frequency(&self) -> Result<f64, GpibError> {
+        self.measure(MeasurementFunction::Frequency)
+    }
+}
+
+/// Registry of the multimeters known to accept the commands of [`ScpiMultimeter`]: Keithley
+/// 2000 series, and HP/Agilent/Keysight 344xx(A)
+pub fn registry() -> DriverRegistry<dyn Multimeter> {
+    let mut registry = DriverRegistry::<dyn Multimeter>::new().with_driver(
+        "KEITHLEY*",
+        "MODEL 20*",
+        |instrument, identity| Ok(Box::new(ScpiMultimeter::open(instrument, identity)?)),
+    );
+    for manufacturer in KEYSIGHT {
+        registry.register(manufacturer, "344*", |instrument, identity| {
+            Ok(Box::new(ScpiMultimeter::open(instrument, identity)?))
+        });
+    }
+    registry
+}
+
+/// Multimeter with the standard SCPI commands
+pub struct ScpiMultimeter {
+    handle: InstrumentHandle,
+    identity: Identity,
+}
+
+impl ScpiMultimeter {
+    pub fn new(handle: InstrumentHandle, identity: Identity) -> Self {
+        ScpiMultimeter { handle, identity }
+    }
+
+    /// Opens the instrument with the default parameters
+    pub fn open(instrument: Instrument, identity: Identity) -> Result<Self, GpibError> {
+        Ok(ScpiMultimeter::new(
+            instrument.open(Parameters::default())?,
+            identity,
+        ))
+    }
+
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+
+    pub fn into_inner(self) -> InstrumentHandle {
+        self.handle
+    }
+}
+
+impl InstrumentDriver for ScpiMultimeter {
+    fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl Multimeter for ScpiMultimeter {
+    fn measure(&self, function: MeasurementFunction) -> Result<f64, GpibError> {
+        self.handle
+            .query_f64(&format!("MEAS:{}?", function.header()))
+    }
+
+    fn configure(&self, function: MeasurementFunction, range: Limit<f64>) -> Result<(), GpibError> {
+        self.handle
+            .blocking_write(&format!("CONF:{} {}\n", function.header(), range.to_scpi()))
+    }
+
+    fn read(&self) -> Result<f64, GpibError> {
+        self.handle.query_f64("READ?")
+    }
+}
+
+impl fmt::Debug for ScpiMultimeter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ScpiMultimeter({}, {:?})", self.handle, self.identity)
+    }
+}
@@ -0,0 +1,252 @@
+//!
+//! Oscilloscopes.
+//!
+//! There is no standard SCPI command set for oscilloscopes. [`ScpiOscilloscope`] implements
+//! [`Oscilloscope`] with the commands of the Keysight InfiniiVision series (`:DIGitize`,
+//! `:WAVeform:PREamble?`, `:WAVeform:DATA?`), which some other oscilloscopes accept as well.
+//! Waveforms are transferred as signed 16-bit big-endian samples, and scaled with the preamble
+//! (see [`crate::waveform`]).
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::block;
+//! use linux_gpib_rs::driver::oscilloscope::{Oscilloscope, ScpiOscilloscope};
+//! use linux_gpib_rs::ieee4882::Ieee4882;
+//! use linux_gpib_rs::instrument::{Board, Parameters};
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(7).unwrap(),
+//!     SecondaryAddress::default(),
+//!     |message: &[u8]| match message {
+//!         b"*IDN?" => Some(Reply::from("KEYSIGHT TECHNOLOGIES,DSOX3024T,MY0000,07.50")),
+//!         b"*OPC?" => Some(Reply::from("1")),
+//!         b":WAV:PRE?" => Some(Reply::from("1,0,3,1,1.0E-06,-1.0E-06,0,0.5,1.0,0")),
+//!         b":WAV:DATA?" => Some(Reply::from(block::encode(&[0, 0, 0, 2, 0xff, 0xfe]))),
+//!         _ => None,
+//!     },
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let handle = board.find_listeners().unwrap()[0].open(Parameters::default()).unwrap();
+//! let identity = handle.identify().unwrap();
+//! let scope = ScpiOscilloscope::new(handle, identity);
+//! let trace = scope.acquire_waveform(1).unwrap();
+//! assert_eq!(trace.values, vec![1.0, 2.0, 0.0]);
+//! assert_eq!(trace.times(), vec![-1e-6, 0.0, 1e-6]);
+//! ```
+//!
+
+use crate::driver::{DriverRegistry, InstrumentDriver, KEYSIGHT, check_channel};
+use crate::error::GpibError;
+use crate::ieee4882::{Identity, Ieee4882};
+use crate::instrument::{Instrument, InstrumentHandle, Parameters};
+use crate::scpi::ScpiQuery;
+use crate::scpi::command::ToScpi;
+use crate::waveform::{self, ByteOrder, Scaling};
+use std::fmt;
+
+/// Acquired waveform, in engineering units
+#[derive(Clone, PartialEq)]
+pub struct Trace {
+    /// Time of the samples, from their index
+    pub x: Scaling,
+    /// Values of the samples (e.g. V)
+    pub values: Vec<f64>,
+}
+
+impl Trace {
+    /// Time axis, in s
+    pub fn times(&self) -> Vec<f64> {
+        self.x.axis(self.values.len())
+    }
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Trace({} samples, x = {})", self.values.len(), self.x)
+    }
+}
+
+/// Oscilloscope. Channels are numbered from 1.
+pub trait Oscilloscope: InstrumentDriver {
+    /// Number of analog channels
+    fn channels(&self) -> u8;
+
+    /// Starts continuous acquisitions
+    fn run(&self) -> Result<(), GpibError>;
+
+    /// Stops the acquisitions
+    fn stop(&self) -> Result<(), GpibError>;
+
+    /// Acquires once, on the next trigger
+    fn single(&self) -> Result<(), GpibError>;
+
+    /// Horizontal scale, in s per division
+    fn set_timebase_scale(&self, seconds_per_division: f64) -> Result<(), GpibError>;
+
+    /// Vertical scale of a channel, in V per division
+    fn set_channel_scale(&self, channel: u8, volts_per_division: f64) -> Result<(), GpibError>;
+
+    fn set_channel_enabled(&self, channel: u8, enabled: bool) -> Result<(), GpibError>;
+
+    /// Acquires a waveform on the channel, waits for the acquisition to complete, and transfers
+    /// it. The acquisition stops afterwards. The I/O timeout must be longer than the time to
+    /// trigger.
+    fn acquire_waveform(&self, channel: u8) -> Result<Trace, GpibError>;
+}
+
+/// Registry of the Keysight InfiniiVision oscilloscopes (DSO-X and MSO-X series). The number of
+/// channels is the last digit of the model number (e.g. 4 for the DSO-X 3024T).
+pub fn registry() -> DriverRegistry<dyn Oscilloscope> {
+    let mut registry = DriverRegistry::<dyn Oscilloscope>::new();
+    for manufacturer in KEYSIGHT {
+        for model in ["DSO-X*", "MSO-X*", "DSOX*", "MSOX*"] {
+            registry.register(manufacturer, model, |instrument, identity| {
+                let channels = identity
+                    .model
+                    .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+                    .chars()
+                    .last()
+                    .and_then(|digit| match digit {
+                        '2' => Some(2),
+                        '4' => Some(4),
+                        _ => None,
+                    })
+                    .unwrap_or(4);
+                Ok(Box::new(
+                    ScpiOscilloscope::open(instrument, identity)?.with_channels(channels),
+                ))
+            });
+        }
+    }
+    registry
+}
+
+/// Oscilloscope with the Keysight InfiniiVision commands
+pub struct ScpiOscilloscope {
+    handle: InstrumentHandle,
+    identity: Identity,
+    channels: u8,
+}
+
+impl ScpiOscilloscope {
+    /// Oscilloscope with 4 channels (see `with_channels`)
+    pub fn new(handle: InstrumentHandle, identity: Identity) -> Self {
+        ScpiOscilloscope {
+            handle,
+            identity,
+            channels: 4,
+        }
+    }
+
+    /// Opens the instrument with the default parameters
+    pub fn open(instrument: Instrument, identity: Identity) -> Result<Self, GpibError> {
+        Ok(ScpiOscilloscope::new(
+            instrument.open(Parameters::default())?,
+            identity,
+        ))
+    }
+
+    pub fn with_channels(mut self, channels: u8) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+
+    pub fn into_inner(self) -> InstrumentHandle {
+        self.handle
+    }
+
+    /// Reads the scaling of the waveform from the preamble (format, type, points, count,
+    /// x increment, x origin, x reference, y increment, y origin, y reference)
+    fn preamble(&self) -> Result<(Scaling, Scaling), GpibError> {
+        let preamble: Vec<f64> = self.handle.query_list(":WAV:PRE?")?;
+        if preamble.len() < 10 {
+            return Err(GpibError::ValueError(format!(
+                "Invalid waveform preamble {:?}: expected 10 values.",
+                preamble
+            )));
+        }
+        Ok((
+            Scaling::new(preamble[4], preamble[5], preamble[6]),
+            Scaling::new(preamble[7], preamble[8], preamble[9]),
+        ))
+    }
+
+    fn write(&self, command: &str) -> Result<(), GpibError> {
+        self.handle.blocking_write(&format!("{}\n", command))
+    }
+}
+
+impl InstrumentDriver for ScpiOscilloscope {
+    fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl Oscilloscope for ScpiOscilloscope {
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn run(&self) -> Result<(), GpibError> {
+        self.write(":RUN")
+    }
+
+    fn stop(&self) -> Result<(), GpibError> {
+        self.write(":STOP")
+    }
+
+    fn single(&self) -> Result<(), GpibError> {
+        self.write(":SING")
+    }
+
+    fn set_timebase_scale(&self, seconds_per_division: f64) -> Result<(), GpibError> {
+        self.write(&format!(":TIM:SCAL {}", seconds_per_division.to_scpi()))
+    }
+
+    fn set_channel_scale(&self, channel: u8, volts_per_division: f64) -> Result<(), GpibError> {
+        check_channel(channel, self.channels)?;
+        self.write(&format!(
+            ":CHAN{}:SCAL {}",
+            channel,
+            volts_per_division.to_scpi()
+        ))
+    }
+
+    fn set_channel_enabled(&self, channel: u8, enabled: bool) -> Result<(), GpibError> {
+        check_channel(channel, self.channels)?;
+        self.write(&format!(":CHAN{}:DISP {}", channel, enabled.to_scpi()))
+    }
+
+    fn acquire_waveform(&self, channel: u8) -> Result<Trace, GpibError> {
+        check_channel(channel, self.channels)?;
+        self.write(&format!(":DIG CHAN{}", channel))?;
+        self.handle.operation_complete()?;
+        self.write(&format!(
+            ":WAV:SOUR CHAN{};FORM WORD;BYT MSBF;UNS 0",
+            channel
+        ))?;
+        let (x, y) = self.preamble()?;
+        let payload = self.handle.blocking_query_block(":WAV:DATA?\n")?;
+        Ok(Trace {
+            x,
+            values: waveform::decode_scaled::<i16>(&payload, ByteOrder::BigEndian, &y)?,
+        })
+    }
+}
+
+impl fmt::Debug for ScpiOscilloscope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ScpiOscilloscope({}, {:?}, {} channels)",
+            self.handle, self.identity, self.channels
+        )
+    }
+}
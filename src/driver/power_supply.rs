@@ -0,0 +1,195 @@
+//!
+//! DC power supplies.
+//!
+//! [`ScpiPowerSupply`] implements [`PowerSupply`] with the `VOLTage`, `CURRent`, `OUTPut` and
+//! `MEASure` commands of the SCPI standard. On supplies with several outputs, the channel is
+//! selected with `INSTrument:NSELect` before each command (e.g. Keysight E3631A, E36300).
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::driver::power_supply::{PowerSupply, ScpiPowerSupply};
+//! use linux_gpib_rs::ieee4882::Ieee4882;
+//! use linux_gpib_rs::instrument::{Board, Parameters};
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::{Arc, Mutex};
+//!
+//! let sent = Arc::new(Mutex::new(Vec::new()));
+//! let log = sent.clone();
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(5).unwrap(),
+//!     SecondaryAddress::default(),
+//!     move |message: &[u8]| {
+//!         log.lock().unwrap().push(String::from_utf8_lossy(message).into_owned());
+//!         match message {
+//!             b"*IDN?" => Some(Reply::from("Agilent Technologies,E3631A,0,2.1-5.0-1.0")),
+//!             b"INST:NSEL 2;:MEAS:CURR?" => Some(Reply::from("+1.25000000E-01")),
+//!             _ => None,
+//!         }
+//!     },
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let handle = board.find_listeners().unwrap()[0].open(Parameters::default()).unwrap();
+//! let identity = handle.identify().unwrap();
+//! let psu = ScpiPowerSupply::new(handle, identity).with_channels(3);
+//! psu.set_output(2, 12.0, 0.5).unwrap();
+//! psu.set_output_enabled(2, true).unwrap();
+//! assert_eq!(psu.measure_current(2).unwrap(), 0.125);
+//! assert!(psu.set_output(4, 1.0, 0.1).is_err());
+//! assert_eq!(
+//!     sent.lock().unwrap()[1..3],
+//!     ["INST:NSEL 2;:VOLT 12;:CURR 0.5", "INST:NSEL 2;:OUTP 1"]
+//! );
+//! ```
+//!
+
+use crate::driver::{DriverRegistry, InstrumentDriver, KEYSIGHT, check_channel};
+use crate::error::GpibError;
+use crate::ieee4882::Identity;
+use crate::instrument::{Instrument, InstrumentHandle, Parameters};
+use crate::scpi::ScpiQuery;
+use crate::scpi::command::ToScpi;
+use std::fmt;
+
+/// DC power supply. Channels are numbered from 1.
+pub trait PowerSupply: InstrumentDriver {
+    /// Number of outputs
+    fn channels(&self) -> u8;
+
+    /// Sets the voltage and the current limit of an output
+    fn set_output(&self, channel: u8, voltage: f64, current: f64) -> Result<(), GpibError>;
+
+    fn set_output_enabled(&self, channel: u8, enabled: bool) -> Result<(), GpibError>;
+
+    fn output_enabled(&self, channel: u8) -> Result<bool, GpibError>;
+
+    /// Measured output voltage
+    fn measure_voltage(&self, channel: u8) -> Result<f64, GpibError>;
+
+    /// Measured output current
+    fn measure_current(&self, channel: u8) -> Result<f64, GpibError>;
+}
+
+/// Registry of the power supplies known to accept the commands of [`ScpiPowerSupply`]: Keysight
+/// E3631A and E36300 series (3 outputs), and E3632A to E3634A (1 output)
+pub fn registry() -> DriverRegistry<dyn PowerSupply> {
+    let mut registry = DriverRegistry::<dyn PowerSupply>::new();
+    for manufacturer in KEYSIGHT {
+        registry.register(manufacturer, "E3631*", |instrument, identity| {
+            Ok(Box::new(
+                ScpiPowerSupply::open(instrument, identity)?.with_channels(3),
+            ))
+        });
+        registry.register(manufacturer, "E363*", |instrument, identity| {
+            Ok(Box::new(ScpiPowerSupply::open(instrument, identity)?))
+        });
+    }
+    registry
+}
+
+/// Power supply with the standard SCPI commands
+pub struct ScpiPowerSupply {
+    handle: InstrumentHandle,
+    identity: Identity,
+    channels: u8,
+}
+
+impl ScpiPowerSupply {
+    /// Single-output power supply (see `with_channels`)
+    pub fn new(handle: InstrumentHandle, identity: Identity) -> Self {
+        ScpiPowerSupply {
+            handle,
+            identity,
+            channels: 1,
+        }
+    }
+
+    /// Opens the instrument with the default parameters
+    pub fn open(instrument: Instrument, identity: Identity) -> Result<Self, GpibError> {
+        Ok(ScpiPowerSupply::new(
+            instrument.open(Parameters::default())?,
+            identity,
+        ))
+    }
+
+    /// Number of outputs, selected with `INST:NSEL` if more than one
+    pub fn with_channels(mut self, channels: u8) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+
+    pub fn into_inner(self) -> InstrumentHandle {
+        self.handle
+    }
+
+    /// Commands preceded by the selection of the channel
+    fn message(&self, channel: u8, commands: &[String]) -> Result<String, GpibError> {
+        check_channel(channel, self.channels)?;
+        let commands = commands.join(";:");
+        if self.channels > 1 {
+            Ok(format!("INST:NSEL {};:{}", channel, commands))
+        } else {
+            Ok(commands)
+        }
+    }
+
+    fn write(&self, channel: u8, commands: &[String]) -> Result<(), GpibError> {
+        self.handle
+            .blocking_write(&format!("{}\n", self.message(channel, commands)?))
+    }
+}
+
+impl InstrumentDriver for ScpiPowerSupply {
+    fn identity(&self) -> &Identity {
+        &self.identity
+    }
+}
+
+impl PowerSupply for ScpiPowerSupply {
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn set_output(&self, channel: u8, voltage: f64, current: f64) -> Result<(), GpibError> {
+        self.write(
+            channel,
+            &[
+                format!("VOLT {}", voltage.to_scpi()),
+                format!("CURR {}", current.to_scpi()),
+            ],
+        )
+    }
+
+    fn set_output_enabled(&self, channel: u8, enabled: bool) -> Result<(), GpibError> {
+        self.write(channel, &[format!("OUTP {}", enabled.to_scpi())])
+    }
+
+    fn output_enabled(&self, channel: u8) -> Result<bool, GpibError> {
+        self.handle
+            .query_bool(&self.message(channel, &["OUTP?".to_owned()])?)
+    }
+
+    fn measure_voltage(&self, channel: u8) -> Result<f64, GpibError> {
+        self.handle
+            .query_f64(&self.message(channel, &["MEAS:VOLT?".to_owned()])?)
+    }
+
+    fn measure_current(&self, channel: u8) -> Result<f64, GpibError> {
+        self.handle
+            .query_f64(&self.message(channel, &["MEAS:CURR?".to_owned()])?)
+    }
+}
+
+impl fmt::Debug for ScpiPowerSupply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ScpiPowerSupply({}, {:?}, {} channels)",
+            self.handle, self.identity, self.channels
+        )
+    }
+}
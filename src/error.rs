@@ -1,6 +1,7 @@
 #[cfg(feature = "linuxgpib")]
 use crate::lowlevel::utility::{AsyncIberr, ThreadIberr};
 
+use crate::scpi::ScpiError;
 use crate::status::IbStatus;
 use std::convert::Infallible;
 use std::error::Error;
@@ -9,8 +10,6 @@ use std::fmt;
 use std::num::TryFromIntError;
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
-#[cfg(feature = "async-tokio")]
-use tokio::task::JoinError;
 
 pub enum IbError {
     EDVR(i64), // In this case, we hold also ibcntl value
@@ -42,8 +41,8 @@ pub enum GpibError {
     DriverError(IbStatus, IbError),
     Timeout,
     ValueError(String),
-    #[cfg(feature = "async-tokio")]
-    TokioError(JoinError),
+    /// Errors reported by the instrument
+    InstrumentError(Vec<ScpiError>),
 }
 
 impl Error for GpibError {}
@@ -76,9 +75,9 @@ impl fmt::Display for GpibError {
             GpibError::ValueError(desc) => {
                 write!(f, "ValueError({})", desc)
             }
-            #[cfg(feature = "async-tokio")]
-            GpibError::TokioError(e) => {
-                write!(f, "Tokio Error ({})", e)
+            GpibError::InstrumentError(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "InstrumentError({})", errors.join("; "))
             }
         }
     }
@@ -96,9 +95,8 @@ impl fmt::Debug for GpibError {
             GpibError::ValueError(desc) => {
                 write!(f, "ValueError({})", desc)
             }
-            #[cfg(feature = "async-tokio")]
-            GpibError::TokioError(e) => {
-                write!(f, "Tokio Error ({:?})", e)
+            GpibError::InstrumentError(errors) => {
+                write!(f, "InstrumentError({:?})", errors)
             }
         }
     }
@@ -309,6 +307,7 @@ impl fmt::Debug for IbError {
 }
 
 impl IbError {
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
     /// Create IbError from iberr value
     pub fn from_iberr(iberr: linux_gpib_sys::iberr_type) -> Result<IbError, GpibError> {
         match iberr {
@@ -348,6 +347,7 @@ impl IbError {
         }
     }
 
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
     /// Create IbError from current Linux-GPIB global iberr variable
     pub unsafe fn current_global_error() -> Result<IbError, GpibError> {
         let status = unsafe { IbStatus::current_global_status() };
@@ -423,9 +423,15 @@ impl From<Infallible> for GpibError {
     }
 }
 
-#[cfg(feature = "async-tokio")]
-impl From<JoinError> for GpibError {
-    fn from(e: JoinError) -> GpibError {
-        GpibError::TokioError(e)
+impl From<GpibError> for std::io::Error {
+    fn from(e: GpibError) -> std::io::Error {
+        match e {
+            GpibError::Timeout
+            | GpibError::DriverError(IbStatus { timo: true, .. }, IbError::EABO) => {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, e)
+            }
+            GpibError::ValueError(_) => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            _ => std::io::Error::other(e),
+        }
     }
 }
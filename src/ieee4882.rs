@@ -0,0 +1,236 @@
+//!
+//! IEEE 488.2 common commands.
+//!
+//! The [`Ieee4882`] trait provides the common commands and queries (`*IDN?`, `*RST`, `*CLS`,
+//! `*ESR?`, ...) on top of two methods sending a command and reading a response. It is
+//! implemented for [`Instrument`] (multidevice API on the board) and [`InstrumentHandle`]
+//! (device descriptor, blocking calls). Commands are terminated with a newline, and the
+//! terminator of the responses is removed.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::ieee4882::Ieee4882;
+//! use linux_gpib_rs::instrument::Board;
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(7).unwrap(),
+//!     SecondaryAddress::default(),
+//!     |message: &[u8]| match message {
+//!         b"*IDN?" => Some(Reply::from("SIM,DMM,1234,1.0,rev B")),
+//!         b"*ESR?" => Some(Reply::from("+33")),
+//!         _ => None,
+//!     },
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let dmm = board.find_listeners().unwrap().remove(0);
+//! let identity = dmm.identify().unwrap();
+//! assert_eq!(identity.manufacturer, "SIM");
+//! assert_eq!(identity.firmware, "1.0,rev B");
+//! let event_status = dmm.event_status().unwrap();
+//! assert!(event_status.opc() && event_status.cme());
+//! ```
+//!
+
+use crate::error::GpibError;
+use crate::instrument::{Instrument, InstrumentHandle};
+use crate::status::{StandardEventStatus, StatusByte};
+use crate::types::IbSendEOI;
+use std::fmt;
+use std::str::FromStr;
+
+/// Identification of an instrument, as returned by `*IDN?`
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub manufacturer: String,
+    pub model: String,
+    /// Serial number, `0` if not available
+    pub serial: String,
+    /// Firmware level, or other information
+    pub firmware: String,
+}
+
+impl FromStr for Identity {
+    type Err = GpibError;
+
+    fn from_str(response: &str) -> Result<Self, Self::Err> {
+        // the firmware field may contain commas
+        let fields: Vec<&str> = response.trim().splitn(4, ',').map(str::trim).collect();
+        if fields.len() != 4 {
+            return Err(GpibError::ValueError(format!(
+                "Invalid identification {:?}: expected 4 comma-separated fields.",
+                response.trim()
+            )));
+        }
+        Ok(Identity {
+            manufacturer: fields[0].to_owned(),
+            model: fields[1].to_owned(),
+            serial: fields[2].to_owned(),
+            firmware: fields[3].to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.manufacturer, self.model, self.serial, self.firmware
+        )
+    }
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Identity({})", self)
+    }
+}
+
+/// IEEE 488.2 common commands
+pub trait Ieee4882 {
+    /// Sends a command, terminated with a newline
+    fn send_command(&self, command: &str) -> Result<(), GpibError>;
+
+    /// Sends a query, terminated with a newline, and returns the response without its terminator
+    fn query_response(&self, query: &str) -> Result<String, GpibError>;
+
+    /// Identification query (`*IDN?`)
+    fn identify(&self) -> Result<Identity, GpibError> {
+        self.query_response("*IDN?")?.parse()
+    }
+
+    /// Reset (`*RST`)
+    fn reset(&self) -> Result<(), GpibError> {
+        self.send_command("*RST")
+    }
+
+    /// Clear status (`*CLS`): clears the event registers and the error queue
+    fn clear_status(&self) -> Result<(), GpibError> {
+        self.send_command("*CLS")
+    }
+
+    /// Operation complete query (`*OPC?`): returns once all pending operations are complete.
+    /// The I/O timeout applies.
+    fn operation_complete(&self) -> Result<(), GpibError> {
+        let response = self.query_response("*OPC?")?;
+        if parse_integer(&response)? == 1 {
+            Ok(())
+        } else {
+            Err(GpibError::ValueError(format!(
+                "Unexpected response to *OPC?: {:?}.",
+                response
+            )))
+        }
+    }
+
+    /// Operation complete command (`*OPC`): sets the OPC bit of the standard event status
+    /// register once all pending operations are complete
+    fn set_operation_complete(&self) -> Result<(), GpibError> {
+        self.send_command("*OPC")
+    }
+
+    /// Wait-to-continue (`*WAI`): the instrument executes the following commands once all
+    /// pending operations are complete
+    fn wait(&self) -> Result<(), GpibError> {
+        self.send_command("*WAI")
+    }
+
+    /// Self-test query (`*TST?`), returns the result code (0 if the test passed)
+    fn self_test(&self) -> Result<i32, GpibError> {
+        parse_integer(&self.query_response("*TST?")?)
+    }
+
+    /// Standard event status register query (`*ESR?`), which also clears the register
+    fn event_status(&self) -> Result<StandardEventStatus, GpibError> {
+        Ok(StandardEventStatus::from_bits(parse_register(
+            &self.query_response("*ESR?")?,
+        )?))
+    }
+
+    /// Standard event status enable register query (`*ESE?`)
+    fn event_status_enable(&self) -> Result<StandardEventStatus, GpibError> {
+        Ok(StandardEventStatus::from_bits(parse_register(
+            &self.query_response("*ESE?")?,
+        )?))
+    }
+
+    /// Sets the standard event status enable register (`*ESE`)
+    fn set_event_status_enable(&self, enable: StandardEventStatus) -> Result<(), GpibError> {
+        self.send_command(&format!("*ESE {}", enable.bits()))
+    }
+
+    /// Status byte query (`*STB?`), with the MSS bit instead of RQS
+    fn status_byte(&self) -> Result<StatusByte, GpibError> {
+        Ok(StatusByte::from_bits(parse_register(
+            &self.query_response("*STB?")?,
+        )?))
+    }
+
+    /// Service request enable register query (`*SRE?`)
+    fn service_request_enable(&self) -> Result<StatusByte, GpibError> {
+        Ok(StatusByte::from_bits(parse_register(
+            &self.query_response("*SRE?")?,
+        )?))
+    }
+
+    /// Sets the service request enable register (`*SRE`). The RQS bit is ignored by the
+    /// instrument.
+    fn set_service_request_enable(&self, enable: StatusByte) -> Result<(), GpibError> {
+        self.send_command(&format!("*SRE {}", enable.bits()))
+    }
+
+    /// Saves the current settings to the given memory location (`*SAV`)
+    fn save(&self, location: u8) -> Result<(), GpibError> {
+        self.send_command(&format!("*SAV {}", location))
+    }
+
+    /// Recalls the settings saved in the given memory location (`*RCL`)
+    fn recall(&self, location: u8) -> Result<(), GpibError> {
+        self.send_command(&format!("*RCL {}", location))
+    }
+
+    /// Learn device setup query (`*LRN?`): returns the commands restoring the current settings
+    fn learn(&self) -> Result<String, GpibError> {
+        self.query_response("*LRN?")
+    }
+}
+
+impl Ieee4882 for Instrument {
+    fn send_command(&self, command: &str) -> Result<(), GpibError> {
+        self.send(format!("{}\n", command).as_bytes(), IbSendEOI::default())
+    }
+
+    fn query_response(&self, query: &str) -> Result<String, GpibError> {
+        Ok(self.query(&format!("{}\n", query))?.trim_end().to_owned())
+    }
+}
+
+impl Ieee4882 for InstrumentHandle {
+    fn send_command(&self, command: &str) -> Result<(), GpibError> {
+        self.blocking_write(&format!("{}\n", command))
+    }
+
+    fn query_response(&self, query: &str) -> Result<String, GpibError> {
+        Ok(self
+            .blocking_query(&format!("{}\n", query))?
+            .trim_end()
+            .to_owned())
+    }
+}
+
+/// Parses an integer response (`<NR1>`, possibly with a sign)
+fn parse_integer(response: &str) -> Result<i32, GpibError> {
+    response.trim().parse().map_err(|e| {
+        GpibError::ValueError(format!("Invalid integer response {:?} ({:?})", response, e))
+    })
+}
+
+/// Parses the value of an 8-bit register
+pub(crate) fn parse_register(response: &str) -> Result<u8, GpibError> {
+    parse_integer(response)?
+        .try_into()
+        .map_err(|_| GpibError::ValueError(format!("Register value {:?} out of range.", response)))
+}
@@ -1,20 +1,37 @@
-use crate::error::{GpibError, IbError};
-use crate::lowlevel::multidevice;
-#[cfg(feature = "async-tokio")]
-use crate::lowlevel::traditional::ibwait;
-use crate::lowlevel::traditional::{ibclr, ibdev, ibonl, ibrd, ibrda, ibtmo, ibwrt, ibwrta};
+use crate::backend::{Backend, default_backend};
+use crate::block::{self, BlockReader};
+use crate::error::GpibError;
+use crate::ieee4882;
 use crate::lowlevel::utility::Addr4882;
+#[cfg(feature = "async")]
+use crate::reactor::{Reactor, Transfer};
+use crate::resource::ResourceName;
+#[cfg(feature = "async")]
+use crate::scpi::FromScpi;
+use crate::scpi::{ErrorCheck, ScpiError};
+#[cfg(feature = "async")]
 use crate::status::IbStatus;
-use crate::types::{IbEosMode, IbOnline, IbSendEOI, IbTimeout, PrimaryAddress, SecondaryAddress};
+use crate::status::{StandardEventStatus, StatusByte};
+use crate::types::{
+    IbEosMode, IbLineStatus, IbOnline, IbOption, IbSendEOI, IbTimeout, PrimaryAddress,
+    SecondaryAddress,
+};
 use std::default::Default;
 use std::fmt;
 use std::os::raw::c_int;
+use std::sync::Arc;
 use std::time::Duration;
+#[cfg(feature = "async")]
+use std::time::Instant;
 
+/// Settings of a handle opened with [`Instrument::open`], built from `Parameters::default()`
+/// with the `with_*` methods, e.g.
+/// `Parameters::default().with_error_check(ErrorCheck::ErrorQueue)`.
 pub struct Parameters {
     pub timeout: IbTimeout,
     pub send_eoi: IbSendEOI,
     pub eos_mode: IbEosMode,
+    pub error_check: ErrorCheck,
 }
 
 impl Default for Parameters {
@@ -23,32 +40,73 @@ impl Default for Parameters {
             timeout: IbTimeout::T1s,
             send_eoi: IbSendEOI::default(),
             eos_mode: IbEosMode::default(),
+            error_check: ErrorCheck::default(),
         }
     }
 }
 
-#[derive(Clone, PartialEq)]
+impl Parameters {
+    pub fn with_timeout(mut self, timeout: IbTimeout) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_send_eoi(mut self, send_eoi: IbSendEOI) -> Self {
+        self.send_eoi = send_eoi;
+        self
+    }
+
+    pub fn with_eos_mode(mut self, eos_mode: IbEosMode) -> Self {
+        self.eos_mode = eos_mode;
+        self
+    }
+
+    /// Checks the instrument for errors after writes and queries (see [`ErrorCheck`])
+    pub fn with_error_check(mut self, error_check: ErrorCheck) -> Self {
+        self.error_check = error_check;
+        self
+    }
+}
+
+#[derive(Clone)]
 pub struct Board {
-    board_number: c_int,
+    pub(crate) board_number: c_int,
+    pub(crate) backend: Arc<dyn Backend>,
 }
 
 #[derive(Clone)]
 pub struct Instrument {
-    board: Board,
-    addr: Addr4882,
+    pub(crate) board: Board,
+    pub(crate) addr: Addr4882,
 }
 
 pub struct InstrumentHandle {
     ud: c_int,
+    #[cfg(feature = "async")]
+    board_number: c_int,
+    backend: Arc<dyn Backend>,
+    error_check: ErrorCheck,
 }
 
 impl Board {
+    /// Board with the default backend (see `backend::default_backend`)
     pub fn with_board_number(board_number: c_int) -> Self {
+        Board::with_backend(board_number, default_backend())
+    }
+
+    /// Board accessed through the provided backend
+    pub fn with_backend(board_number: c_int, backend: Arc<dyn Backend>) -> Self {
         Board {
-            board_number: board_number,
+            board_number,
+            backend,
         }
     }
 
+    /// Backend used to access the board
+    pub fn backend(&self) -> Arc<dyn Backend> {
+        self.backend.clone()
+    }
+
     /// clear devices
     pub fn clear_devices(&self, instruments: &Vec<Instrument>) -> Result<(), GpibError> {
         if instruments
@@ -59,20 +117,22 @@ impl Board {
                 "clear_devices can only send to devices belonging to this board.".to_owned(),
             ));
         }
-        let address_list = instruments.iter().map(|instr| instr.addr).collect();
-        multidevice::DevClearList(self.board_number, &address_list)
+        let address_list: Vec<Addr4882> = instruments.iter().map(|instr| instr.addr).collect();
+        self.backend.DevClearList(self.board_number, &address_list)
     }
 
     /// perform interface clear.
     /// The interface clear causes all devices to untalk and unlisten, puts them into serial poll disabled state
     /// (don't worry, you will still be able to conduct serial polls), and the board becomes controller-in-charge.
     pub fn interface_clear(&self) -> Result<(), GpibError> {
-        multidevice::SendIFC(self.board_number)
+        self.backend.SendIFC(self.board_number)
     }
 
     /// find listeners on the board
     pub fn find_listeners(&self) -> Result<Vec<Instrument>, GpibError> {
-        Ok(multidevice::FindAllLstn(self.board_number)?
+        Ok(self
+            .backend
+            .FindAllLstn(self.board_number)?
             .into_iter()
             .map(|addr| Instrument {
                 board: self.clone(),
@@ -81,6 +141,33 @@ impl Board {
             .collect())
     }
 
+    /// find listeners at the secondary addresses of a primary address
+    pub fn find_secondary_listeners(
+        &self,
+        primary_address: PrimaryAddress,
+    ) -> Result<Vec<Instrument>, GpibError> {
+        let mut instruments = Vec::new();
+        // 0x60 is secondary address 0, as 0 would disable the secondary address
+        for sad in 0x60..=0x7e {
+            let secondary_address = SecondaryAddress::new(sad)?;
+            if self
+                .backend
+                .ibln(self.board_number, primary_address, secondary_address)?
+            {
+                instruments.push(Instrument {
+                    board: self.clone(),
+                    addr: Addr4882::new(primary_address, secondary_address)?,
+                });
+            }
+        }
+        Ok(instruments)
+    }
+
+    /// status of the bus lines
+    pub fn lines(&self) -> Result<IbLineStatus, GpibError> {
+        self.backend.iblines(self.board_number)
+    }
+
     /// write data to multiple devices
     pub fn send_list(
         &self,
@@ -96,8 +183,16 @@ impl Board {
                 "clear_devices can only send to devices belonging to this board.".to_owned(),
             ));
         }
-        let address_list = instruments.iter().map(|instr| instr.addr).collect();
-        multidevice::SendList(self.board_number, &address_list, data, mode)
+        let address_list: Vec<Addr4882> = instruments.iter().map(|instr| instr.addr).collect();
+        self.backend
+            .SendList(self.board_number, &address_list, data, mode)
+    }
+}
+
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.board_number == other.board_number
+            && std::ptr::addr_eq(Arc::as_ptr(&self.backend), Arc::as_ptr(&other.backend))
     }
 }
 
@@ -122,20 +217,29 @@ impl fmt::Debug for Board {
 impl Instrument {
     /// Send data to the instrument with the multidevice 488.2 API
     pub fn send(&self, data: &[u8], mode: IbSendEOI) -> Result<(), GpibError> {
-        multidevice::Send(self.board.board_number, self.addr, data, mode)
+        self.board
+            .backend
+            .Send(self.board.board_number, self.addr, data, mode)
     }
 
-    /// Receive data from the instrument with the multidevice 488.2 API
+    /// Receive data from the instrument with the multidevice 488.2 API, and decode it as UTF-8
     pub fn receive(&self) -> Result<String, GpibError> {
+        let result = self.receive_raw()?;
+        let answer = String::from_utf8(result)?;
+        Ok(answer)
+    }
+
+    /// Receive bytes from the instrument with the multidevice 488.2 API, until EOI
+    pub fn receive_raw(&self) -> Result<Vec<u8>, GpibError> {
         const BUFFER_SIZE: usize = 1024;
         let mut result: Vec<u8> = Vec::new();
         loop {
             let mut buffer: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
-            let (status, n_read) = multidevice::Receive(
+            let (status, n_read) = self.board.backend.Receive(
                 self.board.board_number,
                 self.addr,
                 &mut buffer,
-                linux_gpib_sys::STOPend,
+                crate::sys::STOPend,
             )?;
             if n_read > 0 {
                 result.extend(buffer[0..n_read].to_vec());
@@ -144,8 +248,35 @@ impl Instrument {
                 break;
             }
         }
-        let answer = String::from_utf8(result)?;
-        Ok(answer)
+        Ok(result)
+    }
+
+    /// Clears the instrument (selected device clear)
+    pub fn clear(&self) -> Result<(), GpibError> {
+        self.board
+            .backend
+            .DevClear(self.board.board_number, self.addr)
+    }
+
+    /// Triggers the instrument (group execute trigger)
+    pub fn trigger(&self) -> Result<(), GpibError> {
+        self.board
+            .backend
+            .Trigger(self.board.board_number, self.addr)
+    }
+
+    /// Returns the instrument to local mode (go to local)
+    pub fn enable_local(&self) -> Result<(), GpibError> {
+        self.board
+            .backend
+            .EnableLocal(self.board.board_number, &[self.addr])
+    }
+
+    /// Asserts REN and addresses the instrument, which enters remote mode
+    pub fn enable_remote(&self) -> Result<(), GpibError> {
+        self.board
+            .backend
+            .EnableRemote(self.board.board_number, &[self.addr])
     }
 
     /// Performs send and receive
@@ -154,55 +285,118 @@ impl Instrument {
         self.receive()
     }
 
+    /// Performs send and receive, with bytes
+    pub fn query_raw(&self, data: &[u8]) -> Result<Vec<u8>, GpibError> {
+        self.send(data, IbSendEOI::default())?;
+        self.receive_raw()
+    }
+
+    /// Serial polls the instrument, and returns its status byte
+    pub fn serial_poll(&self) -> Result<StatusByte, GpibError> {
+        self.board
+            .backend
+            .ReadStatusByte(self.board.board_number, self.addr)
+    }
+
+    /// Send data, with a timeout applying to this operation only.
+    /// The board timeout is restored afterwards.
+    pub fn send_with_timeout(
+        &self,
+        data: &[u8],
+        mode: IbSendEOI,
+        timeout: Duration,
+    ) -> Result<(), GpibError> {
+        let guard = TimeoutGuard::apply(&self.board.backend, self.board.board_number, timeout)?;
+        guard.release(self.send(data, mode))
+    }
+
+    /// Receive data, with a timeout applying to this operation only.
+    /// The board timeout is restored afterwards.
+    pub fn receive_with_timeout(&self, timeout: Duration) -> Result<String, GpibError> {
+        let guard = TimeoutGuard::apply(&self.board.backend, self.board.board_number, timeout)?;
+        guard.release(self.receive())
+    }
+
+    /// Performs send and receive, with a timeout applying to this operation only.
+    /// The board timeout is restored afterwards.
+    pub fn query_with_timeout(&self, data: &str, timeout: Duration) -> Result<String, GpibError> {
+        let guard = TimeoutGuard::apply(&self.board.backend, self.board.board_number, timeout)?;
+        guard.release(self.query(data))
+    }
+
+    /// Receive an IEEE 488.2 definite or indefinite length block, and return its payload
+    pub fn read_block(&self) -> Result<Vec<u8>, GpibError> {
+        let mut reader = BlockReader::new();
+        while let Some(wanted) = reader.wanted() {
+            let mut buffer = vec![0; wanted];
+            let (status, n_read) = self.board.backend.Receive(
+                self.board.board_number,
+                self.addr,
+                &mut buffer,
+                crate::sys::STOPend,
+            )?;
+            if n_read == 0 && !status.end {
+                return Err(reader.truncated());
+            }
+            reader.feed(&buffer[0..n_read], status.end)?;
+        }
+        Ok(reader.into_data())
+    }
+
+    /// Send command followed by data as an IEEE 488.2 definite length block, and a newline
+    pub fn write_block(&self, command: &str, data: &[u8]) -> Result<(), GpibError> {
+        self.send(&block_message(command, data), IbSendEOI::default())
+    }
+
+    /// Instrument at the given address on the board
+    pub fn with_board(board: Board, addr: Addr4882) -> Self {
+        Instrument { board, addr }
+    }
+
     /// Create Instrument from a VISA string
     pub fn from_visa_string(address: &str) -> Result<Self, GpibError> {
-        let v: Vec<&str> = address.split("::").collect();
-        if v.len() < 2 {
-            return Err(GpibError::ValueError(format!(
-                "Invalid address '{}'.",
-                address
-            )));
-        }
-        if v[0].starts_with("GPIB") {
-            let (_, board_number) = v[0].split_at(4);
-            let board_number = i32::from_str_radix(board_number, 10).map_err(|e| {
-                GpibError::ValueError(format!(
-                    "Unable to parse GPIB Board index from string '{}' ({:?})",
-                    board_number, e,
-                ))
-            })?;
-            let primary_address = i32::from_str_radix(v[1], 10).map_err(|e| {
-                GpibError::ValueError(format!(
-                    "Unable to parse GPIB primary address from string '{}' ({:?})",
-                    v[1], e,
-                ))
-            })?;
-            Ok(Self {
-                board: Board::with_board_number(board_number),
-                addr: Addr4882::new(
-                    PrimaryAddress::new(primary_address)?,
-                    SecondaryAddress::default(),
-                )?,
-            })
-        } else {
-            Err(GpibError::ValueError(
-                "Address is expected as GPIBN::primary_address::INSTR".to_owned(),
-            ))
-        }
+        Instrument::from_resource_name(&address.parse()?)
+    }
+
+    /// Create Instrument on the board given in the resource name, with the default backend
+    pub fn from_resource_name(resource: &ResourceName) -> Result<Self, GpibError> {
+        Ok(Instrument::with_board(
+            Board::with_board_number(resource.board()),
+            resource.address()?,
+        ))
+    }
+
+    /// Resource name of the instrument
+    pub fn resource_name(&self) -> Result<ResourceName, GpibError> {
+        Ok(ResourceName::instrument(
+            self.board.board_number,
+            self.addr.primary_address()?,
+            self.addr.secondary_address()?,
+        ))
     }
 
     /// Create VISA string from board and address
     pub fn visa_string(&self) -> String {
-        format!(
-            "GPIB{}::{}::INSTR",
-            self.board.board_number,
-            self.addr.pad(),
-        )
+        match self.addr.sad() {
+            0 => format!(
+                "GPIB{}::{}::INSTR",
+                self.board.board_number,
+                self.addr.pad()
+            ),
+            // VISA secondary addresses are 0 to 30, without the 0x60 offset
+            sad => format!(
+                "GPIB{}::{}::{}::INSTR",
+                self.board.board_number,
+                self.addr.pad(),
+                sad - 0x60,
+            ),
+        }
     }
 
     /// Open with the traditional 488.1 API
     pub fn open(&self, params: Parameters) -> Result<InstrumentHandle, GpibError> {
-        let ud = ibdev(
+        let backend = self.board.backend.clone();
+        let ud = backend.ibdev(
             self.board.board_number,
             self.addr.primary_address()?,
             self.addr.secondary_address()?,
@@ -210,8 +404,14 @@ impl Instrument {
             params.send_eoi,
             params.eos_mode,
         )?;
-        ibclr(ud)?;
-        Ok(InstrumentHandle { ud })
+        backend.ibclr(ud)?;
+        Ok(InstrumentHandle {
+            ud,
+            #[cfg(feature = "async")]
+            board_number: self.board.board_number,
+            backend,
+            error_check: params.error_check,
+        })
     }
 }
 
@@ -233,7 +433,7 @@ impl InstrumentHandle {
         let mut result: Vec<u8> = Vec::new();
         loop {
             let mut buffer: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
-            let (status, n_read) = ibrd(self.ud, &mut buffer)?;
+            let (status, n_read) = self.backend.ibrd(self.ud, &mut buffer)?;
             if n_read > 0 {
                 result.extend(buffer[0..n_read].to_vec());
             }
@@ -244,42 +444,44 @@ impl InstrumentHandle {
         Ok(result)
     }
 
+    /// Reads an IEEE 488.2 definite or indefinite length block, and returns its payload.
+    /// End-of-string detection is disabled during the transfer, so that the payload can hold
+    /// any byte.
+    pub fn blocking_read_block(&self) -> Result<Vec<u8>, GpibError> {
+        let guard = ReadEosGuard::disable(&self.backend, self.ud)?;
+        let result = (|| {
+            let mut reader = BlockReader::new();
+            while let Some(wanted) = reader.wanted() {
+                let mut buffer = vec![0; wanted];
+                let (status, n_read) = self.backend.ibrd(self.ud, &mut buffer)?;
+                if n_read == 0 && !status.end {
+                    return Err(reader.truncated());
+                }
+                reader.feed(&buffer[0..n_read], status.end)?;
+            }
+            Ok(reader.into_data())
+        })();
+        guard.release(result)
+    }
+
     pub fn blocking_read(&self) -> Result<String, GpibError> {
         let result = self.blocking_read_raw()?;
         let answer = String::from_utf8(result)?;
         Ok(answer)
     }
 
-    #[cfg(feature = "async-tokio")]
+    /// Reads until EOI (or EOS, if enabled).
+    ///
+    /// This is cancel-safe: if the future is dropped before completion, the transfer is aborted
+    /// with `ibstop`, and the data received so far is lost.
+    #[cfg(feature = "async")]
     pub async fn read_raw(&self) -> Result<Vec<u8>, GpibError> {
         const BUFFER_SIZE: usize = 1024;
         let mut result: Vec<u8> = Vec::new();
         loop {
-            let mut buffer: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
-            unsafe { ibrda(self.ud, &mut buffer) }?;
-            let (status, n_read) = ibwait(
-                self.ud,
-                IbStatus::default()
-                    .with_timo(true)
-                    .with_cmpl(true)
-                    .with_end(true),
-            )
-            .await?;
-            if status.err {
-                return Err(GpibError::DriverError(
-                    status,
-                    #[cfg(feature = "linuxgpib")]
-                    IbError::current_thread_local_error()?,
-                    #[cfg(feature = "nigpib")]
-                    unsafe { IbError::current_global_error() }?,
-                ));
-            } else if status.timo {
-                return Err(GpibError::Timeout);
-            }
-            log::debug!("read({}) -> {} bytes read.", self.ud, n_read);
-            if n_read > 0 {
-                result.extend(buffer[0..n_read].to_vec());
-            }
+            let (status, buffer) = self.read_chunk(BUFFER_SIZE).await?;
+            let n_read = buffer.len();
+            result.extend(buffer);
             if status.end || n_read < BUFFER_SIZE || n_read == 0 {
                 break;
             }
@@ -287,7 +489,28 @@ impl InstrumentHandle {
         Ok(result)
     }
 
-    #[cfg(feature = "async-tokio")]
+    /// Reads an IEEE 488.2 definite or indefinite length block, and returns its payload.
+    /// End-of-string detection is disabled during the transfer, so that the payload can hold
+    /// any byte. The setting is restored afterwards, also if the future is dropped.
+    #[cfg(feature = "async")]
+    pub async fn read_block(&self) -> Result<Vec<u8>, GpibError> {
+        let guard = ReadEosGuard::disable(&self.backend, self.ud)?;
+        let result = async {
+            let mut reader = BlockReader::new();
+            while let Some(wanted) = reader.wanted() {
+                let (status, buffer) = self.read_chunk(wanted).await?;
+                if buffer.is_empty() && !status.end {
+                    return Err(reader.truncated());
+                }
+                reader.feed(&buffer, status.end)?;
+            }
+            Ok(reader.into_data())
+        }
+        .await;
+        guard.release(result)
+    }
+
+    #[cfg(feature = "async")]
     pub async fn read(&self) -> Result<String, GpibError> {
         let result = self.read_raw().await?;
         let answer = String::from_utf8(result)?;
@@ -295,32 +518,47 @@ impl InstrumentHandle {
     }
 
     pub fn blocking_write(&self, data: &str) -> Result<(), GpibError> {
-        let _n_written = ibwrt(self.ud, data.as_bytes())?;
+        self.blocking_write_raw(data.as_bytes())
+    }
+
+    pub fn blocking_write_raw(&self, data: &[u8]) -> Result<(), GpibError> {
+        self.blocking_send(data)?;
+        self.blocking_check_errors()
+    }
+
+    fn blocking_send(&self, data: &[u8]) -> Result<(), GpibError> {
+        let _n_written = self.backend.ibwrt(self.ud, data)?;
         Ok(())
     }
 
-    #[cfg(feature = "async-tokio")]
+    #[cfg(feature = "async")]
     pub async fn write(&self, data: &str) -> Result<(), GpibError> {
-        let data = data.as_bytes();
-        unsafe { ibwrta(self.ud, data) }?;
-        let (status, _count) = ibwait(
-            self.ud,
-            IbStatus::default()
-                .with_timo(true)
-                .with_cmpl(true)
-                .with_end(true)
-                .with_rqs(true),
-        )
-        .await?;
-        if status.err {
-            Err(GpibError::DriverError(
-                status,
-                #[cfg(feature = "linuxgpib")]
-                IbError::current_thread_local_error()?,
-                #[cfg(feature = "nigpib")]
-                unsafe { IbError::current_global_error() }?,
-            ))
-        } else if status.timo {
+        self.write_raw(data.as_bytes()).await
+    }
+
+    /// Writes data asynchronously.
+    ///
+    /// This is cancel-safe: the data is copied, and if the future is dropped before completion,
+    /// the transfer is aborted with `ibstop`.
+    #[cfg(feature = "async")]
+    pub async fn write_raw(&self, data: &[u8]) -> Result<(), GpibError> {
+        self.send(data).await?;
+        self.check_errors().await
+    }
+
+    /// Writes data asynchronously, without error checking
+    #[cfg(feature = "async")]
+    pub(crate) async fn send(&self, data: &[u8]) -> Result<(), GpibError> {
+        let ((status, _count), _buffer) = self
+            .transfer(
+                Transfer::Write(data.to_vec()),
+                IbStatus::default()
+                    .with_timo(true)
+                    .with_cmpl(true)
+                    .with_end(true),
+            )
+            .await?;
+        if status.timo {
             Err(GpibError::Timeout)
         } else if status.cmpl || status.end {
             Ok(())
@@ -332,32 +570,491 @@ impl InstrumentHandle {
         }
     }
 
+    /// Writes command followed by data as an IEEE 488.2 definite length block, and a newline
+    pub fn blocking_write_block(&self, command: &str, data: &[u8]) -> Result<(), GpibError> {
+        self.blocking_write_raw(&block_message(command, data))
+    }
+
+    /// Writes command followed by data as an IEEE 488.2 definite length block, and a newline
+    #[cfg(feature = "async")]
+    pub async fn write_block(&self, command: &str, data: &[u8]) -> Result<(), GpibError> {
+        self.write_raw(&block_message(command, data)).await
+    }
+
     pub fn blocking_query(&self, data: &str) -> Result<String, GpibError> {
-        self.blocking_write(data)?;
-        self.blocking_read()
+        let answer = String::from_utf8(self.blocking_query_raw(data.as_bytes())?)?;
+        Ok(answer)
     }
 
-    #[cfg(feature = "async-tokio")]
+    /// Writes data and reads the response. In checked mode, errors are checked after the read,
+    /// since querying them in between would discard the response.
+    pub fn blocking_query_raw(&self, data: &[u8]) -> Result<Vec<u8>, GpibError> {
+        let answer = self.blocking_query_unchecked(data)?;
+        self.blocking_check_errors()?;
+        Ok(answer)
+    }
+
+    /// Writes a query and reads the IEEE 488.2 block of the response (e.g. `:WAV:DATA?`).
+    /// In checked mode, errors are checked after the read.
+    pub fn blocking_query_block(&self, data: &str) -> Result<Vec<u8>, GpibError> {
+        self.blocking_send(data.as_bytes())?;
+        let payload = self.blocking_read_block()?;
+        self.blocking_check_errors()?;
+        Ok(payload)
+    }
+
+    /// Writes a query and reads a binary response without block header until END (e.g. `TRCB?`
+    /// on the SR830). End-of-string detection is disabled during the transfer. In checked mode,
+    /// errors are checked after the read.
+    pub fn blocking_query_binary(&self, data: &str) -> Result<Vec<u8>, GpibError> {
+        self.blocking_send(data.as_bytes())?;
+        let guard = ReadEosGuard::disable(&self.backend, self.ud)?;
+        let payload = guard.release(self.blocking_read_raw())?;
+        self.blocking_check_errors()?;
+        Ok(payload)
+    }
+
+    fn blocking_query_unchecked(&self, data: &[u8]) -> Result<Vec<u8>, GpibError> {
+        self.blocking_send(data)?;
+        self.blocking_read_raw()
+    }
+
+    /// Reads with a timeout applying to this operation only.
+    /// The timeout of the handle is restored afterwards.
+    pub fn blocking_read_with_timeout(&self, timeout: Duration) -> Result<String, GpibError> {
+        let guard = TimeoutGuard::apply(&self.backend, self.ud, timeout)?;
+        guard.release(self.blocking_read())
+    }
+
+    /// Writes with a timeout applying to this operation only.
+    /// The timeout of the handle is restored afterwards.
+    pub fn blocking_write_with_timeout(
+        &self,
+        data: &str,
+        timeout: Duration,
+    ) -> Result<(), GpibError> {
+        let guard = TimeoutGuard::apply(&self.backend, self.ud, timeout)?;
+        guard.release(self.blocking_write(data))
+    }
+
+    /// Queries with a timeout applying to this operation only.
+    /// The timeout of the handle is restored afterwards.
+    pub fn blocking_query_with_timeout(
+        &self,
+        data: &str,
+        timeout: Duration,
+    ) -> Result<String, GpibError> {
+        let guard = TimeoutGuard::apply(&self.backend, self.ud, timeout)?;
+        guard.release(self.blocking_query(data))
+    }
+
+    #[cfg(feature = "async")]
     pub async fn query(&self, data: &str) -> Result<String, GpibError> {
-        self.write(data).await?;
-        self.read().await
+        let answer = String::from_utf8(self.query_raw(data.as_bytes()).await?)?;
+        Ok(answer)
+    }
+
+    /// Writes data and reads the response. In checked mode, errors are checked after the read,
+    /// since querying them in between would discard the response.
+    #[cfg(feature = "async")]
+    pub async fn query_raw(&self, data: &[u8]) -> Result<Vec<u8>, GpibError> {
+        let answer = self.query_unchecked(data).await?;
+        self.check_errors().await?;
+        Ok(answer)
+    }
+
+    /// Queries, and parses the response (without its terminator) as a SCPI value. The typed
+    /// queries of `scpi::ScpiQuery` are the blocking counterparts.
+    #[cfg(feature = "async")]
+    pub async fn query_parsed<T: FromScpi>(&self, data: &str) -> Result<T, GpibError> {
+        T::from_scpi(self.query(data).await?.trim_end())
+    }
+
+    /// Writes a query and reads the IEEE 488.2 block of the response (e.g. `:WAV:DATA?`).
+    /// In checked mode, errors are checked after the read.
+    #[cfg(feature = "async")]
+    pub async fn query_block(&self, data: &str) -> Result<Vec<u8>, GpibError> {
+        self.send(data.as_bytes()).await?;
+        let payload = self.read_block().await?;
+        self.check_errors().await?;
+        Ok(payload)
+    }
+
+    #[cfg(feature = "async")]
+    async fn query_unchecked(&self, data: &[u8]) -> Result<Vec<u8>, GpibError> {
+        self.send(data).await?;
+        self.read_raw().await
+    }
+
+    /// Drains the SCPI error queue (`SYST:ERR?` until `0,"No error"`), and returns the errors
+    pub fn blocking_read_errors(&self) -> Result<Vec<ScpiError>, GpibError> {
+        let mut errors = Vec::new();
+        for _ in 0..MAX_QUEUED_ERRORS {
+            let error: ScpiError =
+                String::from_utf8(self.blocking_query_unchecked(b"SYST:ERR?\n")?)?.parse()?;
+            if !error.is_error() {
+                return Ok(errors);
+            }
+            errors.push(error);
+        }
+        Err(queue_not_drained(errors))
+    }
+
+    /// Drains the SCPI error queue (`SYST:ERR?` until `0,"No error"`), and returns the errors
+    #[cfg(feature = "async")]
+    pub async fn read_errors(&self) -> Result<Vec<ScpiError>, GpibError> {
+        let mut errors = Vec::new();
+        for _ in 0..MAX_QUEUED_ERRORS {
+            let error: ScpiError =
+                String::from_utf8(self.query_unchecked(b"SYST:ERR?\n").await?)?.parse()?;
+            if !error.is_error() {
+                return Ok(errors);
+            }
+            errors.push(error);
+        }
+        Err(queue_not_drained(errors))
+    }
+
+    /// Error checking mode of the handle (see [`Parameters::with_error_check`])
+    pub fn error_check(&self) -> ErrorCheck {
+        self.error_check
+    }
+
+    fn blocking_check_errors(&self) -> Result<(), GpibError> {
+        match self.error_check {
+            ErrorCheck::Disabled => Ok(()),
+            ErrorCheck::ErrorQueue => instrument_errors(self.blocking_read_errors()?),
+            ErrorCheck::EventStatus => {
+                let response = String::from_utf8(self.blocking_query_unchecked(b"*ESR?\n")?)?;
+                event_status_errors(StandardEventStatus::from_bits(ieee4882::parse_register(
+                    &response,
+                )?))
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn check_errors(&self) -> Result<(), GpibError> {
+        match self.error_check {
+            ErrorCheck::Disabled => Ok(()),
+            ErrorCheck::ErrorQueue => instrument_errors(self.read_errors().await?),
+            ErrorCheck::EventStatus => {
+                let response = String::from_utf8(self.query_unchecked(b"*ESR?\n").await?)?;
+                event_status_errors(StandardEventStatus::from_bits(ieee4882::parse_register(
+                    &response,
+                )?))
+            }
+        }
+    }
+
+    /// Reads with a timeout applying to this operation only.
+    /// The timeout of the handle is restored afterwards, also if the future is dropped.
+    #[cfg(feature = "async")]
+    pub async fn read_with_timeout(&self, timeout: Duration) -> Result<String, GpibError> {
+        let guard = TimeoutGuard::apply(&self.backend, self.ud, timeout)?;
+        guard.release(self.read().await)
+    }
+
+    /// Writes with a timeout applying to this operation only.
+    /// The timeout of the handle is restored afterwards, also if the future is dropped.
+    #[cfg(feature = "async")]
+    pub async fn write_with_timeout(&self, data: &str, timeout: Duration) -> Result<(), GpibError> {
+        let guard = TimeoutGuard::apply(&self.backend, self.ud, timeout)?;
+        guard.release(self.write(data).await)
+    }
+
+    /// Queries with a timeout applying to this operation only.
+    /// The timeout of the handle is restored afterwards, also if the future is dropped.
+    #[cfg(feature = "async")]
+    pub async fn query_with_timeout(
+        &self,
+        data: &str,
+        timeout: Duration,
+    ) -> Result<String, GpibError> {
+        let guard = TimeoutGuard::apply(&self.backend, self.ud, timeout)?;
+        guard.release(self.query(data).await)
+    }
+
+    /// Waits for the duration, with the timer of the I/O thread of the board, so that polling
+    /// loops do not depend on the async runtime
+    #[cfg(feature = "async")]
+    pub(crate) async fn sleep(&self, duration: Duration) -> Result<(), GpibError> {
+        Reactor::for_board(&self.backend, self.board_number)?
+            .sleep(duration)
+            .await
+    }
+
+    /// Waits until the instrument has completed its pending operations, without blocking the
+    /// bus as `*OPC?` does. `*ESE 1;*SRE 32;*OPC` makes the instrument request service once the
+    /// OPC bit is set, and the I/O thread of the board waits for the request (`ibwait` with RQS)
+    /// while the other instruments of the board are used. The instrument is then serial polled,
+    /// and the standard event status register, which is returned, is read with `*ESR?`.
+    ///
+    /// The timeout applies to the wait for the service request, and leaves the timeout of the
+    /// handle unchanged. The standard event status
+    /// register is cleared first, and the enable registers are left set. Automatic serial polling
+    /// (`IbcAUTOPOLL`, enabled by default) is used, so the instrument must not be subscribed to
+    /// an `SrqService` at the same time.
+    #[cfg(feature = "async")]
+    pub async fn wait_for_operation_complete(
+        &self,
+        timeout: Duration,
+    ) -> Result<StandardEventStatus, GpibError> {
+        self.execute_and_wait(None, timeout).await
+    }
+
+    /// Sends command, followed by `*OPC`, and waits until its operations are complete, as
+    /// `wait_for_operation_complete` does
+    #[cfg(feature = "async")]
+    pub async fn execute_and_wait(
+        &self,
+        command: Option<&str>,
+        timeout: Duration,
+    ) -> Result<StandardEventStatus, GpibError> {
+        // a previous OPC event would end the wait at once
+        self.query_unchecked(b"*ESR?\n").await?;
+        let message = match command {
+            Some(command) => format!("*ESE 1;*SRE 32;{};*OPC\n", command),
+            None => "*ESE 1;*SRE 32;*OPC\n".to_owned(),
+        };
+        self.send(message.as_bytes()).await?;
+        let reactor = Reactor::for_board(&self.backend, self.board_number)?;
+        let deadline = Instant::now() + timeout;
+        let event_status = loop {
+            let (status, _count) = reactor
+                .wait_until(self.ud, IbStatus::default().with_rqs(true), deadline)
+                .await?;
+            if status.timo {
+                return Err(GpibError::Timeout);
+            }
+            let status_byte = reactor.serial_poll(self.ud).await?;
+            if !status_byte.esb() {
+                log::debug!("Service request of {:?}: {}", self, status_byte);
+                continue;
+            }
+            let response = String::from_utf8(self.query_unchecked(b"*ESR?\n").await?)?;
+            let event_status = StandardEventStatus::from_bits(ieee4882::parse_register(&response)?);
+            if event_status.opc() {
+                break event_status;
+            }
+            log::debug!("Service request of {:?}: {}", self, event_status);
+        };
+        match self.error_check {
+            ErrorCheck::Disabled => {}
+            ErrorCheck::ErrorQueue => self.check_errors().await?,
+            ErrorCheck::EventStatus => event_status_errors(event_status)?,
+        }
+        Ok(event_status)
     }
 
     /// Clears the interface
     pub fn clear(&self) -> Result<(), GpibError> {
-        ibclr(self.ud)
+        self.backend.ibclr(self.ud)
+    }
+
+    /// Serial polls the instrument, and returns its status byte
+    pub fn serial_poll(&self) -> Result<StatusByte, GpibError> {
+        self.backend.ibrsp(self.ud)
     }
 
     /// Sets the timeout to the closest possible value
     pub fn set_timeout(&self, timeout: Duration) -> Result<(), GpibError> {
         let tmo = IbTimeout::closest_from(timeout);
-        ibtmo(self.ud, tmo)
+        self.backend.ibtmo(self.ud, tmo)
+    }
+
+    #[cfg(feature = "async")]
+    /// Reads at most count bytes asynchronously, until EOI or EOS
+    pub(crate) async fn read_chunk(&self, count: usize) -> Result<(IbStatus, Vec<u8>), GpibError> {
+        let ((status, n_read), mut buffer) = self
+            .transfer(
+                Transfer::Read(count),
+                IbStatus::default()
+                    .with_timo(true)
+                    .with_cmpl(true)
+                    .with_end(true),
+            )
+            .await?;
+        if status.timo {
+            return Err(GpibError::Timeout);
+        }
+        log::debug!("read({}) -> {} bytes read.", self.ud, n_read);
+        buffer.truncate(n_read);
+        Ok((status, buffer))
+    }
+
+    #[cfg(feature = "async")]
+    /// Runs an asynchronous transfer on the I/O thread of the board, and waits for one of the
+    /// events in status_mask.
+    ///
+    /// The buffer is owned by the I/O thread until the transfer is over. If the returned future
+    /// is dropped, the transfer is aborted with `ibstop`, and the buffer is released by the I/O
+    /// thread.
+    async fn transfer(
+        &self,
+        transfer: Transfer,
+        status_mask: IbStatus,
+    ) -> Result<((IbStatus, usize), Vec<u8>), GpibError> {
+        let reactor = Reactor::for_board(&self.backend, self.board_number)?;
+        let mut guard = StopOnDrop {
+            reactor: reactor.clone(),
+            ud: self.ud,
+            armed: true,
+        };
+        let (result, buffer) = reactor.transfer(self.ud, transfer, status_mask).await?;
+        guard.armed = false;
+        Ok((result?, buffer))
+    }
+}
+
+#[cfg(feature = "async")]
+/// Aborts the asynchronous transfer of a descriptor, unless disarmed
+struct StopOnDrop {
+    reactor: Arc<Reactor>,
+    ud: c_int,
+    armed: bool,
+}
+
+#[cfg(feature = "async")]
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        if self.armed {
+            log::debug!("Asynchronous transfer cancelled (ud = {})", self.ud);
+            self.reactor.stop(self.ud);
+        }
+    }
+}
+
+/// Temporary timeout of a board or device descriptor. The previous timeout is restored when the
+/// guard is released or dropped.
+struct TimeoutGuard {
+    backend: Arc<dyn Backend>,
+    ud: c_int,
+    previous: Option<IbTimeout>,
+}
+
+impl TimeoutGuard {
+    /// Applies the closest possible value to timeout
+    fn apply(
+        backend: &Arc<dyn Backend>,
+        ud: c_int,
+        timeout: Duration,
+    ) -> Result<TimeoutGuard, GpibError> {
+        let previous = IbTimeout::from_timeout(backend.ibask(ud, IbOption::TMO)?)?;
+        backend.ibtmo(ud, IbTimeout::closest_from(timeout))?;
+        Ok(TimeoutGuard {
+            backend: backend.clone(),
+            ud,
+            previous: Some(previous),
+        })
+    }
+
+    /// Restores the previous timeout, and passes the result of the operation through. An error
+    /// of the operation takes precedence over an error while restoring.
+    fn release<T>(mut self, result: Result<T, GpibError>) -> Result<T, GpibError> {
+        let restored = match self.previous.take() {
+            Some(previous) => self.backend.ibtmo(self.ud, previous),
+            None => Ok(()),
+        };
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+}
+
+impl Drop for TimeoutGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take()
+            && let Err(e) = self.backend.ibtmo(self.ud, previous)
+        {
+            log::debug!("Unable to restore the timeout (ud = {}): {:?}", self.ud, e);
+        }
+    }
+}
+
+/// End-of-string detection disabled on reads of a descriptor. The previous setting is restored
+/// when the guard is released or dropped.
+struct ReadEosGuard {
+    backend: Arc<dyn Backend>,
+    ud: c_int,
+    previous: Option<c_int>,
+}
+
+impl ReadEosGuard {
+    fn disable(backend: &Arc<dyn Backend>, ud: c_int) -> Result<ReadEosGuard, GpibError> {
+        let reos = backend.ibask(ud, IbOption::EOSrd)?;
+        if reos != 0 {
+            backend.ibconfig(ud, IbOption::EOSrd, 0)?;
+        }
+        Ok(ReadEosGuard {
+            backend: backend.clone(),
+            ud,
+            previous: (reos != 0).then_some(reos),
+        })
+    }
+
+    /// Restores the previous setting, and passes the result of the operation through. An error
+    /// of the operation takes precedence over an error while restoring.
+    fn release<T>(mut self, result: Result<T, GpibError>) -> Result<T, GpibError> {
+        let restored = match self.previous.take() {
+            Some(previous) => self.backend.ibconfig(self.ud, IbOption::EOSrd, previous),
+            None => Ok(()),
+        };
+        let value = result?;
+        restored?;
+        Ok(value)
     }
 }
 
+impl Drop for ReadEosGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take()
+            && let Err(e) = self.backend.ibconfig(self.ud, IbOption::EOSrd, previous)
+        {
+            log::debug!(
+                "Unable to restore end-of-string detection (ud = {}): {:?}",
+                self.ud,
+                e
+            );
+        }
+    }
+}
+
+/// Maximum number of entries read from the error queue. SCPI error queues are usually much
+/// smaller, this prevents an endless loop with an instrument which never reports `0,"No error"`.
+const MAX_QUEUED_ERRORS: usize = 100;
+
+fn queue_not_drained(errors: Vec<ScpiError>) -> GpibError {
+    GpibError::ValueError(format!(
+        "Error queue not empty after {} entries: {:?}",
+        errors.len(),
+        errors
+    ))
+}
+
+fn instrument_errors(errors: Vec<ScpiError>) -> Result<(), GpibError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(GpibError::InstrumentError(errors))
+    }
+}
+
+fn event_status_errors(event_status: StandardEventStatus) -> Result<(), GpibError> {
+    instrument_errors(ScpiError::from_event_status(event_status))
+}
+
+/// Command, definite length block and newline
+fn block_message(command: &str, data: &[u8]) -> Vec<u8> {
+    let mut message = command.as_bytes().to_vec();
+    message.extend(block::encode(data));
+    message.push(b'\n');
+    message
+}
+
 impl Drop for InstrumentHandle {
     fn drop(&mut self) {
-        match ibonl(self.ud, IbOnline::Close) {
+        match self.backend.ibonl(self.ud, IbOnline::Close) {
             Ok(()) => {}
             Err(e) => {
                 println!("Error while closing (ud = {}): {:?}", self.ud, e);
@@ -8,13 +8,18 @@
 //! This crate needs to link to an installed linux-gpib user library. It will look for `gpib/ib.h` in either `/usr/include` or `/usr/local/include`,
 //! and for `libgpib.so` in either `/usr/lib` or `/usr/local/lib`.
 //!
+//! The library is only needed with the `linuxgpib` (default) or `nigpib` feature. Without either
+//! of them, the `lowlevel::traditional` and `lowlevel::multidevice` modules and the native
+//! backend are left out, and the crate can be used with the simulated backend
+//! (`backend::sim`) only, e.g. to run tests on machines without linux-gpib.
+//!
 //!
 //! ## Example
 //!
 //! Add dependencies below to `Cargo.toml`
 //!
 //! ```toml
-//! linux-gpib-rs = { version = "0.2", features = ["async-tokio"] }
+//! linux-gpib-rs = { version = "0.3", features = ["async-tokio"] }
 //! ```
 //!
 //! Codes below will connect to the instrument on `GPIB0::1::INSTR` and print out its `*IDN?` response.
@@ -69,9 +74,30 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! The asynchronous methods do not depend on a particular runtime: the transfers, waits and
+//! serial polls are made by one I/O thread per board, which completes the futures. Configuration
+//! calls which return at once (timeout and end-of-string settings) are made on the calling
+//! thread. The `async` feature enables the asynchronous methods alone, `async-tokio` adds the
+//! tokio `AsyncRead`/`AsyncWrite` implementations of `InstrumentHandle::into_stream`, and
+//! `async-smol` adds the `futures-io` ones, for smol or async-std.
 
+pub mod backend;
+pub mod block;
+pub mod driver;
 pub mod error;
+pub mod ieee4882;
 pub mod instrument;
 pub mod lowlevel;
+#[cfg(feature = "async")]
+mod reactor;
+pub mod resource;
+pub mod scpi;
+#[cfg(feature = "async")]
+pub mod srq;
 pub mod status;
+#[cfg(any(feature = "async-tokio", feature = "async-smol"))]
+pub mod stream;
+mod sys;
 pub mod types;
+pub mod waveform;
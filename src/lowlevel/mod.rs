@@ -1,3 +1,5 @@
+#[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
 pub mod multidevice;
+#[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
 pub mod traditional;
 pub mod utility;
@@ -1,4 +1,6 @@
 #![allow(non_snake_case)]
+#[cfg(feature = "async")]
+use crate::backend::native::native_backend;
 use crate::error::{GpibError, IbError};
 use crate::lowlevel::utility::Addr4882;
 #[cfg(feature = "linuxgpib")]
@@ -6,7 +8,9 @@ use crate::lowlevel::utility::{ThreadIbcnt, ThreadIbcntl};
 #[cfg(feature = "nigpib")]
 use crate::lowlevel::utility::Ibcnt;
 
-use crate::status::IbStatus;
+#[cfg(feature = "async")]
+use crate::reactor::Reactor;
+use crate::status::{IbStatus, StatusByte};
 use crate::types::{IbSendEOI, PrimaryAddress, SecondaryAddress};
 use linux_gpib_sys::Addr4882_t;
 use std::default::Default;
@@ -125,7 +129,7 @@ pub fn DevClear(board: c_int, address: Addr4882) -> Result<(), GpibError> {
 /// clear multiple devices
 ///
 /// DevClear() causes the interface board specified by board_desc to send the clear command simultaneously to all the GPIB addresses specified by the addressList array. If addressList is empty or NULL, then the clear command is sent to all devices on the bus. If you only wish to clear a single device, DevClear() or ibclr() may be slightly more convenient.
-pub fn DevClearList(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibError> {
+pub fn DevClearList(board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -160,7 +164,7 @@ pub fn DevClearList(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibE
 ///If addressList is empty or NULL, then the REN line is unasserted and all devices enter local mode. The board must be system controller to change the state of the REN line.
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-enablelocal.html)
-pub fn EnableLocal(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibError> {
+pub fn EnableLocal(board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -191,7 +195,7 @@ pub fn EnableLocal(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibEr
 /// EnableRemote() asserts the REN (remote enable) line, and addresses all of the devices in the addressList array as listeners (causing them to enter remote mode). The board must be system controller.
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-enableremote.html)
-pub fn EnableRemote(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibError> {
+pub fn EnableRemote(board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -222,7 +226,10 @@ pub fn EnableRemote(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibE
 /// FindRQS will serial poll the GPIB addresses specified in the addressList array until it finds a device requesting service. The status byte of the device requesting service and its address are returned. If no device requesting service is found, an ETAB error is returned.
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-findrqs.html)
-pub fn FindRQS(board: c_int, addresses: &Vec<Addr4882>) -> Result<(Addr4882, c_short), GpibError> {
+pub fn FindRQS(
+    board: c_int,
+    addresses: &[Addr4882],
+) -> Result<(Addr4882, StatusByte), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -254,7 +261,7 @@ pub fn FindRQS(board: c_int, addresses: &Vec<Addr4882>) -> Result<(Addr4882, c_s
                 "index stored in Ibcnt is larger than addresses array length".to_owned(),
             ))
         } else {
-            Ok((addresses[index], status_byte))
+            Ok((addresses[index], StatusByte::from_bits(status_byte as u8)))
         }
     }
 }
@@ -346,7 +353,7 @@ pub fn PPollConfig(
 /// disable devices' parallel poll response
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-ppollunconfig.html)
-pub fn PPollUnconfig(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibError> {
+pub fn PPollUnconfig(board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -412,7 +419,7 @@ pub fn RcvRespMsg(board: c_int, buffer: &mut [u8], termination: c_int) -> Result
 /// ReadStatusByte() causes the board specified by the board descriptor board_desc to serial poll the GPIB address specified by address. The status byte is stored at the location specified by the result pointer. If you wish to serial poll multiple devices, it may be slightly more efficient to use AllSPoll(). Serial polls may also be conducted with the 'traditional API' function ibrsp().
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-readstatusbyte.html)
-pub fn ReadStatusByte(board: c_int, address: Addr4882) -> Result<c_short, GpibError> {
+pub fn ReadStatusByte(board: c_int, address: Addr4882) -> Result<StatusByte, GpibError> {
     let mut result: c_short = 0;
     unsafe {
         linux_gpib_sys::ReadStatusByte(board, address.addr, &mut result);
@@ -430,7 +437,7 @@ pub fn ReadStatusByte(board: c_int, address: Addr4882) -> Result<c_short, GpibEr
             unsafe { IbError::current_global_error() }?,
         ))
     } else {
-        Ok(result)
+        Ok(StatusByte::from_bits(result as u8))
     }
 }
 
@@ -521,7 +528,7 @@ pub fn ReceiveSetup(board: c_int, address: Addr4882) -> Result<(), GpibError> {
 /// - The *RST message is sent to every device specified in the addressList.
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-resetsys.html)
-pub fn ResetSys(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibError> {
+pub fn ResetSys(board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -623,7 +630,7 @@ pub fn SendIFC(board: c_int) -> Result<(), GpibError> {
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-sendlist.html)
 pub fn SendList(
     board: c_int,
-    addresses: &Vec<Addr4882>,
+    addresses: &[Addr4882],
     buffer: &[u8],
     eot_mode: IbSendEOI,
 ) -> Result<(), GpibError> {
@@ -693,7 +700,7 @@ pub fn SendLLO(board: c_int) -> Result<(), GpibError> {
 /// put devices into remote with lockout state
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-setrwls.html)
-pub fn SetRWLS(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibError> {
+pub fn SetRWLS(board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -753,7 +760,7 @@ pub fn TestSRQ(board: c_int) -> Result<bool, GpibError> {
 /// perform self-test queries on devices
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-testsys.html)
-pub fn TestSys(board: c_int, addresses: &Vec<Addr4882>) -> Result<Vec<c_short>, GpibError> {
+pub fn TestSys(board: c_int, addresses: &[Addr4882]) -> Result<Vec<c_short>, GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -808,7 +815,7 @@ pub fn Trigger(board: c_int, address: Addr4882) -> Result<(), GpibError> {
 /// trigger multiple devices
 ///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-triggerlist.html)
-pub fn TriggerList(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibError> {
+pub fn TriggerList(board: c_int, addresses: &[Addr4882]) -> Result<(), GpibError> {
     let mut instruments = addresses
         .iter()
         .map(|a| a.addr)
@@ -834,31 +841,43 @@ pub fn TriggerList(board: c_int, addresses: &Vec<Addr4882>) -> Result<(), GpibEr
     }
 }
 
-#[cfg(feature = "async-tokio")]
+/// sleep until the SRQ bus line is asserted, blocking the current thread
+///
+/// The result is 1 if the SRQ line was asserted, and 0 if the board timeout expired first.
+///
+/// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-waitsrq.html)
+pub fn blocking_WaitSRQ(board: c_int) -> Result<c_short, GpibError> {
+    let mut result: c_short = 0;
+    unsafe {
+        linux_gpib_sys::WaitSRQ(board, &mut result);
+    }
+    #[cfg(feature = "linuxgpib")]
+    let status = IbStatus::current_thread_local_status();
+    #[cfg(feature = "nigpib")]
+    let status = unsafe { IbStatus::current_global_status() };
+    if status.err {
+        Err(GpibError::DriverError(
+            status,
+            #[cfg(feature = "linuxgpib")]
+            IbError::current_thread_local_error()?,
+            #[cfg(feature = "nigpib")]
+            unsafe { IbError::current_global_error() }?,
+        ))
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "async")]
 /// sleep until the SRQ bus line is asserted
 ///
+/// The SRQ line is polled by the I/O thread of the board. The result is 1 if the SRQ line was
+/// asserted, and 0 if the board timeout expired first.
+///
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-waitsrq.html)
 pub async fn WaitSRQ(board: c_int) -> Result<c_short, GpibError> {
-    tokio::task::spawn_blocking(move || {
-        let mut result: c_short = 0;
-        unsafe {
-            linux_gpib_sys::WaitSRQ(board, &mut result);
-        }
-        #[cfg(feature = "linuxgpib")]
-        let status = IbStatus::current_thread_local_status();
-        #[cfg(feature = "nigpib")]
-        let status = unsafe { IbStatus::current_global_status() };
-        if status.err {
-            Err(GpibError::DriverError(
-                status,
-                #[cfg(feature = "linuxgpib")]
-                IbError::current_thread_local_error()?,
-                #[cfg(feature = "nigpib")]
-                unsafe { IbError::current_global_error() }?,
-            ))
-        } else {
-            Ok(result)
-        }
-    })
-    .await?
+    let (status, _count) = Reactor::for_board(&native_backend(), board)?
+        .wait(board, IbStatus::default().with_srqi(true).with_timo(true))
+        .await?;
+    Ok(if status.srqi { 1 } else { 0 })
 }
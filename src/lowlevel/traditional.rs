@@ -1,9 +1,13 @@
+#[cfg(feature = "async")]
+use crate::backend::native::native_backend;
 use crate::error::{GpibError, IbError};
 #[cfg(feature = "nigpib")]
 use crate::lowlevel::utility::Ibcnt;
 #[cfg(feature = "linuxgpib")]
 use crate::lowlevel::utility::{AsyncIbcntl, ThreadIbcnt, ThreadIbcntl};
-use crate::status::IbStatus;
+#[cfg(feature = "async")]
+use crate::reactor::Reactor;
+use crate::status::{IbStatus, StatusByte};
 use crate::types::{
     IbEosMode, IbEvent, IbLineStatus, IbOnline, IbOption, IbSendEOI, IbTimeout, PrimaryAddress,
     SecondaryAddress,
@@ -559,7 +563,7 @@ pub fn ibrsc(ud: c_int, request_control: c_int) -> Result<(), GpibError> {
 
 /// ibrsp --  read status byte / serial poll (device)
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-ibrsp.html)
-pub fn ibrsp(ud: c_int) -> Result<c_char, GpibError> {
+pub fn ibrsp(ud: c_int) -> Result<StatusByte, GpibError> {
     let mut result: c_char = 0;
     let status =
         IbStatus::from_ibsta(unsafe { linux_gpib_sys::ibrsp(ud, &mut result as *mut c_char) });
@@ -572,7 +576,7 @@ pub fn ibrsp(ud: c_int) -> Result<c_char, GpibError> {
             unsafe { IbError::current_global_error() }?,
         ))
     } else {
-        Ok(result)
+        Ok(StatusByte::from_bits(result as u8))
     }
 }
 
@@ -744,36 +748,44 @@ pub fn ibvers() -> Result<String, GpibError> {
     Ok(unsafe { CStr::from_ptr(buffer_ptr) }.to_str()?.to_owned())
 }
 
-#[cfg(feature = "async-tokio")]
-/// wait for event (board or device)
+/// wait for event (board or device), blocking the current thread
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-ibwait.html)
-pub async fn ibwait(ud: c_int, status_mask: IbStatus) -> Result<(IbStatus, usize), GpibError> {
+pub fn blocking_ibwait(ud: c_int, status_mask: IbStatus) -> Result<(IbStatus, usize), GpibError> {
     let status_mask = status_mask.as_status_mask();
-    let res = tokio::task::spawn_blocking(move || {
-        let status = IbStatus::from_ibsta(unsafe { linux_gpib_sys::ibwait(ud, status_mask) });
-        if status.err {
-            Err(GpibError::DriverError(
-                status,
-                #[cfg(feature = "linuxgpib")]
-                IbError::current_async_local_error()?,
-                #[cfg(feature = "nigpib")]
-                unsafe { IbError::current_global_error() }?,
-            ))
-        } else {
-            Ok((
-                status,
-                #[cfg(feature = "linuxgpib")]
-                AsyncIbcntl().try_into()?,
-                #[cfg(feature = "nigpib")]
-                Ibcnt().try_into()?,
-            ))
-        }
-    })
-    .await?;
+    let status = IbStatus::from_ibsta(unsafe { linux_gpib_sys::ibwait(ud, status_mask) });
+    let res = if status.err {
+        Err(GpibError::DriverError(
+            status,
+            #[cfg(feature = "linuxgpib")]
+            IbError::current_async_local_error()?,
+            #[cfg(feature = "nigpib")]
+            unsafe { IbError::current_global_error() }?,
+        ))
+    } else {
+        Ok((
+            status,
+            #[cfg(feature = "linuxgpib")]
+            AsyncIbcntl().try_into()?,
+            #[cfg(feature = "nigpib")]
+            Ibcnt().try_into()?,
+        ))
+    };
     log::debug!("ibwait({}, {}) -> {:?}", ud, status_mask, res);
     res
 }
 
+#[cfg(feature = "async")]
+/// wait for event (board or device)
+///
+/// The event is polled by the I/O thread of the board.
+///
+/// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-ibwait.html)
+pub async fn ibwait(ud: c_int, status_mask: IbStatus) -> Result<(IbStatus, usize), GpibError> {
+    Reactor::for_descriptor(&native_backend(), ud)?
+        .wait(ud, status_mask)
+        .await
+}
+
 /// ibwrt -- write data bytes (board or device)
 /// See: [Linux GPIB Reference](https://linux-gpib.sourceforge.io/doc_html/reference-function-ibwrt.html)
 pub fn ibwrt(ud: c_int, data: &[u8]) -> Result<usize, GpibError> {
@@ -2,9 +2,10 @@
 
 use crate::error::GpibError;
 use crate::types::{PrimaryAddress, SecondaryAddress};
-use linux_gpib_sys::{Addr4882_t, NOADDR};
+use crate::sys::{Addr4882_t, NOADDR};
 use std::default::Default;
 use std::fmt;
+#[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
 use std::os::raw::{c_int, c_uint, c_long};
 
 /// pack primary and secondary address into an Addr4882_t value
@@ -12,7 +13,7 @@ pub fn MakeAddr(pad: u16, sad: u16) -> Addr4882_t {
     let first_part: u16 = pad & 0xff;
     let second_part: u16 = (sad << 8) & 0xff00;
 
-    #[cfg(feature = "linuxgpib")]
+    #[cfg(not(feature = "nigpib"))]
     let res = first_part | second_part;
     #[cfg(feature = "nigpib")]
     let res = (first_part | second_part).try_into().unwrap();
@@ -101,7 +102,7 @@ pub fn AsyncIbsta() -> c_int {
 
 #[derive(Clone, Copy)]
 pub struct Addr4882 {
-    pub addr: linux_gpib_sys::Addr4882_t,
+    pub addr: Addr4882_t,
 }
 
 impl Addr4882 {
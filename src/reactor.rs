@@ -0,0 +1,533 @@
+//!
+//! Per-board I/O reactor used by the asynchronous API.
+//!
+//! Each board of a backend gets a dedicated thread, started on first use, which makes the driver
+//! calls of the asynchronous operations in the order they are submitted. Waiting does not block
+//! the thread: the awaited events are polled with `ibwait` and an empty status mask, which
+//! returns the current status at once, and the futures are completed through channels when an
+//! event occurs or when the timeout of the descriptor expires.
+//!
+//! The number of threads therefore does not depend on the number of pending operations, and
+//! the thread-local status of Linux GPIB (`ThreadIbsta`, `AsyncIbsta`, `AsyncIbcntl`, ...) is
+//! always read on the thread which made the driver call.
+//!
+//! The thread also dispatches the service requests of the board (see [`crate::srq`]): while
+//! there are subscribers, the SRQ line is polled every `SRQ_POLL_PERIOD`, and the subscribed
+//! devices requesting service are serial polled with `FindRQS`. The thread does not block on the
+//! SRQ line, since it would delay the other requests, and need a timeout of the board.
+//!
+//! It finally completes the timers of the polling loops (see `InstrumentHandle::sleep`), which
+//! therefore do not depend on the async runtime either.
+//!
+//! The thread only holds a weak reference to the backend, and stops once the backend is dropped
+//! (e.g. a `SimulatedBus` at the end of a test). Pending requests then complete with an error.
+//!
+
+use crate::backend::Backend;
+use crate::error::{GpibError, IbError};
+use crate::lowlevel::utility::Addr4882;
+use crate::status::{IbStatus, StatusByte};
+use crate::types::{IbOption, IbTimeout};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::os::raw::c_int;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Period at which the awaited events are polled.
+const POLL_PERIOD: Duration = Duration::from_millis(2);
+
+/// Period at which the SRQ line is polled while there are subscribers. It is longer than
+/// `POLL_PERIOD`, so that an idle subscription makes few driver calls.
+const SRQ_POLL_PERIOD: Duration = Duration::from_millis(20);
+
+/// Delay before serial polling again when SRQ is asserted, but none of the subscribed devices
+/// requests service (e.g. SRQ asserted by another device).
+const SRQ_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Period at which an idle I/O thread checks that its backend is still alive.
+const IDLE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Reactors by backend instance and board index
+static REACTORS: Mutex<BTreeMap<(usize, c_int), Entry>> = Mutex::new(BTreeMap::new());
+
+struct Entry {
+    backend: Weak<dyn Backend>,
+    reactor: Arc<Reactor>,
+}
+
+type IoResult = Result<(IbStatus, usize), GpibError>;
+
+/// Asynchronous transfer started by the reactor
+pub(crate) enum Transfer {
+    /// Read at most the given number of bytes
+    Read(usize),
+    Write(Vec<u8>),
+}
+
+enum Request {
+    Stop(c_int),
+    SerialPoll {
+        ud: c_int,
+        completer: Completer<Result<StatusByte, GpibError>>,
+    },
+    Subscribe(Subscriber),
+    Sleep {
+        deadline: Instant,
+        completer: Completer<()>,
+    },
+    Wait {
+        ud: c_int,
+        transfer: Option<Transfer>,
+        status_mask: IbStatus,
+        /// Replaces the timeout of the descriptor
+        deadline: Option<Instant>,
+        completer: Completer<(IoResult, Vec<u8>)>,
+    },
+}
+
+struct Waiter {
+    ud: c_int,
+    status_mask: IbStatus,
+    deadline: Option<Instant>,
+    /// Buffer of the transfer in progress, owned by the reactor until the transfer is over
+    buffer: Option<Vec<u8>>,
+    completer: Completer<(IoResult, Vec<u8>)>,
+}
+
+/// Receiver of the status bytes of a device, when it requests service
+pub(crate) struct Subscriber {
+    pub(crate) address: Addr4882,
+    /// Cleared when the subscriber is dropped
+    pub(crate) active: Arc<AtomicBool>,
+    pub(crate) deliver: Box<dyn FnMut(StatusByte) + Send>,
+}
+
+/// Handle to the I/O thread of a board
+pub(crate) struct Reactor {
+    board: c_int,
+    requests: mpsc::Sender<Request>,
+}
+
+impl Reactor {
+    /// Reactor of a board, started on first use
+    pub(crate) fn for_board(
+        backend: &Arc<dyn Backend>,
+        board: c_int,
+    ) -> Result<Arc<Reactor>, GpibError> {
+        let key = (Arc::as_ptr(backend) as *const () as usize, board);
+        let mut reactors = REACTORS.lock().unwrap_or_else(PoisonError::into_inner);
+        // the threads of dropped backends stop by themselves, and their address may be reused
+        reactors.retain(|_, entry| entry.backend.strong_count() > 0);
+        if let Some(entry) = reactors.get(&key) {
+            return Ok(entry.reactor.clone());
+        }
+        let (requests, receiver) = mpsc::channel();
+        let thread_backend = Arc::downgrade(backend);
+        std::thread::Builder::new()
+            .name(format!("gpib-board-{}", board))
+            .spawn(move || run(thread_backend, board, receiver))
+            .map_err(|e| {
+                GpibError::ValueError(format!(
+                    "Unable to start the I/O thread of board {} ({})",
+                    board, e
+                ))
+            })?;
+        let reactor = Arc::new(Reactor { board, requests });
+        reactors.insert(
+            key,
+            Entry {
+                backend: Arc::downgrade(backend),
+                reactor: reactor.clone(),
+            },
+        );
+        Ok(reactor)
+    }
+
+    /// Reactor of the board a board or device descriptor belongs to
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
+    pub(crate) fn for_descriptor(
+        backend: &Arc<dyn Backend>,
+        ud: c_int,
+    ) -> Result<Arc<Reactor>, GpibError> {
+        // board descriptors are the board index
+        let board = backend.ibask(ud, IbOption::BNA).unwrap_or(ud);
+        Reactor::for_board(backend, board)
+    }
+
+    /// Waits for one of the events in status_mask. If status_mask includes `timo`, the wait
+    /// ends with `timo` set when the timeout of the descriptor expires.
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
+    pub(crate) async fn wait(&self, ud: c_int, status_mask: IbStatus) -> IoResult {
+        let (result, _buffer) = self.submit(ud, None, status_mask, None).await?;
+        result
+    }
+
+    /// Waits for one of the events in status_mask, or until the deadline, in which case the
+    /// wait ends with `timo` set. The timeout of the descriptor is not used, nor changed.
+    pub(crate) async fn wait_until(
+        &self,
+        ud: c_int,
+        status_mask: IbStatus,
+        deadline: Instant,
+    ) -> IoResult {
+        let (result, _buffer) = self
+            .submit(ud, None, status_mask.with_timo(true), Some(deadline))
+            .await?;
+        result
+    }
+
+    /// Serial polls a device (`ibrsp`) on the I/O thread
+    pub(crate) async fn serial_poll(&self, ud: c_int) -> Result<StatusByte, GpibError> {
+        let (completer, completion) = channel();
+        let _ = self.requests.send(Request::SerialPoll { ud, completer });
+        completion.await?
+    }
+
+    /// Starts an asynchronous transfer, and waits for one of the events in status_mask, as
+    /// [`Reactor::wait`].
+    ///
+    /// The buffer of the transfer is owned by the I/O thread, and given back with the result once
+    /// the transfer is over. If the future is dropped, the buffer is released by the I/O thread
+    /// when the transfer completes (see [`Reactor::stop`]).
+    pub(crate) async fn transfer(
+        &self,
+        ud: c_int,
+        transfer: Transfer,
+        status_mask: IbStatus,
+    ) -> Result<(IoResult, Vec<u8>), GpibError> {
+        self.submit(ud, Some(transfer), status_mask, None).await
+    }
+
+    /// Completes after the duration, without blocking the caller or depending on a runtime
+    pub(crate) async fn sleep(&self, duration: Duration) -> Result<(), GpibError> {
+        let (completer, completion) = channel();
+        let _ = self.requests.send(Request::Sleep {
+            deadline: Instant::now() + duration,
+            completer,
+        });
+        completion.await
+    }
+
+    /// Aborts the asynchronous transfer of a descriptor, without waiting
+    pub(crate) fn stop(&self, ud: c_int) {
+        if self.requests.send(Request::Stop(ud)).is_err() {
+            log::debug!(
+                "I/O thread of board {} stopped, ibstop({}) ignored",
+                self.board,
+                ud
+            );
+        }
+    }
+
+    /// Delivers the status byte of a device to the subscriber whenever it requests service
+    pub(crate) fn subscribe(&self, subscriber: Subscriber) -> Result<(), GpibError> {
+        self.requests
+            .send(Request::Subscribe(subscriber))
+            .map_err(|_| {
+                GpibError::ValueError(format!("The I/O thread of board {} stopped.", self.board))
+            })
+    }
+
+    fn submit(
+        &self,
+        ud: c_int,
+        transfer: Option<Transfer>,
+        status_mask: IbStatus,
+        deadline: Option<Instant>,
+    ) -> Completion<(IoResult, Vec<u8>)> {
+        let (completer, completion) = channel();
+        // if the thread is gone, the completer is dropped with the request, which completes
+        // the future with an error
+        let _ = self.requests.send(Request::Wait {
+            ud,
+            transfer,
+            status_mask,
+            deadline,
+            completer,
+        });
+        completion
+    }
+}
+
+impl fmt::Debug for Reactor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Reactor(board = {})", self.board)
+    }
+}
+
+/// Loop of the I/O thread, until the backend is dropped
+fn run(backend: Weak<dyn Backend>, board: c_int, requests: Receiver<Request>) {
+    let mut waiters: Vec<Waiter> = Vec::new();
+    let mut subscribers: Vec<Subscriber> = Vec::new();
+    let mut sleepers: Vec<(Instant, Completer<()>)> = Vec::new();
+    let mut next_srq_poll = Instant::now();
+    loop {
+        let next_wake = sleepers.iter().map(|(deadline, _)| *deadline).min();
+        let mut timeout = next_wake.map_or(IDLE_PERIOD, |deadline| {
+            deadline
+                .saturating_duration_since(Instant::now())
+                .min(IDLE_PERIOD)
+        });
+        if !waiters.is_empty() {
+            timeout = timeout.min(POLL_PERIOD);
+        } else if !subscribers.is_empty() {
+            timeout = timeout.min(next_srq_poll.saturating_duration_since(Instant::now()));
+        }
+        let request = match requests.recv_timeout(timeout) {
+            Ok(request) => Some(request),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        let Some(strong_backend) = backend.upgrade() else {
+            log::debug!("Backend dropped, I/O thread of board {} stopped", board);
+            return;
+        };
+        let backend = strong_backend.as_ref();
+        for request in request.into_iter().chain(requests.try_iter()) {
+            match request {
+                Request::Stop(ud) => {
+                    if let Err(e) = backend.ibstop(ud) {
+                        log::debug!("ibstop({}) -> {:?}", ud, e);
+                    }
+                }
+                Request::SerialPoll { ud, completer } => completer.complete(backend.ibrsp(ud)),
+                Request::Subscribe(subscriber) => subscribers.push(subscriber),
+                Request::Sleep {
+                    deadline,
+                    completer,
+                } => sleepers.push((deadline, completer)),
+                Request::Wait {
+                    ud,
+                    transfer,
+                    status_mask,
+                    deadline,
+                    completer,
+                } => match start(backend, ud, transfer, status_mask, deadline) {
+                    Ok((deadline, buffer)) => waiters.push(Waiter {
+                        ud,
+                        status_mask,
+                        deadline,
+                        buffer,
+                        completer,
+                    }),
+                    Err(e) => completer.complete((Err(e), Vec::new())),
+                },
+            }
+        }
+        let now = Instant::now();
+        let mut i = 0;
+        while i < sleepers.len() {
+            if sleepers[i].0 <= now {
+                sleepers.swap_remove(i).1.complete(());
+            } else {
+                i += 1;
+            }
+        }
+        let mut i = 0;
+        while i < waiters.len() {
+            match poll(backend, &waiters[i], now) {
+                Some(result) => {
+                    let waiter = waiters.swap_remove(i);
+                    waiter
+                        .completer
+                        .complete((result, waiter.buffer.unwrap_or_default()));
+                }
+                None => i += 1,
+            }
+        }
+        subscribers.retain(|subscriber| subscriber.active.load(Ordering::Acquire));
+        if !subscribers.is_empty() && now >= next_srq_poll {
+            let delay = if dispatch(backend, board, &mut subscribers) {
+                SRQ_POLL_PERIOD
+            } else {
+                SRQ_RETRY_DELAY
+            };
+            next_srq_poll = now + delay;
+        }
+    }
+}
+
+/// Serial polls the subscribed devices if SRQ is asserted, and delivers their status byte.
+/// Returns false if SRQ is asserted, but none of the subscribed devices requests service.
+fn dispatch(backend: &dyn Backend, board: c_int, subscribers: &mut [Subscriber]) -> bool {
+    match backend.ibwait(board, IbStatus::default()) {
+        Ok((status, _)) if status.srqi => {}
+        Ok(_) => return true,
+        Err(e) => {
+            log::debug!("ibwait({}, 0) -> {:?}", board, e);
+            return true;
+        }
+    }
+    let mut addresses: Vec<Addr4882> = Vec::new();
+    for subscriber in subscribers.iter() {
+        if !addresses.iter().any(|a| a.addr == subscriber.address.addr) {
+            addresses.push(subscriber.address);
+        }
+    }
+    // several devices may request service at once, poll each of them once
+    let mut found = false;
+    while !addresses.is_empty() {
+        match backend.FindRQS(board, &addresses) {
+            Ok((address, status_byte)) => {
+                found = true;
+                for subscriber in subscribers
+                    .iter_mut()
+                    .filter(|subscriber| subscriber.address.addr == address.addr)
+                {
+                    (subscriber.deliver)(status_byte);
+                }
+                addresses.retain(|a| a.addr != address.addr);
+            }
+            Err(GpibError::DriverError(_, IbError::ETAB)) => break,
+            Err(e) => {
+                log::debug!("FindRQS({}) -> {:?}", board, e);
+                break;
+            }
+        }
+    }
+    found
+}
+
+/// Starts the transfer, and returns the deadline of the wait and the buffer of the transfer.
+fn start(
+    backend: &dyn Backend,
+    ud: c_int,
+    transfer: Option<Transfer>,
+    status_mask: IbStatus,
+    deadline: Option<Instant>,
+) -> Result<(Option<Instant>, Option<Vec<u8>>), GpibError> {
+    let deadline = match deadline {
+        Some(deadline) => Some(deadline),
+        None if status_mask.timo => {
+            let timeout = IbTimeout::from_timeout(backend.ibask(ud, IbOption::TMO)?)?;
+            Instant::now().checked_add(timeout.as_duration())
+        }
+        None => None,
+    };
+    let buffer = match transfer {
+        None => None,
+        Some(Transfer::Read(count)) => {
+            let mut buffer = vec![0; count];
+            // SAFETY: the buffer is kept by the waiter until the transfer is over, or stopped
+            unsafe { backend.ibrda(ud, &mut buffer) }?;
+            Some(buffer)
+        }
+        Some(Transfer::Write(data)) => {
+            // SAFETY: as above
+            unsafe { backend.ibwrta(ud, &data) }?;
+            Some(data)
+        }
+    };
+    Ok((deadline, buffer))
+}
+
+/// Returns the result of the wait if it is over.
+///
+/// The wait of a transfer is only over once the transfer is complete (`cmpl`), whatever the
+/// other events of status_mask, or once it has been stopped: the driver may use the buffer until
+/// then.
+fn poll(backend: &dyn Backend, waiter: &Waiter, now: Instant) -> Option<IoResult> {
+    let status = match backend.ibwait(waiter.ud, IbStatus::default()) {
+        Ok((status, _)) => status,
+        Err(e) => {
+            stop_transfer(backend, waiter);
+            return Some(Err(e));
+        }
+    };
+    // the timeout is handled here, with the deadline
+    let over = if waiter.buffer.is_some() {
+        status.cmpl
+    } else {
+        status.as_ibsta() & waiter.status_mask.with_timo(false).as_ibsta() != 0
+    };
+    if over {
+        // returns at once, and resynchronizes the transfer
+        Some(backend.ibwait(waiter.ud, waiter.status_mask))
+    } else if waiter.deadline.is_some_and(|deadline| now >= deadline) {
+        stop_transfer(backend, waiter);
+        Some(Ok((status.with_timo(true), 0)))
+    } else {
+        None
+    }
+}
+
+/// Stops the transfer of the waiter, if any, so that its buffer can be released.
+fn stop_transfer(backend: &dyn Backend, waiter: &Waiter) {
+    if waiter.buffer.is_some()
+        && let Err(e) = backend.ibstop(waiter.ud)
+    {
+        log::debug!("ibstop({}) -> {:?}", waiter.ud, e);
+    }
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+fn channel<T>() -> (Completer<T>, Completion<T>) {
+    let slot = Arc::new(Mutex::new(Slot {
+        value: None,
+        closed: false,
+        waker: None,
+    }));
+    (Completer { slot: slot.clone() }, Completion { slot })
+}
+
+fn lock<T>(slot: &Mutex<Slot<T>>) -> MutexGuard<'_, Slot<T>> {
+    slot.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sending half of a one-shot channel, completing a [`Completion`]
+struct Completer<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Completer<T> {
+    fn complete(self, value: T) {
+        lock(&self.slot).value = Some(value);
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut slot = lock(&self.slot);
+            slot.closed = true;
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Future of a request to the I/O thread, runtime independent
+pub(crate) struct Completion<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Future for Completion<T> {
+    type Output = Result<T, GpibError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = lock(&self.slot);
+        if let Some(value) = slot.value.take() {
+            Poll::Ready(Ok(value))
+        } else if slot.closed {
+            Poll::Ready(Err(GpibError::ValueError(
+                "The I/O thread of the board stopped before completing the request.".to_owned(),
+            )))
+        } else {
+            slot.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,620 @@
+//!
+//! VISA resource names for GPIB.
+//!
+//! Two resource classes are supported, with the syntax of the VISA specification:
+//!
+//! - `GPIB[board]::primary address[::secondary address][::INSTR]` for instruments,
+//! - `GPIB[board]::INTFC` for the interface (board) itself.
+//!
+//! Parsing is case-insensitive, and the board index defaults to 0. Secondary addresses are
+//! accepted either between 0 and 30, or between 96 and 126 (with the 0x60 offset of the NI/Linux
+//! GPIB libraries). They are displayed between 0 and 30, as other VISA libraries expect them.
+//!
+//! ```rust
+//! use linux_gpib_rs::resource::ResourceName;
+//!
+//! let resource: ResourceName = "gpib0::5::96::instr".parse().unwrap();
+//! assert_eq!(resource.to_string(), "GPIB0::5::0::INSTR");
+//! assert_eq!("GPIB0::5::0::INSTR".parse::<ResourceName>().unwrap(), resource);
+//! assert_eq!("GPIB::1".parse::<ResourceName>().unwrap().to_string(), "GPIB0::1::INSTR");
+//! assert!("GPIB0::31::INSTR".parse::<ResourceName>().is_err());
+//! ```
+//!
+//! [`ResourceManager`] discovers the resources on the boards, lists them with VISA search
+//! expressions (as `viFindRsrc`), and resolves aliases.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::resource::ResourceManager;
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let bus = SimulatedBus::new().with_device(
+//!         0,
+//!         PrimaryAddress::new(5)?,
+//!         SecondaryAddress::default(),
+//!         |_: &[u8]| Some(Reply::from("SIM,DMM,0,1.0")),
+//!     );
+//!     let mut rm = ResourceManager::with_backend(Arc::new(bus));
+//!     rm.parse_aliases("dmm1 = \"GPIB0::5::INSTR\"")?;
+//!     let resources = rm.list_resources("GPIB?*::INSTR")?;
+//!     assert_eq!(resources, vec!["GPIB0::5::INSTR".parse()?]);
+//!     assert_eq!(rm.list_resources("?*")?.len(), 2);
+//!     assert_eq!(rm.list_resources("gpib[0-3]::(5|7)::INSTR")?.len(), 1);
+//!     assert!(rm.list_resources("GPIB0::[^5]::INSTR")?.is_empty());
+//!     assert_eq!(rm.open("dmm1")?.query("*IDN?\n")?, "SIM,DMM,0,1.0\n");
+//!     Ok(())
+//! }
+//! ```
+//!
+
+use crate::backend::{Backend, default_backend};
+use crate::error::{GpibError, IbError};
+use crate::instrument::{Board, Instrument};
+use crate::lowlevel::utility::Addr4882;
+use crate::types::{IbOption, PrimaryAddress, SecondaryAddress};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Board indices probed by `ResourceManager::list_boards` (GPIB_MAX_NUM_BOARDS in Linux GPIB).
+const MAX_NUM_BOARDS: c_int = 16;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ResourceName {
+    /// Instrument (`GPIB0::5::INSTR`)
+    Instr {
+        board: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+    },
+    /// Interface (`GPIB0::INTFC`)
+    Intfc { board: c_int },
+}
+
+impl ResourceName {
+    pub fn instrument(
+        board: c_int,
+        primary_address: PrimaryAddress,
+        secondary_address: SecondaryAddress,
+    ) -> Self {
+        ResourceName::Instr {
+            board,
+            primary_address,
+            secondary_address,
+        }
+    }
+
+    pub fn interface(board: c_int) -> Self {
+        ResourceName::Intfc { board }
+    }
+
+    pub fn board(&self) -> c_int {
+        match self {
+            ResourceName::Instr { board, .. } | ResourceName::Intfc { board } => *board,
+        }
+    }
+
+    pub fn primary_address(&self) -> Option<PrimaryAddress> {
+        match self {
+            ResourceName::Instr {
+                primary_address, ..
+            } => Some(*primary_address),
+            ResourceName::Intfc { .. } => None,
+        }
+    }
+
+    pub fn secondary_address(&self) -> Option<SecondaryAddress> {
+        match self {
+            ResourceName::Instr {
+                secondary_address, ..
+            } => Some(*secondary_address),
+            ResourceName::Intfc { .. } => None,
+        }
+    }
+
+    /// Address for the multidevice API (instruments only)
+    pub fn address(&self) -> Result<Addr4882, GpibError> {
+        match self {
+            ResourceName::Instr {
+                primary_address,
+                secondary_address,
+                ..
+            } => Addr4882::new(*primary_address, *secondary_address),
+            ResourceName::Intfc { .. } => Err(GpibError::ValueError(format!(
+                "{} is an interface, not an instrument.",
+                self
+            ))),
+        }
+    }
+}
+
+fn parse_number(resource: &str, what: &str, value: &str) -> Result<c_int, GpibError> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(GpibError::ValueError(format!(
+            "Invalid {} '{}' in resource name '{}'.",
+            what, value, resource
+        )));
+    }
+    value.parse::<c_int>().map_err(|e| {
+        GpibError::ValueError(format!(
+            "Invalid {} '{}' in resource name '{}' ({}).",
+            what, value, resource, e
+        ))
+    })
+}
+
+fn with_context(resource: &str, error: GpibError) -> GpibError {
+    match error {
+        GpibError::ValueError(desc) => {
+            GpibError::ValueError(format!("Invalid resource name '{}': {}", resource, desc))
+        }
+        other => other,
+    }
+}
+
+impl FromStr for ResourceName {
+    type Err = GpibError;
+
+    fn from_str(resource: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = resource.trim().split("::").collect();
+        let Some(index) = fields[0]
+            .get(..4)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("GPIB"))
+            .map(|_| &fields[0][4..])
+        else {
+            return Err(GpibError::ValueError(format!(
+                "Resource name '{}' does not start with GPIB.",
+                resource
+            )));
+        };
+        let board = match index {
+            "" => 0,
+            index => parse_number(resource, "board index", index)?,
+        };
+        let mut fields = &fields[1..];
+        match fields.last() {
+            Some(last) if last.eq_ignore_ascii_case("INTFC") => {
+                if fields.len() > 1 {
+                    return Err(GpibError::ValueError(format!(
+                        "Unexpected address in interface resource name '{}' (expected GPIB[board]::INTFC).",
+                        resource
+                    )));
+                }
+                return Ok(ResourceName::interface(board));
+            }
+            Some(last) if last.eq_ignore_ascii_case("INSTR") => {
+                fields = &fields[..fields.len() - 1];
+            }
+            Some(last) if last.chars().any(|c| c.is_ascii_alphabetic()) => {
+                return Err(GpibError::ValueError(format!(
+                    "Unsupported resource class '{}' in resource name '{}' (expected INSTR or INTFC).",
+                    last, resource
+                )));
+            }
+            _ => {}
+        }
+        let (pad, sad) = match fields {
+            [pad] => (*pad, None),
+            [pad, sad] => (*pad, Some(*sad)),
+            [] => {
+                return Err(GpibError::ValueError(format!(
+                    "Missing primary address in resource name '{}'.",
+                    resource
+                )));
+            }
+            _ => {
+                return Err(GpibError::ValueError(format!(
+                    "Too many fields in resource name '{}' (expected GPIB[board]::primary address[::secondary address][::INSTR]).",
+                    resource
+                )));
+            }
+        };
+        let primary_address = PrimaryAddress::new(parse_number(resource, "primary address", pad)?)
+            .map_err(|e| with_context(resource, e))?;
+        let secondary_address = match sad {
+            None => SecondaryAddress::default(),
+            Some(sad) => {
+                let sad = parse_number(resource, "secondary address", sad)?;
+                // VISA secondary addresses are 0 to 30, SecondaryAddress::new(0) would mean none
+                let sad = if (0..=30).contains(&sad) {
+                    sad + 0x60
+                } else {
+                    sad
+                };
+                SecondaryAddress::new(sad).map_err(|e| with_context(resource, e))?
+            }
+        };
+        Ok(ResourceName::instrument(
+            board,
+            primary_address,
+            secondary_address,
+        ))
+    }
+}
+
+impl fmt::Display for ResourceName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResourceName::Instr {
+                board,
+                primary_address,
+                secondary_address,
+            } => {
+                if secondary_address.as_sad() == 0 {
+                    write!(f, "GPIB{}::{}::INSTR", board, primary_address)
+                } else {
+                    // without the 0x60 offset
+                    write!(
+                        f,
+                        "GPIB{}::{}::{}::INSTR",
+                        board,
+                        primary_address,
+                        secondary_address.as_sad() - 0x60
+                    )
+                }
+            }
+            ResourceName::Intfc { board } => write!(f, "GPIB{}::INTFC", board),
+        }
+    }
+}
+
+impl fmt::Debug for ResourceName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResourceName({})", self)
+    }
+}
+
+/// Discovery of the resources, and resolution of aliases
+pub struct ResourceManager {
+    backend: Arc<dyn Backend>,
+    aliases: BTreeMap<String, ResourceName>,
+}
+
+impl Default for ResourceManager {
+    fn default() -> Self {
+        ResourceManager::new()
+    }
+}
+
+impl ResourceManager {
+    /// Resource manager with the default backend (see `backend::default_backend`)
+    pub fn new() -> Self {
+        ResourceManager::with_backend(default_backend())
+    }
+
+    /// Resource manager using the provided backend
+    pub fn with_backend(backend: Arc<dyn Backend>) -> Self {
+        ResourceManager {
+            backend,
+            aliases: BTreeMap::new(),
+        }
+    }
+
+    /// Defines an alias for a resource
+    pub fn with_alias(mut self, alias: &str, resource: ResourceName) -> Self {
+        self.aliases.insert(alias.to_owned(), resource);
+        self
+    }
+
+    /// Aliases defined so far
+    pub fn aliases(&self) -> &BTreeMap<String, ResourceName> {
+        &self.aliases
+    }
+
+    /// Loads aliases from a file, with one `alias = resource` definition per line.
+    /// Blank lines, comments starting with `#` and `[section]` headers are ignored, and the
+    /// resource may be quoted, so that a flat TOML table can be used as alias file.
+    pub fn load_aliases(&mut self, path: impl AsRef<Path>) -> Result<(), GpibError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            GpibError::ValueError(format!("Unable to read {} ({})", path.display(), e))
+        })?;
+        self.parse_aliases(&content)
+    }
+
+    /// Loads aliases from a string, see [`ResourceManager::load_aliases`].
+    pub fn parse_aliases(&mut self, content: &str) -> Result<(), GpibError> {
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let Some((alias, resource)) = line.split_once('=') else {
+                return Err(GpibError::ValueError(format!(
+                    "Invalid alias definition on line {} ('{}'), expected alias = resource.",
+                    number + 1,
+                    line
+                )));
+            };
+            let unquote = |s: &str| s.trim().trim_matches('"').to_owned();
+            let resource = unquote(resource).parse()?;
+            self.aliases.insert(unquote(alias), resource);
+        }
+        Ok(())
+    }
+
+    /// Resource of an alias, or parsed resource name
+    pub fn resolve(&self, name: &str) -> Result<ResourceName, GpibError> {
+        match self.aliases.get(name) {
+            Some(resource) => Ok(*resource),
+            None => name.parse(),
+        }
+    }
+
+    /// Instrument from an alias or a resource name
+    pub fn open(&self, name: &str) -> Result<Instrument, GpibError> {
+        let resource = self.resolve(name)?;
+        Ok(Instrument::with_board(
+            self.board(resource.board()),
+            resource.address()?,
+        ))
+    }
+
+    /// Board with the backend of the resource manager
+    pub fn board(&self, board_number: c_int) -> Board {
+        Board::with_backend(board_number, self.backend.clone())
+    }
+
+    /// Boards which are configured
+    pub fn list_boards(&self) -> Vec<c_int> {
+        (0..MAX_NUM_BOARDS)
+            .filter(|board| self.backend.ibask(*board, IbOption::PAD).is_ok())
+            .collect()
+    }
+
+    /// Interfaces of the configured boards, and instruments listening on them
+    pub fn find_resources(&self) -> Result<Vec<ResourceName>, GpibError> {
+        let mut resources = Vec::new();
+        for board in self.list_boards() {
+            resources.push(ResourceName::interface(board));
+            let listeners = match self.backend.FindAllLstn(board) {
+                Ok(listeners) => listeners,
+                Err(GpibError::DriverError(_, IbError::ENOL | IbError::EBUS | IbError::ETAB)) => {
+                    Vec::new()
+                }
+                Err(e) => return Err(e),
+            };
+            for address in listeners {
+                resources.push(ResourceName::instrument(
+                    board,
+                    address.primary_address()?,
+                    address.secondary_address()?,
+                ));
+            }
+        }
+        log::debug!("find_resources() -> {:?}", resources);
+        Ok(resources)
+    }
+
+    /// Resources matching a VISA search expression, e.g. `GPIB?*::INSTR` or `?*`.
+    ///
+    /// As with `viFindRsrc`, `?` matches any character, `*` and `+` repeat the preceding
+    /// character zero or more/one or more times, `[list]` and `[^list]` match characters in (or not
+    /// in) the list, which may include ranges (`[0-9]`), `exp|exp` matches either expression and
+    /// parentheses group expressions. The match is case-insensitive.
+    pub fn list_resources(&self, expression: &str) -> Result<Vec<ResourceName>, GpibError> {
+        let pattern = SearchExpression::new(expression)?;
+        Ok(self
+            .find_resources()?
+            .into_iter()
+            .filter(|resource| pattern.matches(&resource.to_string()))
+            .collect())
+    }
+}
+
+impl fmt::Debug for ResourceManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResourceManager({:?})", self.aliases)
+    }
+}
+
+enum Node {
+    Char(char),
+    Any,
+    Set {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        at_least_once: bool,
+    },
+}
+
+/// VISA regular expression for resource names
+struct SearchExpression {
+    alternatives: Vec<Vec<Node>>,
+}
+
+impl SearchExpression {
+    fn new(expression: &str) -> Result<Self, GpibError> {
+        let chars: Vec<char> = expression.chars().collect();
+        let mut position = 0;
+        let alternatives = Self::parse_alternatives(&chars, &mut position, expression)?;
+        if position < chars.len() {
+            return Err(Self::error(expression, "unbalanced parenthesis"));
+        }
+        Ok(SearchExpression { alternatives })
+    }
+
+    fn error(expression: &str, desc: &str) -> GpibError {
+        GpibError::ValueError(format!(
+            "Invalid search expression '{}' ({}).",
+            expression, desc
+        ))
+    }
+
+    fn parse_alternatives(
+        chars: &[char],
+        position: &mut usize,
+        expression: &str,
+    ) -> Result<Vec<Vec<Node>>, GpibError> {
+        let mut alternatives = vec![Vec::new()];
+        while let Some(&c) = chars.get(*position) {
+            *position += 1;
+            let node = match c {
+                ')' => {
+                    *position -= 1;
+                    break;
+                }
+                '|' => {
+                    alternatives.push(Vec::new());
+                    continue;
+                }
+                '?' => Node::Any,
+                '(' => {
+                    let group = Self::parse_alternatives(chars, position, expression)?;
+                    if chars.get(*position) != Some(&')') {
+                        return Err(Self::error(expression, "unbalanced parenthesis"));
+                    }
+                    *position += 1;
+                    Node::Group(group)
+                }
+                '[' => Self::parse_set(chars, position, expression)?,
+                '*' | '+' => {
+                    let Some(node) = alternatives.last_mut().and_then(|nodes| nodes.pop()) else {
+                        return Err(Self::error(expression, "nothing to repeat"));
+                    };
+                    Node::Repeat {
+                        node: Box::new(node),
+                        at_least_once: c == '+',
+                    }
+                }
+                '\\' => match chars.get(*position) {
+                    Some(&escaped) => {
+                        *position += 1;
+                        Node::Char(escaped)
+                    }
+                    None => return Err(Self::error(expression, "trailing backslash")),
+                },
+                c => Node::Char(c),
+            };
+            if let Some(nodes) = alternatives.last_mut() {
+                nodes.push(node);
+            }
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_set(
+        chars: &[char],
+        position: &mut usize,
+        expression: &str,
+    ) -> Result<Node, GpibError> {
+        let negated = chars.get(*position) == Some(&'^');
+        if negated {
+            *position += 1;
+        }
+        let mut ranges = Vec::new();
+        loop {
+            let Some(&first) = chars.get(*position) else {
+                return Err(Self::error(expression, "unterminated character list"));
+            };
+            *position += 1;
+            if first == ']' && !ranges.is_empty() {
+                return Ok(Node::Set { negated, ranges });
+            }
+            if chars.get(*position) == Some(&'-')
+                && let Some(&last) = chars.get(*position + 1)
+                && last != ']'
+            {
+                *position += 2;
+                ranges.push((first, last));
+            } else {
+                ranges.push((first, first));
+            }
+        }
+    }
+
+    fn matches(&self, resource: &str) -> bool {
+        let input: Vec<char> = resource.chars().collect();
+        Self::ends_alternatives(&self.alternatives, &input, 0).contains(&input.len())
+    }
+
+    /// Positions where the alternatives can end, starting at position
+    fn ends_alternatives(
+        alternatives: &[Vec<Node>],
+        input: &[char],
+        position: usize,
+    ) -> Vec<usize> {
+        let mut ends = Vec::new();
+        for nodes in alternatives {
+            let mut positions = vec![position];
+            for node in nodes {
+                let mut next = Vec::new();
+                for p in positions {
+                    for end in Self::ends(node, input, p) {
+                        if !next.contains(&end) {
+                            next.push(end);
+                        }
+                    }
+                }
+                positions = next;
+            }
+            for end in positions {
+                if !ends.contains(&end) {
+                    ends.push(end);
+                }
+            }
+        }
+        ends
+    }
+
+    /// Positions where the node can end, starting at position
+    fn ends(node: &Node, input: &[char], position: usize) -> Vec<usize> {
+        match node {
+            Node::Char(c) => match input.get(position) {
+                Some(i) if i.eq_ignore_ascii_case(c) => vec![position + 1],
+                _ => vec![],
+            },
+            Node::Any => {
+                if position < input.len() {
+                    vec![position + 1]
+                } else {
+                    vec![]
+                }
+            }
+            Node::Set { negated, ranges } => match input.get(position) {
+                Some(i) => {
+                    let i = i.to_ascii_uppercase();
+                    let in_list = ranges.iter().any(|(first, last)| {
+                        (first.to_ascii_uppercase()..=last.to_ascii_uppercase()).contains(&i)
+                    });
+                    if in_list != *negated {
+                        vec![position + 1]
+                    } else {
+                        vec![]
+                    }
+                }
+                None => vec![],
+            },
+            Node::Group(alternatives) => Self::ends_alternatives(alternatives, input, position),
+            Node::Repeat {
+                node,
+                at_least_once,
+            } => {
+                let mut ends = if *at_least_once {
+                    vec![]
+                } else {
+                    vec![position]
+                };
+                let mut frontier = vec![position];
+                while let Some(p) = frontier.pop() {
+                    for end in Self::ends(node, input, p) {
+                        if end != p && !ends.contains(&end) {
+                            ends.push(end);
+                            frontier.push(end);
+                        }
+                    }
+                }
+                ends
+            }
+        }
+    }
+}
@@ -0,0 +1,439 @@
+//!
+//! SCPI conventions: error queue entries, automatic error checking, and parsing of responses.
+//!
+//! SCPI instruments queue their errors, which are read one at a time with `SYST:ERR?` until
+//! `0,"No error"` is returned. With an [`ErrorCheck`] mode other than `Disabled` in the
+//! [`Parameters`](crate::instrument::Parameters) of an `InstrumentHandle`, the error queue or the
+//! standard event status register is checked after each write and query, and instrument errors
+//! are returned as `GpibError::InstrumentError`.
+//!
+//! ```rust
+//! use linux_gpib_rs::scpi::ScpiError;
+//!
+//! let error: ScpiError = "-113,\"Undefined header;FOO:BAR\"".parse().unwrap();
+//! assert_eq!(error.code, -113);
+//! assert_eq!(error.message, "Undefined header;FOO:BAR");
+//! assert!(!"+0,\"No error\"".parse::<ScpiError>().unwrap().is_error());
+//! ```
+//!
+//! Responses are parsed with [`FromScpi`], and the [`ScpiQuery`] trait provides typed queries
+//! for the types implementing [`Ieee4882`]. Numbers are accepted in the NR1, NR2 and NR3 formats,
+//! with an optional SI or SCPI multiplier and unit (`1.5 mV`, `10kHz`, `2.2 MOHM`), which are
+//! converted to the base unit. The SCPI values `9.9E37` (overflow) and `9.91E37` (not a number)
+//! are converted to infinity and NaN.
+//!
+//! ```rust
+//! use linux_gpib_rs::scpi::FromScpi;
+//!
+//! assert_eq!(f64::from_scpi("+1.50000E-03").unwrap(), 1.5e-3);
+//! assert_eq!(f64::from_scpi("1.5 mV").unwrap(), 1.5e-3);
+//! assert_eq!(f64::from_scpi("+9.9E37").unwrap(), f64::INFINITY);
+//! assert!(f64::from_scpi("NAN").unwrap().is_nan());
+//! assert_eq!(i64::from_scpi("+1.00E+01").unwrap(), 10);
+//! assert_eq!(bool::from_scpi("OFF").unwrap(), false);
+//! assert_eq!(String::from_scpi("\"say \"\"hi\"\"\"").unwrap(), "say \"hi\"");
+//! assert_eq!(Vec::<f64>::from_scpi("1,2.5,-3E-1").unwrap(), vec![1.0, 2.5, -0.3]);
+//! assert!(f64::from_scpi("1.0 furlong").is_err());
+//! ```
+//!
+//! The commands of an instrument can be declared with typed arguments and responses, see
+//! [`command`].
+//!
+
+pub mod command;
+
+use crate::error::GpibError;
+use crate::ieee4882::Ieee4882;
+use crate::status::StandardEventStatus;
+use std::fmt;
+use std::str::FromStr;
+
+/// Entry of the SCPI error queue, as returned by `SYST:ERR?`
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ScpiError {
+    /// Error code: 0 for no error, negative for the errors defined by SCPI, positive for
+    /// device-specific errors
+    pub code: i32,
+    pub message: String,
+}
+
+impl ScpiError {
+    pub fn new(code: i32, message: &str) -> Self {
+        ScpiError {
+            code,
+            message: message.to_owned(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.code != 0
+    }
+
+    /// Generic SCPI errors corresponding to the error bits of the standard event status register
+    pub fn from_event_status(event_status: StandardEventStatus) -> Vec<ScpiError> {
+        let mut errors = Vec::new();
+        if event_status.cme() {
+            errors.push(ScpiError::new(-100, "Command error"));
+        }
+        if event_status.exe() {
+            errors.push(ScpiError::new(-200, "Execution error"));
+        }
+        if event_status.dde() {
+            errors.push(ScpiError::new(-300, "Device-specific error"));
+        }
+        if event_status.qye() {
+            errors.push(ScpiError::new(-400, "Query error"));
+        }
+        errors
+    }
+}
+
+impl FromStr for ScpiError {
+    type Err = GpibError;
+
+    fn from_str(response: &str) -> Result<Self, Self::Err> {
+        let response = response.trim();
+        let (code, message) = response.split_once(',').unwrap_or((response, ""));
+        let code = code.trim().parse().map_err(|e| {
+            GpibError::ValueError(format!("Invalid SCPI error {:?} ({:?})", response, e))
+        })?;
+        let message = message.trim();
+        let message = message
+            .strip_prefix('"')
+            .and_then(|m| m.strip_suffix('"'))
+            .unwrap_or(message);
+        Ok(ScpiError::new(code, message))
+    }
+}
+
+impl fmt::Display for ScpiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},\"{}\"", self.code, self.message)
+    }
+}
+
+impl fmt::Debug for ScpiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ScpiError({})", self)
+    }
+}
+
+/// Automatic error checking after writes and queries
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorCheck {
+    /// No checking
+    #[default]
+    Disabled,
+    /// Drains the error queue with `SYST:ERR?`
+    ErrorQueue,
+    /// Reads the standard event status register with `*ESR?`, for instruments without an error
+    /// queue. This clears the register, including the OPC bit.
+    EventStatus,
+}
+
+impl fmt::Display for ErrorCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCheck::Disabled => write!(f, "Disabled"),
+            ErrorCheck::ErrorQueue => write!(f, "ErrorQueue"),
+            ErrorCheck::EventStatus => write!(f, "EventStatus"),
+        }
+    }
+}
+
+impl fmt::Debug for ErrorCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ErrorCheck({})", self)
+    }
+}
+
+/// SCPI value returned for an overflow, or an infinite value
+pub const OVERFLOW: f64 = 9.9e37;
+
+/// SCPI value returned for a measurement which is not a number
+pub const NOT_A_NUMBER: f64 = 9.91e37;
+
+/// Value which can be parsed from a SCPI response
+pub trait FromScpi: Sized {
+    /// Parses a response, without its terminator. The error holds the raw response.
+    fn from_scpi(response: &str) -> Result<Self, GpibError>;
+}
+
+impl FromScpi for f64 {
+    fn from_scpi(response: &str) -> Result<Self, GpibError> {
+        parse_number(response).ok_or_else(|| invalid(response, "number"))
+    }
+}
+
+impl FromScpi for f32 {
+    fn from_scpi(response: &str) -> Result<Self, GpibError> {
+        Ok(f64::from_scpi(response)? as f32)
+    }
+}
+
+macro_rules! impl_from_scpi_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromScpi for $t {
+                fn from_scpi(response: &str) -> Result<Self, GpibError> {
+                    let text = response.trim();
+                    if let Ok(value) = text.parse() {
+                        return Ok(value);
+                    }
+                    // NR2 or NR3 format with an integral value
+                    match parse_number(text) {
+                        Some(value)
+                            if value.fract() == 0.0
+                                && value >= <$t>::MIN as f64
+                                && value <= <$t>::MAX as f64 =>
+                        {
+                            Ok(value as $t)
+                        }
+                        _ => Err(invalid(response, stringify!($t))),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_scpi_integer!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+
+impl FromScpi for bool {
+    fn from_scpi(response: &str) -> Result<Self, GpibError> {
+        let text = response.trim();
+        if text.eq_ignore_ascii_case("ON") {
+            return Ok(true);
+        }
+        if text.eq_ignore_ascii_case("OFF") {
+            return Ok(false);
+        }
+        match text.parse::<f64>() {
+            Ok(1.0) => Ok(true),
+            Ok(0.0) => Ok(false),
+            _ => Err(invalid(response, "boolean")),
+        }
+    }
+}
+
+/// String response. The quotes of a SCPI string (`"..."` or `'...'`) are removed, and doubled
+/// quotes inside are unescaped. Other responses are returned as is, without surrounding spaces.
+impl FromScpi for String {
+    fn from_scpi(response: &str) -> Result<Self, GpibError> {
+        let text = response.trim();
+        let Some(quote) = text.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            return Ok(text.to_owned());
+        };
+        if text.len() < 2 || !text.ends_with(quote) {
+            return Err(invalid(response, "string"));
+        }
+        let inner = &text[1..text.len() - 1];
+        let doubled: String = [quote, quote].iter().collect();
+        if inner.replace(&doubled, "").contains(quote) {
+            return Err(invalid(response, "string"));
+        }
+        Ok(inner.replace(&doubled, &quote.to_string()))
+    }
+}
+
+/// Comma-separated list. Commas inside quoted strings do not separate the elements, and an empty
+/// response is an empty list.
+impl<T: FromScpi> FromScpi for Vec<T> {
+    fn from_scpi(response: &str) -> Result<Self, GpibError> {
+        if response.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        split_list(response)
+            .into_iter()
+            .map(T::from_scpi)
+            .collect::<Result<Vec<T>, GpibError>>()
+            .map_err(|e| {
+                GpibError::ValueError(format!("Invalid list {:?} ({})", response.trim(), e))
+            })
+    }
+}
+
+/// Typed queries, for the types implementing `Ieee4882`
+pub trait ScpiQuery: Ieee4882 {
+    /// Sends a query, and parses the response
+    fn query_as<T: FromScpi>(&self, query: &str) -> Result<T, GpibError> {
+        T::from_scpi(&self.query_response(query)?)
+    }
+
+    fn query_f64(&self, query: &str) -> Result<f64, GpibError> {
+        self.query_as(query)
+    }
+
+    fn query_i64(&self, query: &str) -> Result<i64, GpibError> {
+        self.query_as(query)
+    }
+
+    /// Queries a boolean (`1`, `0`, `ON` or `OFF`)
+    fn query_bool(&self, query: &str) -> Result<bool, GpibError> {
+        self.query_as(query)
+    }
+
+    /// Queries a comma-separated list
+    fn query_list<T: FromScpi>(&self, query: &str) -> Result<Vec<T>, GpibError> {
+        self.query_as(query)
+    }
+
+    /// Queries a string, and removes its quotes
+    fn query_quoted_string(&self, query: &str) -> Result<String, GpibError> {
+        self.query_as(query)
+    }
+}
+
+impl<T: Ieee4882 + ?Sized> ScpiQuery for T {}
+
+fn invalid(response: &str, expected: &str) -> GpibError {
+    GpibError::ValueError(format!(
+        "Invalid SCPI response {:?}: expected {}.",
+        response.trim(),
+        expected
+    ))
+}
+
+/// Number with an optional multiplier and unit, None if invalid
+fn parse_number(response: &str) -> Option<f64> {
+    let text = response.trim();
+    if text.eq_ignore_ascii_case("NAN") {
+        return Some(f64::NAN);
+    }
+    if let Some(sign) = ["INF", "+INF", "-INF"]
+        .iter()
+        .position(|s| text.eq_ignore_ascii_case(s))
+    {
+        return Some(if sign == 2 {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        });
+    }
+    let end = number_length(text);
+    if end == 0 {
+        return None;
+    }
+    let value: f64 = text[..end].parse().ok()?;
+    let value = if value.abs() == OVERFLOW {
+        value.signum() * f64::INFINITY
+    } else if value == NOT_A_NUMBER {
+        f64::NAN
+    } else {
+        value
+    };
+    Some(value * multiplier(text[end..].trim_start())?)
+}
+
+/// Length of the numeric part of text (sign, mantissa and exponent)
+fn number_length(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let digits = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+    let mut i = 0;
+    if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+        i += 1;
+    }
+    let start = i;
+    i = digits(i);
+    if i < bytes.len() && bytes[i] == b'.' {
+        i = digits(i + 1);
+    }
+    if i == start || (i == start + 1 && bytes[start] == b'.') {
+        return 0;
+    }
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        let mut j = i + 1;
+        if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+            j += 1;
+        }
+        let k = digits(j);
+        if k > j {
+            i = k;
+        }
+    }
+    i
+}
+
+/// Units accepted after a number, possibly with a multiplier
+const UNITS: [&str; 14] = [
+    "V", "A", "W", "HZ", "OHM", "S", "SEC", "F", "H", "K", "CEL", "DB", "DBM", "PCT",
+];
+
+/// Multiplier given by the suffix of a number, None if the suffix is not a known unit
+fn multiplier(suffix: &str) -> Option<f64> {
+    if suffix.is_empty() || suffix == "%" || is_unit(suffix) {
+        return Some(1.0);
+    }
+    if suffix.chars().all(|c| !c.is_ascii_lowercase()) {
+        // SCPI multipliers are case-insensitive: M is milli, MA is mega, except in MHZ and MOHM
+        let upper = suffix.to_ascii_uppercase();
+        if upper == "MHZ" || upper == "MOHM" {
+            return Some(1e6);
+        }
+        for (prefix, factor) in [
+            ("EX", 1e18),
+            ("PE", 1e15),
+            ("MA", 1e6),
+            ("T", 1e12),
+            ("G", 1e9),
+            ("K", 1e3),
+            ("M", 1e-3),
+            ("U", 1e-6),
+            ("N", 1e-9),
+            ("P", 1e-12),
+            ("F", 1e-15),
+        ] {
+            if let Some(unit) = upper.strip_prefix(prefix)
+                && (unit.is_empty() || is_unit(unit))
+            {
+                return Some(factor);
+            }
+        }
+        return None;
+    }
+    // SI prefixes are case-sensitive
+    let mut chars = suffix.chars();
+    let factor = match chars.next()? {
+        'T' => 1e12,
+        'G' => 1e9,
+        'M' => 1e6,
+        'k' => 1e3,
+        'm' => 1e-3,
+        'u' | 'µ' => 1e-6,
+        'n' => 1e-9,
+        'p' => 1e-12,
+        'f' => 1e-15,
+        _ => return None,
+    };
+    let unit = chars.as_str();
+    (unit.is_empty() || is_unit(unit)).then_some(factor)
+}
+
+fn is_unit(unit: &str) -> bool {
+    UNITS.iter().any(|u| unit.eq_ignore_ascii_case(u))
+}
+
+/// Splits a list response at the commas which are not inside a quoted string
+fn split_list(response: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in response.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ',') => {
+                elements.push(&response[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(&response[start..]);
+    elements
+}
@@ -0,0 +1,528 @@
+//!
+//! Declaration of SCPI command trees.
+//!
+//! [`scpi_commands!`](crate::scpi_commands) declares the commands of an instrument once, as
+//! methods of a wrapper around any type implementing `Ieee4882` (`InstrumentHandle` or
+//! `Instrument`). The headers are checked when compiling, the arguments are formatted with
+//! [`ToScpi`] (with an optional unit, and `MIN`, `MAX` or `DEF` with [`Limit`]), and the
+//! responses are parsed with `FromScpi`. [`scpi_enum!`](crate::scpi_enum) declares the
+//! enumerated parameters, with their long and short forms.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::instrument::Board;
+//! use linux_gpib_rs::scpi::command::Limit;
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use linux_gpib_rs::{scpi_commands, scpi_enum};
+//! use std::sync::{Arc, Mutex};
+//!
+//! scpi_enum! {
+//!     pub enum TriggerSource {
+//!         Immediate = "IMMediate",
+//!         Bus = "BUS",
+//!         External = "EXTernal",
+//!     }
+//! }
+//!
+//! scpi_commands! {
+//!     /// Digital multimeter
+//!     pub struct Dmm;
+//!     /// DC voltage range
+//!     setting range, set_range = "VOLT:DC:RANG" in "V" => limited f64;
+//!     setting trigger_source, set_trigger_source = "TRIG:SOUR" => TriggerSource;
+//!     setting display, set_display = "DISP" => bool;
+//!     command configure(range: Limit<f64>, resolution: Limit<f64>) = "CONF:VOLT:DC";
+//!     command initiate() = "INIT";
+//!     query read() -> f64 = "READ?";
+//! }
+//!
+//! let sent = Arc::new(Mutex::new(Vec::new()));
+//! let log = sent.clone();
+//! let bus = SimulatedBus::new().with_device(
+//!     0,
+//!     PrimaryAddress::new(22).unwrap(),
+//!     SecondaryAddress::default(),
+//!     move |message: &[u8]| {
+//!         log.lock().unwrap().push(String::from_utf8_lossy(message).into_owned());
+//!         match message {
+//!             b"VOLT:DC:RANG?" => Some(Reply::from("+1.00000000E+01")),
+//!             b"TRIG:SOUR?" => Some(Reply::from("BUS")),
+//!             b"READ?" => Some(Reply::from("-1.23E-03")),
+//!             _ => None,
+//!         }
+//!     },
+//! );
+//! let board = Board::with_backend(0, Arc::new(bus));
+//! let dmm = Dmm::new(board.find_listeners().unwrap().remove(0));
+//! dmm.set_range(10.0).unwrap();
+//! dmm.set_range(Limit::Max).unwrap();
+//! dmm.set_trigger_source(TriggerSource::External).unwrap();
+//! dmm.set_display(false).unwrap();
+//! dmm.configure(Limit::Default, 1e-5).unwrap();
+//! dmm.initiate().unwrap();
+//! assert_eq!(dmm.range().unwrap(), 10.0);
+//! assert_eq!(dmm.trigger_source().unwrap(), TriggerSource::Bus);
+//! assert_eq!(dmm.read().unwrap(), -1.23e-3);
+//! assert_eq!(
+//!     sent.lock().unwrap()[..6],
+//!     [
+//!         "VOLT:DC:RANG 10 V",
+//!         "VOLT:DC:RANG MAX",
+//!         "TRIG:SOUR EXT",
+//!         "DISP 0",
+//!         "CONF:VOLT:DC DEF,0.00001",
+//!         "INIT",
+//!     ]
+//! );
+//! ```
+//!
+
+use std::fmt;
+
+/// Value which can be sent as a SCPI argument
+pub trait ToScpi {
+    fn to_scpi(&self) -> String;
+
+    /// Argument followed by a unit, if not empty
+    fn to_scpi_with_unit(&self, unit: &str) -> String {
+        if unit.is_empty() {
+            self.to_scpi()
+        } else {
+            format!("{} {}", self.to_scpi(), unit)
+        }
+    }
+}
+
+impl ToScpi for f64 {
+    fn to_scpi(&self) -> String {
+        if self.is_nan() {
+            "NAN".to_owned()
+        } else if self.is_infinite() {
+            format!("{:E}", self.signum() * super::OVERFLOW)
+        } else {
+            self.to_string()
+        }
+    }
+}
+
+impl ToScpi for f32 {
+    fn to_scpi(&self) -> String {
+        f64::from(*self).to_scpi()
+    }
+}
+
+macro_rules! impl_to_scpi_integer {
+    ($($t:ty),*) => {
+        $(
+            impl ToScpi for $t {
+                fn to_scpi(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_to_scpi_integer!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+
+impl ToScpi for bool {
+    fn to_scpi(&self) -> String {
+        if *self { "1" } else { "0" }.to_owned()
+    }
+}
+
+/// String argument, sent quoted
+impl ToScpi for str {
+    fn to_scpi(&self) -> String {
+        format!("\"{}\"", self.replace('"', "\"\""))
+    }
+}
+
+impl ToScpi for String {
+    fn to_scpi(&self) -> String {
+        self.as_str().to_scpi()
+    }
+}
+
+impl<T: ToScpi + ?Sized> ToScpi for &T {
+    fn to_scpi(&self) -> String {
+        (**self).to_scpi()
+    }
+}
+
+/// Numeric argument, or one of the limits accepted by SCPI instruments
+#[derive(Clone, Copy, PartialEq)]
+pub enum Limit<T> {
+    Value(T),
+    /// `MIN`
+    Min,
+    /// `MAX`
+    Max,
+    /// `DEF`, the value after `*RST`
+    Default,
+}
+
+impl<T> From<T> for Limit<T> {
+    fn from(value: T) -> Self {
+        Limit::Value(value)
+    }
+}
+
+impl<T: ToScpi> ToScpi for Limit<T> {
+    fn to_scpi(&self) -> String {
+        match self {
+            Limit::Value(value) => value.to_scpi(),
+            Limit::Min => "MIN".to_owned(),
+            Limit::Max => "MAX".to_owned(),
+            Limit::Default => "DEF".to_owned(),
+        }
+    }
+
+    /// The unit only follows a value
+    fn to_scpi_with_unit(&self, unit: &str) -> String {
+        match self {
+            Limit::Value(value) => value.to_scpi_with_unit(unit),
+            _ => self.to_scpi(),
+        }
+    }
+}
+
+impl<T: ToScpi> fmt::Debug for Limit<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Limit({})", self.to_scpi())
+    }
+}
+
+/// Checks the syntax of a command header: a common command (`*RST`), or nodes separated by
+/// colons, each starting with a letter and made of letters and digits. Query headers end with
+/// `?`. This is a `const fn`, so that `scpi_commands!` checks the headers when compiling.
+pub const fn is_valid_header(header: &str, query: bool) -> bool {
+    let bytes = header.as_bytes();
+    let mut end = bytes.len();
+    if query {
+        if end == 0 || bytes[end - 1] != b'?' {
+            return false;
+        }
+        end -= 1;
+    }
+    if end == 0 {
+        return false;
+    }
+    let mut i = 0;
+    if bytes[0] == b'*' {
+        i = 1;
+        while i < end {
+            if !bytes[i].is_ascii_alphabetic() {
+                return false;
+            }
+            i += 1;
+        }
+        return end > 1;
+    }
+    if bytes[0] == b':' {
+        i = 1;
+    }
+    let mut node_start = i;
+    while i < end {
+        let byte = bytes[i];
+        if byte == b':' {
+            if i == node_start {
+                return false;
+            }
+            node_start = i + 1;
+        } else if i == node_start {
+            if !byte.is_ascii_alphabetic() {
+                return false;
+            }
+        } else if !byte.is_ascii_alphanumeric() {
+            return false;
+        }
+        i += 1;
+    }
+    end > node_start
+}
+
+/// Message made of a header and its comma-separated arguments
+pub fn message(header: &str, arguments: &[String]) -> String {
+    if arguments.is_empty() {
+        header.to_owned()
+    } else {
+        format!("{} {}", header, arguments.join(","))
+    }
+}
+
+/// Short form of a mnemonic, made of its upper case letters and digits (`IMM` for `IMMediate`)
+pub fn short_form(mnemonic: &str) -> String {
+    mnemonic
+        .chars()
+        .filter(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        .collect()
+}
+
+/// True if the response is the short or the long form of the mnemonic, in any case
+pub fn matches_mnemonic(response: &str, mnemonic: &str) -> bool {
+    let response = response.trim();
+    response.eq_ignore_ascii_case(&short_form(mnemonic)) || response.eq_ignore_ascii_case(mnemonic)
+}
+
+/// Declares the commands of an instrument, as methods of a wrapper around a type implementing
+/// `Ieee4882`.
+///
+/// ```text
+/// scpi_commands! {
+///     pub struct Name;
+///     setting getter, setter = "HEADER" [in "UNIT"] => Type;
+///     setting getter, setter = "HEADER" [in "UNIT"] => limited Type;
+///     command name(argument: Type, ...) = "HEADER";
+///     query name(argument: Type, ...) -> Type = "HEADER?";
+/// }
+/// ```
+///
+/// A setting gives a getter sending `HEADER?`, and a setter sending `HEADER value`. The setter
+/// of a `limited` setting also accepts `Limit::Min`, `Limit::Max` and `Limit::Default`. Commands
+/// and queries send their arguments separated with commas. The arguments implement `ToScpi`
+/// (any value converting into the declared type is accepted), and the returned values
+/// implement `FromScpi`. Documentation comments are kept on the generated methods.
+///
+/// An invalid header is a compilation error:
+///
+/// ```compile_fail
+/// linux_gpib_rs::scpi_commands! {
+///     struct Source;
+///     command output_on() = "OUTP::STAT ON";
+/// }
+/// ```
+#[macro_export]
+macro_rules! scpi_commands {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident;
+        $($items:tt)*
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<H> {
+            handle: H,
+        }
+
+        impl<H: $crate::ieee4882::Ieee4882> $name<H> {
+            $vis fn new(handle: H) -> Self {
+                $name { handle }
+            }
+
+            /// Underlying instrument
+            $vis fn handle(&self) -> &H {
+                &self.handle
+            }
+
+            $vis fn into_inner(self) -> H {
+                self.handle
+            }
+
+            $crate::scpi_commands!(@items [$vis] $($items)*);
+        }
+    };
+
+    (@items [$vis:vis]) => {};
+
+    (@items [$vis:vis]
+        $(#[$meta:meta])*
+        setting $getter:ident, $setter:ident = $header:literal $(in $unit:literal)?
+            => limited $type:ty;
+        $($rest:tt)*
+    ) => {
+        $crate::scpi_commands!(@getter [$vis] $(#[$meta])* $getter, $header, $type);
+
+        $(#[$meta])*
+        $vis fn $setter(
+            &self,
+            value: impl Into<$crate::scpi::command::Limit<$type>>,
+        ) -> Result<(), $crate::error::GpibError> {
+            const _: () = assert!(
+                    $crate::scpi::command::is_valid_header($header, false),
+                    concat!("Invalid SCPI header: ", $header)
+            );
+            let argument = $crate::scpi::command::ToScpi::to_scpi_with_unit(
+                &value.into(),
+                $crate::scpi_commands!(@unit $($unit)?),
+            );
+            $crate::ieee4882::Ieee4882::send_command(
+                &self.handle,
+                &$crate::scpi::command::message($header, &[argument]),
+            )
+        }
+
+        $crate::scpi_commands!(@items [$vis] $($rest)*);
+    };
+
+    (@items [$vis:vis]
+        $(#[$meta:meta])*
+        setting $getter:ident, $setter:ident = $header:literal $(in $unit:literal)? => $type:ty;
+        $($rest:tt)*
+    ) => {
+        $crate::scpi_commands!(@getter [$vis] $(#[$meta])* $getter, $header, $type);
+
+        $(#[$meta])*
+        $vis fn $setter(&self, value: impl Into<$type>) -> Result<(), $crate::error::GpibError> {
+            const _: () = assert!(
+                    $crate::scpi::command::is_valid_header($header, false),
+                    concat!("Invalid SCPI header: ", $header)
+            );
+            let argument = $crate::scpi::command::ToScpi::to_scpi_with_unit(
+                &value.into(),
+                $crate::scpi_commands!(@unit $($unit)?),
+            );
+            $crate::ieee4882::Ieee4882::send_command(
+                &self.handle,
+                &$crate::scpi::command::message($header, &[argument]),
+            )
+        }
+
+        $crate::scpi_commands!(@items [$vis] $($rest)*);
+    };
+
+    (@items [$vis:vis]
+        $(#[$meta:meta])*
+        command $name:ident($($argument:ident: $type:ty),* $(,)?) = $header:literal;
+        $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        $vis fn $name(&self $(, $argument: impl Into<$type>)*) -> Result<(), $crate::error::GpibError> {
+            const _: () = assert!(
+                    $crate::scpi::command::is_valid_header($header, false),
+                    concat!("Invalid SCPI header: ", $header)
+            );
+            let arguments: Vec<String> = vec![
+                $($crate::scpi::command::ToScpi::to_scpi(&Into::<$type>::into($argument))),*
+            ];
+            $crate::ieee4882::Ieee4882::send_command(
+                &self.handle,
+                &$crate::scpi::command::message($header, &arguments),
+            )
+        }
+
+        $crate::scpi_commands!(@items [$vis] $($rest)*);
+    };
+
+    (@items [$vis:vis]
+        $(#[$meta:meta])*
+        query $name:ident($($argument:ident: $type:ty),* $(,)?) -> $output:ty = $header:literal;
+        $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        $vis fn $name(&self $(, $argument: impl Into<$type>)*) -> Result<$output, $crate::error::GpibError> {
+            const _: () = assert!(
+                    $crate::scpi::command::is_valid_header($header, true),
+                    concat!("Invalid SCPI query header: ", $header)
+            );
+            let arguments: Vec<String> = vec![
+                $($crate::scpi::command::ToScpi::to_scpi(&Into::<$type>::into($argument))),*
+            ];
+            let response = $crate::ieee4882::Ieee4882::query_response(
+                &self.handle,
+                &$crate::scpi::command::message($header, &arguments),
+            )?;
+            <$output as $crate::scpi::FromScpi>::from_scpi(&response)
+        }
+
+        $crate::scpi_commands!(@items [$vis] $($rest)*);
+    };
+
+    (@getter [$vis:vis] $(#[$meta:meta])* $getter:ident, $header:literal, $type:ty) => {
+        $(#[$meta])*
+        $vis fn $getter(&self) -> Result<$type, $crate::error::GpibError> {
+            const _: () = assert!(
+                    $crate::scpi::command::is_valid_header($header, false),
+                    concat!("Invalid SCPI header: ", $header)
+            );
+            let response =
+                $crate::ieee4882::Ieee4882::query_response(&self.handle, concat!($header, "?"))?;
+            <$type as $crate::scpi::FromScpi>::from_scpi(&response)
+        }
+    };
+
+    (@unit) => {
+        ""
+    };
+
+    (@unit $unit:literal) => {
+        $unit
+    };
+}
+
+/// Declares an enumerated SCPI parameter. Each variant is given its mnemonic, with the short
+/// form in upper case (`IMMediate`). The short form is sent, and both forms are accepted in
+/// responses.
+///
+/// ```rust
+/// use linux_gpib_rs::scpi::FromScpi;
+/// use linux_gpib_rs::scpi::command::ToScpi;
+///
+/// linux_gpib_rs::scpi_enum! {
+///     pub enum Coupling {
+///         Ac = "AC",
+///         Dc = "DC",
+///         Ground = "GROund",
+///     }
+/// }
+///
+/// assert_eq!(Coupling::Ground.to_scpi(), "GRO");
+/// assert_eq!(Coupling::from_scpi("ground").unwrap(), Coupling::Ground);
+/// assert!(Coupling::from_scpi("GROUNDED").is_err());
+/// ```
+#[macro_export]
+macro_rules! scpi_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $mnemonic:literal),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant),+
+        }
+
+        impl $name {
+            /// Mnemonic, with the short form in upper case
+            $vis fn mnemonic(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $mnemonic),+
+                }
+            }
+        }
+
+        impl $crate::scpi::command::ToScpi for $name {
+            fn to_scpi(&self) -> String {
+                $crate::scpi::command::short_form(self.mnemonic())
+            }
+        }
+
+        impl $crate::scpi::FromScpi for $name {
+            fn from_scpi(response: &str) -> Result<Self, $crate::error::GpibError> {
+                $(
+                    if $crate::scpi::command::matches_mnemonic(response, $mnemonic) {
+                        return Ok($name::$variant);
+                    }
+                )+
+                Err($crate::error::GpibError::ValueError(format!(
+                    "Invalid SCPI response {:?}: expected {}.",
+                    response.trim(),
+                    stringify!($name)
+                )))
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                write!(f, "{}", self.mnemonic())
+            }
+        }
+
+        impl ::std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                write!(f, "{}({})", stringify!($name), self.mnemonic())
+            }
+        }
+    };
+}
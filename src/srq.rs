@@ -0,0 +1,222 @@
+//!
+//! Dispatch of service requests to the instruments of a board.
+//!
+//! An [`SrqService`] delivers the status byte of an instrument each time it requests service,
+//! either as a [`SrqStream`], or to a callback. The SRQ line is watched by the I/O thread of the
+//! board, which serial polls the subscribed instruments with `FindRQS` when it is asserted. Each
+//! instrument requesting service is polled once, so that several instruments asserting SRQ at
+//! the same time are all reported, and serial polling clears their request.
+//!
+//! The instruments have to be configured to request service (e.g. with `*SRE`), and the board
+//! has to be controller-in-charge. Serial polling the subscribed instruments elsewhere would
+//! race with the service.
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus, SimulatedDevice};
+//! use linux_gpib_rs::instrument::Board;
+//! use linux_gpib_rs::srq::SrqService;
+//! use linux_gpib_rs::types::{IbSendEOI, PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//!
+//! /// Requests service once a measurement is started
+//! #[derive(Default)]
+//! struct Meter {
+//!     status_byte: u8,
+//! }
+//!
+//! impl SimulatedDevice for Meter {
+//!     fn handle_message(&mut self, _message: &[u8]) -> Option<Reply> {
+//!         self.status_byte = 0x41;
+//!         None
+//!     }
+//!
+//!     fn status_byte(&self) -> u8 {
+//!         self.status_byte
+//!     }
+//!
+//!     fn serial_poll(&mut self) -> u8 {
+//!         std::mem::take(&mut self.status_byte)
+//!     }
+//! }
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     // any executor will do, the stream is woken by the I/O thread of the board
+//!     pollster::block_on(async {
+//!         let bus = SimulatedBus::new().with_device(
+//!             0,
+//!             PrimaryAddress::new(9)?,
+//!             SecondaryAddress::default(),
+//!             Meter::default(),
+//!         );
+//!         let board = Board::with_backend(0, Arc::new(bus));
+//!         let meter = board.find_listeners()?.remove(0);
+//!         let service = SrqService::new(&board)?;
+//!         let mut requests = service.subscribe(&meter)?;
+//!         meter.send(b"INIT\n", IbSendEOI::default())?;
+//!         let status_byte = requests.next().await.unwrap();
+//!         assert!(status_byte.rqs());
+//!         assert_eq!(status_byte.bits(), 0x41);
+//!         Ok(())
+//!     })
+//! }
+//! ```
+//!
+
+use crate::error::GpibError;
+use crate::instrument::{Board, Instrument};
+use crate::reactor::{Reactor, Subscriber};
+use crate::status::StatusByte;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+/// Service request dispatcher of a board
+#[derive(Clone)]
+pub struct SrqService {
+    board: Board,
+    reactor: Arc<Reactor>,
+}
+
+impl SrqService {
+    pub fn new(board: &Board) -> Result<Self, GpibError> {
+        Ok(SrqService {
+            board: board.clone(),
+            reactor: Reactor::for_board(&board.backend, board.board_number)?,
+        })
+    }
+
+    /// Stream of the status bytes of the instrument, each time it requests service.
+    /// The subscription ends when the stream is dropped.
+    pub fn subscribe(&self, instrument: &Instrument) -> Result<SrqStream, GpibError> {
+        let queue = Arc::new(Mutex::new(Queue {
+            status_bytes: VecDeque::new(),
+            waker: None,
+        }));
+        let sender = queue.clone();
+        let subscription = self.register(
+            instrument,
+            Box::new(move |status_byte| {
+                let waker = {
+                    let mut queue = sender.lock().unwrap_or_else(PoisonError::into_inner);
+                    queue.status_bytes.push_back(status_byte);
+                    queue.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }),
+        )?;
+        Ok(SrqStream {
+            instrument: instrument.clone(),
+            queue,
+            _subscription: subscription,
+        })
+    }
+
+    /// Calls callback with the status byte of the instrument, each time it requests service.
+    /// The subscription ends when the returned value is dropped.
+    ///
+    /// The callback runs on the I/O thread of the board, and must not block.
+    pub fn on_service_request(
+        &self,
+        instrument: &Instrument,
+        callback: impl FnMut(StatusByte) + Send + 'static,
+    ) -> Result<SrqSubscription, GpibError> {
+        self.register(instrument, Box::new(callback))
+    }
+
+    fn register(
+        &self,
+        instrument: &Instrument,
+        deliver: Box<dyn FnMut(StatusByte) + Send>,
+    ) -> Result<SrqSubscription, GpibError> {
+        if instrument.board != self.board {
+            return Err(GpibError::ValueError(format!(
+                "{} is not on {}.",
+                instrument, self.board
+            )));
+        }
+        let active = Arc::new(AtomicBool::new(true));
+        self.reactor.subscribe(Subscriber {
+            address: instrument.addr,
+            active: active.clone(),
+            deliver,
+        })?;
+        Ok(SrqSubscription {
+            instrument: instrument.clone(),
+            active,
+        })
+    }
+}
+
+impl fmt::Debug for SrqService {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SrqService({:?})", self.board)
+    }
+}
+
+/// Subscription to the service requests of an instrument, which ends when dropped
+pub struct SrqSubscription {
+    instrument: Instrument,
+    active: Arc<AtomicBool>,
+}
+
+impl Drop for SrqSubscription {
+    fn drop(&mut self) {
+        self.active.store(false, Ordering::Release);
+    }
+}
+
+impl fmt::Debug for SrqSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SrqSubscription({:?})", self.instrument)
+    }
+}
+
+struct Queue {
+    status_bytes: VecDeque<StatusByte>,
+    waker: Option<Waker>,
+}
+
+/// Status bytes of an instrument requesting service
+pub struct SrqStream {
+    instrument: Instrument,
+    queue: Arc<Mutex<Queue>>,
+    _subscription: SrqSubscription,
+}
+
+impl SrqStream {
+    /// Waits for the next service request, and returns the status byte. Returns None if the
+    /// I/O thread of the board stopped.
+    pub async fn next(&mut self) -> Option<StatusByte> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl Stream for SrqStream {
+    type Item = StatusByte;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<StatusByte>> {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(status_byte) = queue.status_bytes.pop_front() {
+            Poll::Ready(Some(status_byte))
+        } else if Arc::strong_count(&self.queue) == 1 {
+            // the subscriber was dropped by the I/O thread
+            Poll::Ready(None)
+        } else {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl fmt::Debug for SrqStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SrqStream({:?})", self.instrument)
+    }
+}
@@ -1,16 +1,18 @@
 #[cfg(feature = "linuxgpib")]
 use crate::lowlevel::utility::{AsyncIbsta, ThreadIbsta};
-use linux_gpib_sys::{
+use crate::sys::{
     ibsta_bit_numbers_ATN_NUM, ibsta_bit_numbers_CIC_NUM, ibsta_bit_numbers_CMPL_NUM,
     ibsta_bit_numbers_DCAS_NUM, ibsta_bit_numbers_DTAS_NUM, ibsta_bit_numbers_END_NUM,
     ibsta_bit_numbers_ERR_NUM, ibsta_bit_numbers_EVENT_NUM, ibsta_bit_numbers_LACS_NUM,
     ibsta_bit_numbers_LOK_NUM, ibsta_bit_numbers_REM_NUM, ibsta_bit_numbers_RQS_NUM,
     ibsta_bit_numbers_SPOLL_NUM, ibsta_bit_numbers_SRQI_NUM, ibsta_bit_numbers_TACS_NUM,
-    ibsta_bit_numbers_TIMO_NUM,
+    ibsta_bit_numbers_TIMO_NUM, ibsta_type, status_mask_type,
 };
 use std::default::Default;
 use std::fmt;
+use std::ops;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct IbStatus {
     pub dcas: bool,
     pub dtas: bool,
@@ -33,6 +35,7 @@ pub struct IbStatus {
 impl IbStatus {
     /// Get current value of from Linux-GPIB ibsta global variable.
     /// Use `current_thread_local_status` or `current_async_status` instead.
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
     pub unsafe fn current_global_status() -> IbStatus {
         #[cfg(feature = "linuxgpib")]
         return IbStatus::from_ibsta(unsafe { linux_gpib_sys::ibsta });
@@ -54,7 +57,7 @@ impl IbStatus {
     }
 
     /// Convert c_int status value to IbStatus
-    pub fn from_ibsta(ibsta: linux_gpib_sys::ibsta_type) -> IbStatus {
+    pub fn from_ibsta(ibsta: ibsta_type) -> IbStatus {
         let dcas = ((1 << ibsta_bit_numbers_DCAS_NUM) & ibsta) != 0;
         let dtas = ((1 << ibsta_bit_numbers_DTAS_NUM) & ibsta) != 0;
         let lacs = ((1 << ibsta_bit_numbers_LACS_NUM) & ibsta) != 0;
@@ -91,16 +94,16 @@ impl IbStatus {
         }
     }
 
-    pub fn as_status_mask(&self) -> linux_gpib_sys::status_mask_type {
-            #[cfg(feature = "linuxgpib")]
-            let res = self.as_ibsta();
-            #[cfg(feature = "nigpib")]
-            let res = self.as_ibsta().try_into().unwrap();
-            res
+    pub fn as_status_mask(&self) -> status_mask_type {
+        #[cfg(not(feature = "nigpib"))]
+        let res = self.as_ibsta();
+        #[cfg(feature = "nigpib")]
+        let res = self.as_ibsta().try_into().unwrap();
+        res
     }
 
     /// Convert IbStatus to Linux GPIB c_int status
-    pub fn as_ibsta(&self) -> linux_gpib_sys::ibsta_type {
+    pub fn as_ibsta(&self) -> ibsta_type {
         let mut ibsta = 0;
         if self.dcas {
             ibsta = ibsta | (1 << ibsta_bit_numbers_DCAS_NUM);
@@ -361,3 +364,429 @@ impl Default for IbStatus {
         }
     }
 }
+
+/// IEEE 488.2 status byte, as returned by a serial poll or `*STB?`
+///
+/// Bits 0 to 3 and 7 are device-specific (SCPI instruments use bit 2 for the error queue, bit 3
+/// for the questionable status and bit 7 for the operation status), and are kept as received.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct StatusByte(u8);
+
+impl StatusByte {
+    /// EAV: error or event queue not empty (SCPI)
+    pub const EAV: StatusByte = StatusByte(0x04);
+    /// QUES: questionable status summary (SCPI)
+    pub const QUES: StatusByte = StatusByte(0x08);
+    /// MAV: message available in the output queue
+    pub const MAV: StatusByte = StatusByte(0x10);
+    /// ESB: event status bit, an enabled bit of the standard event status register is set
+    pub const ESB: StatusByte = StatusByte(0x20);
+    /// RQS: the device requested service (serial poll), same bit as MSS
+    pub const RQS: StatusByte = StatusByte(0x40);
+    /// MSS: master summary status (`*STB?`), same bit as RQS
+    pub const MSS: StatusByte = StatusByte(0x40);
+    /// OPER: operation status summary (SCPI)
+    pub const OPER: StatusByte = StatusByte(0x80);
+
+    pub fn from_bits(bits: u8) -> Self {
+        StatusByte(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// True if all the bits set in `other` are set
+    pub fn contains(&self, other: StatusByte) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// True if at least one of the bits set in `other` is set
+    pub fn intersects(&self, other: StatusByte) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn eav(&self) -> bool {
+        self.contains(Self::EAV)
+    }
+
+    pub fn ques(&self) -> bool {
+        self.contains(Self::QUES)
+    }
+
+    pub fn mav(&self) -> bool {
+        self.contains(Self::MAV)
+    }
+
+    pub fn esb(&self) -> bool {
+        self.contains(Self::ESB)
+    }
+
+    pub fn rqs(&self) -> bool {
+        self.contains(Self::RQS)
+    }
+
+    pub fn mss(&self) -> bool {
+        self.contains(Self::MSS)
+    }
+
+    pub fn oper(&self) -> bool {
+        self.contains(Self::OPER)
+    }
+
+    fn with(self, flag: StatusByte, value: bool) -> Self {
+        if value {
+            StatusByte(self.0 | flag.0)
+        } else {
+            StatusByte(self.0 & !flag.0)
+        }
+    }
+
+    pub fn with_eav(self, eav: bool) -> Self {
+        self.with(Self::EAV, eav)
+    }
+
+    pub fn with_ques(self, ques: bool) -> Self {
+        self.with(Self::QUES, ques)
+    }
+
+    pub fn with_mav(self, mav: bool) -> Self {
+        self.with(Self::MAV, mav)
+    }
+
+    pub fn with_esb(self, esb: bool) -> Self {
+        self.with(Self::ESB, esb)
+    }
+
+    pub fn with_rqs(self, rqs: bool) -> Self {
+        self.with(Self::RQS, rqs)
+    }
+
+    pub fn with_mss(self, mss: bool) -> Self {
+        self.with(Self::MSS, mss)
+    }
+
+    pub fn with_oper(self, oper: bool) -> Self {
+        self.with(Self::OPER, oper)
+    }
+}
+
+impl From<u8> for StatusByte {
+    fn from(bits: u8) -> Self {
+        StatusByte(bits)
+    }
+}
+
+impl From<StatusByte> for u8 {
+    fn from(status_byte: StatusByte) -> Self {
+        status_byte.0
+    }
+}
+
+impl ops::BitOr for StatusByte {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        StatusByte(self.0 | other.0)
+    }
+}
+
+impl ops::BitAnd for StatusByte {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        StatusByte(self.0 & other.0)
+    }
+}
+
+impl fmt::Debug for StatusByte {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut description = String::new();
+        for bit in 0..2 {
+            if self.0 & (1 << bit) != 0 {
+                description.push_str(&format!("bit{} (device-specific) ", bit));
+            }
+        }
+        if self.eav() {
+            description.push_str("EAV (error or event queue not empty) ");
+        }
+        if self.ques() {
+            description.push_str("QUES (questionable status summary) ");
+        }
+        if self.mav() {
+            description.push_str("MAV (message available) ");
+        }
+        if self.esb() {
+            description.push_str("ESB (standard event status summary) ");
+        }
+        if self.rqs() {
+            description.push_str("RQS/MSS (device requests service) ");
+        }
+        if self.oper() {
+            description.push_str("OPER (operation status summary)");
+        }
+        if !description.is_empty() {
+            write!(f, "StatusByte({})", description.trim())
+        } else {
+            write!(f, "StatusByte(No flag set)")
+        }
+    }
+}
+
+impl fmt::Display for StatusByte {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut description = String::new();
+        for bit in 0..2 {
+            if self.0 & (1 << bit) != 0 {
+                description.push_str(&format!("bit{} ", bit));
+            }
+        }
+        if self.eav() {
+            description.push_str("EAV ");
+        }
+        if self.ques() {
+            description.push_str("QUES ");
+        }
+        if self.mav() {
+            description.push_str("MAV ");
+        }
+        if self.esb() {
+            description.push_str("ESB ");
+        }
+        if self.rqs() {
+            description.push_str("RQS ");
+        }
+        if self.oper() {
+            description.push_str("OPER");
+        }
+        if !description.is_empty() {
+            write!(f, "StatusByte({})", description.trim())
+        } else {
+            write!(f, "StatusByte(No flag set)")
+        }
+    }
+}
+
+/// IEEE 488.2 standard event status register, as returned by `*ESR?`, or written with `*ESE`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct StandardEventStatus(u8);
+
+impl StandardEventStatus {
+    /// OPC: operation complete, set by `*OPC`
+    pub const OPC: StandardEventStatus = StandardEventStatus(0x01);
+    /// RQC: request control
+    pub const RQC: StandardEventStatus = StandardEventStatus(0x02);
+    /// QYE: query error
+    pub const QYE: StandardEventStatus = StandardEventStatus(0x04);
+    /// DDE: device-dependent error
+    pub const DDE: StandardEventStatus = StandardEventStatus(0x08);
+    /// EXE: execution error
+    pub const EXE: StandardEventStatus = StandardEventStatus(0x10);
+    /// CME: command error
+    pub const CME: StandardEventStatus = StandardEventStatus(0x20);
+    /// URQ: user request
+    pub const URQ: StandardEventStatus = StandardEventStatus(0x40);
+    /// PON: power on
+    pub const PON: StandardEventStatus = StandardEventStatus(0x80);
+
+    pub fn from_bits(bits: u8) -> Self {
+        StandardEventStatus(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// True if all the bits set in `other` are set
+    pub fn contains(&self, other: StandardEventStatus) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// True if at least one of the bits set in `other` is set
+    pub fn intersects(&self, other: StandardEventStatus) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// True if one of the error bits (QYE, DDE, EXE, CME) is set
+    pub fn has_error(&self) -> bool {
+        self.intersects(Self::QYE | Self::DDE | Self::EXE | Self::CME)
+    }
+
+    pub fn opc(&self) -> bool {
+        self.contains(Self::OPC)
+    }
+
+    pub fn rqc(&self) -> bool {
+        self.contains(Self::RQC)
+    }
+
+    pub fn qye(&self) -> bool {
+        self.contains(Self::QYE)
+    }
+
+    pub fn dde(&self) -> bool {
+        self.contains(Self::DDE)
+    }
+
+    pub fn exe(&self) -> bool {
+        self.contains(Self::EXE)
+    }
+
+    pub fn cme(&self) -> bool {
+        self.contains(Self::CME)
+    }
+
+    pub fn urq(&self) -> bool {
+        self.contains(Self::URQ)
+    }
+
+    pub fn pon(&self) -> bool {
+        self.contains(Self::PON)
+    }
+
+    fn with(self, flag: StandardEventStatus, value: bool) -> Self {
+        if value {
+            StandardEventStatus(self.0 | flag.0)
+        } else {
+            StandardEventStatus(self.0 & !flag.0)
+        }
+    }
+
+    pub fn with_opc(self, opc: bool) -> Self {
+        self.with(Self::OPC, opc)
+    }
+
+    pub fn with_rqc(self, rqc: bool) -> Self {
+        self.with(Self::RQC, rqc)
+    }
+
+    pub fn with_qye(self, qye: bool) -> Self {
+        self.with(Self::QYE, qye)
+    }
+
+    pub fn with_dde(self, dde: bool) -> Self {
+        self.with(Self::DDE, dde)
+    }
+
+    pub fn with_exe(self, exe: bool) -> Self {
+        self.with(Self::EXE, exe)
+    }
+
+    pub fn with_cme(self, cme: bool) -> Self {
+        self.with(Self::CME, cme)
+    }
+
+    pub fn with_urq(self, urq: bool) -> Self {
+        self.with(Self::URQ, urq)
+    }
+
+    pub fn with_pon(self, pon: bool) -> Self {
+        self.with(Self::PON, pon)
+    }
+}
+
+impl From<u8> for StandardEventStatus {
+    fn from(bits: u8) -> Self {
+        StandardEventStatus(bits)
+    }
+}
+
+impl From<StandardEventStatus> for u8 {
+    fn from(status: StandardEventStatus) -> Self {
+        status.0
+    }
+}
+
+impl ops::BitOr for StandardEventStatus {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        StandardEventStatus(self.0 | other.0)
+    }
+}
+
+impl ops::BitAnd for StandardEventStatus {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        StandardEventStatus(self.0 & other.0)
+    }
+}
+
+impl fmt::Debug for StandardEventStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut description = String::new();
+        if self.opc() {
+            description.push_str("OPC (operation complete) ");
+        }
+        if self.rqc() {
+            description.push_str("RQC (request control) ");
+        }
+        if self.qye() {
+            description.push_str("QYE (query error) ");
+        }
+        if self.dde() {
+            description.push_str("DDE (device-dependent error) ");
+        }
+        if self.exe() {
+            description.push_str("EXE (execution error) ");
+        }
+        if self.cme() {
+            description.push_str("CME (command error) ");
+        }
+        if self.urq() {
+            description.push_str("URQ (user request) ");
+        }
+        if self.pon() {
+            description.push_str("PON (power on)");
+        }
+        if !description.is_empty() {
+            write!(f, "StandardEventStatus({})", description.trim())
+        } else {
+            write!(f, "StandardEventStatus(No flag set)")
+        }
+    }
+}
+
+impl fmt::Display for StandardEventStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut description = String::new();
+        if self.opc() {
+            description.push_str("OPC ");
+        }
+        if self.rqc() {
+            description.push_str("RQC ");
+        }
+        if self.qye() {
+            description.push_str("QYE ");
+        }
+        if self.dde() {
+            description.push_str("DDE ");
+        }
+        if self.exe() {
+            description.push_str("EXE ");
+        }
+        if self.cme() {
+            description.push_str("CME ");
+        }
+        if self.urq() {
+            description.push_str("URQ ");
+        }
+        if self.pon() {
+            description.push_str("PON");
+        }
+        if !description.is_empty() {
+            write!(f, "StandardEventStatus({})", description.trim())
+        } else {
+            write!(f, "StandardEventStatus(No flag set)")
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//!
+//! Byte stream adapter for `InstrumentHandle`, implementing the `AsyncRead` and `AsyncWrite`
+//! traits of tokio (feature `async-tokio`), and of `futures-io` (feature `async-smol`, for smol,
+//! async-std and the `futures` utilities).
+//!
+//! This allows to use the I/O utilities of the runtime (`BufReader::read_line`, `copy`,
+//! `tokio_util::codec::Framed`, ...) with GPIB instruments, as with TCP or serial instruments.
+//! Reads are done with `ibrda`/`ibwait` and writes with `ibwrta`/`ibwait`, so that the stream
+//! shares the cancel-safety of the asynchronous `InstrumentHandle` methods.
+//!
+//! The stream never reaches end-of-file. EOI only ends a read, and the message boundaries are
+//! expected to be given by the termination character, as with other byte streams. A timeout
+//! is reported as an `std::io::ErrorKind::TimedOut` error.
+//!
+//! GPIB is half-duplex: starting a write while a read is in progress aborts the read.
+//! Writes are not checked for instrument errors (see `Parameters::error_check`), since a message
+//! may be split across several writes.
+//!
+//! With tokio:
+//!
+//! ```rust
+//! use linux_gpib_rs::backend::sim::{Reply, SimulatedBus};
+//! use linux_gpib_rs::instrument::{Board, Parameters};
+//! use linux_gpib_rs::types::{PrimaryAddress, SecondaryAddress};
+//! use std::sync::Arc;
+//! # #[cfg(feature = "async-tokio")]
+//! use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+//!
+//! # #[cfg(not(feature = "async-tokio"))]
+//! # fn main() {}
+//! # #[cfg(feature = "async-tokio")]
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let bus = SimulatedBus::new().with_device(
+//!         0,
+//!         PrimaryAddress::new(5)?,
+//!         SecondaryAddress::default(),
+//!         |_: &[u8]| Some(Reply::from("SIM,DMM,0,1.0")),
+//!     );
+//!     let board = Board::with_backend(0, Arc::new(bus));
+//!     let handle = board.find_listeners()?[0].open(Parameters::default())?;
+//!     let mut stream = BufReader::new(handle.into_stream());
+//!     stream.write_all(b"*IDN?\n").await?;
+//!     let mut line = String::new();
+//!     stream.read_line(&mut line).await?;
+//!     assert_eq!(line, "SIM,DMM,0,1.0\n");
+//!     Ok(())
+//! }
+//! ```
+//!
+
+use crate::error::GpibError;
+use crate::instrument::InstrumentHandle;
+use crate::status::IbStatus;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+
+/// Number of bytes requested by each `ibrda`.
+const CHUNK_SIZE: usize = 1024;
+
+type IoFuture<T> = Pin<Box<dyn Future<Output = Result<T, GpibError>> + Send>>;
+
+/// `InstrumentHandle` as an asynchronous byte stream
+pub struct InstrumentStream {
+    handle: Arc<InstrumentHandle>,
+    read: Option<IoFuture<(IbStatus, Vec<u8>)>>,
+    received: Vec<u8>,
+    position: usize,
+    write: Option<IoFuture<usize>>,
+}
+
+impl InstrumentStream {
+    pub fn new(handle: InstrumentHandle) -> Self {
+        InstrumentStream {
+            handle: Arc::new(handle),
+            read: None,
+            received: Vec::new(),
+            position: 0,
+            write: None,
+        }
+    }
+
+    /// Underlying instrument handle
+    pub fn handle(&self) -> &InstrumentHandle {
+        &self.handle
+    }
+}
+
+impl InstrumentHandle {
+    /// Converts the handle into a byte stream
+    pub fn into_stream(self) -> InstrumentStream {
+        InstrumentStream::new(self)
+    }
+}
+
+impl InstrumentStream {
+    /// Reads from the instrument until some data is received.
+    fn poll_received(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.position >= self.received.len() {
+            let read = self.read.get_or_insert_with(|| {
+                let handle = self.handle.clone();
+                Box::pin(async move { handle.read_chunk(CHUNK_SIZE).await })
+            });
+            let result = ready!(read.as_mut().poll(cx));
+            self.read = None;
+            let (_status, data) = result?;
+            // an empty read is not end-of-file on GPIB, read again
+            self.received = data;
+            self.position = 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Takes at most count bytes of the received data.
+    fn take(&mut self, count: usize) -> &[u8] {
+        let count = count.min(self.received.len() - self.position);
+        let data = &self.received[self.position..self.position + count];
+        self.position += count;
+        data
+    }
+
+    fn poll_write_data(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.read.take().is_some() {
+            log::debug!("Read aborted by a write on {:?}", self.handle);
+        }
+        let write = self.write.get_or_insert_with(|| {
+            let handle = self.handle.clone();
+            let data = buf.to_vec();
+            Box::pin(async move {
+                handle.send(&data).await?;
+                Ok(data.len())
+            })
+        });
+        let result = ready!(write.as_mut().poll(cx));
+        self.write = None;
+        Poll::Ready(Ok(result?))
+    }
+
+    fn poll_flush_data(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(write) = self.write.as_mut() {
+            let result = ready!(write.as_mut().poll(cx));
+            self.write = None;
+            result?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async-tokio")]
+impl tokio::io::AsyncRead for InstrumentStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(this.poll_received(cx))?;
+        buf.put_slice(this.take(buf.remaining()));
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async-tokio")]
+impl tokio::io::AsyncWrite for InstrumentStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_data(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush_data(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush_data(cx)
+    }
+}
+
+#[cfg(feature = "async-smol")]
+impl futures_io::AsyncRead for InstrumentStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        ready!(this.poll_received(cx))?;
+        let data = this.take(buf.len());
+        buf[..data.len()].copy_from_slice(data);
+        Poll::Ready(Ok(data.len()))
+    }
+}
+
+#[cfg(feature = "async-smol")]
+impl futures_io::AsyncWrite for InstrumentStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_data(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush_data(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush_data(cx)
+    }
+}
+
+impl fmt::Debug for InstrumentStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InstrumentStream({:?})", self.handle)
+    }
+}
@@ -0,0 +1,51 @@
+//!
+//! Types and constants of the GPIB library used outside of the library calls.
+//!
+//! They come from `linux-gpib-sys` when a library is selected with the `linuxgpib` or `nigpib`
+//! feature. Otherwise they are defined here, with the Linux GPIB values, so that the crate builds
+//! without any GPIB library, e.g. to run application tests against the simulated backend.
+//!
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+
+#[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
+pub(crate) use linux_gpib_sys::{
+    Addr4882_t, NOADDR, STOPend, ibsta_bit_numbers_ATN_NUM, ibsta_bit_numbers_CIC_NUM,
+    ibsta_bit_numbers_CMPL_NUM, ibsta_bit_numbers_DCAS_NUM, ibsta_bit_numbers_DTAS_NUM,
+    ibsta_bit_numbers_END_NUM, ibsta_bit_numbers_ERR_NUM, ibsta_bit_numbers_EVENT_NUM,
+    ibsta_bit_numbers_LACS_NUM, ibsta_bit_numbers_LOK_NUM, ibsta_bit_numbers_REM_NUM,
+    ibsta_bit_numbers_RQS_NUM, ibsta_bit_numbers_SPOLL_NUM, ibsta_bit_numbers_SRQI_NUM,
+    ibsta_bit_numbers_TACS_NUM, ibsta_bit_numbers_TIMO_NUM, ibsta_type, status_mask_type,
+};
+
+#[cfg(not(any(feature = "linuxgpib", feature = "nigpib")))]
+pub(crate) use self::definitions::*;
+
+#[cfg(not(any(feature = "linuxgpib", feature = "nigpib")))]
+mod definitions {
+    use std::os::raw::{c_int, c_uint};
+
+    pub type Addr4882_t = u16;
+    pub type ibsta_type = c_int;
+    pub type status_mask_type = c_int;
+
+    pub const NOADDR: Addr4882_t = 0xffff;
+    pub const STOPend: c_int = 0x100;
+
+    pub const ibsta_bit_numbers_DCAS_NUM: c_uint = 0;
+    pub const ibsta_bit_numbers_DTAS_NUM: c_uint = 1;
+    pub const ibsta_bit_numbers_LACS_NUM: c_uint = 2;
+    pub const ibsta_bit_numbers_TACS_NUM: c_uint = 3;
+    pub const ibsta_bit_numbers_ATN_NUM: c_uint = 4;
+    pub const ibsta_bit_numbers_CIC_NUM: c_uint = 5;
+    pub const ibsta_bit_numbers_REM_NUM: c_uint = 6;
+    pub const ibsta_bit_numbers_LOK_NUM: c_uint = 7;
+    pub const ibsta_bit_numbers_CMPL_NUM: c_uint = 8;
+    pub const ibsta_bit_numbers_EVENT_NUM: c_uint = 9;
+    pub const ibsta_bit_numbers_SPOLL_NUM: c_uint = 10;
+    pub const ibsta_bit_numbers_RQS_NUM: c_uint = 11;
+    pub const ibsta_bit_numbers_SRQI_NUM: c_uint = 12;
+    pub const ibsta_bit_numbers_END_NUM: c_uint = 13;
+    pub const ibsta_bit_numbers_TIMO_NUM: c_uint = 14;
+    pub const ibsta_bit_numbers_ERR_NUM: c_uint = 15;
+}
@@ -458,6 +458,33 @@ impl IbTimeout {
         }
     }
 
+    pub(crate) fn from_timeout(value: c_int) -> Result<IbTimeout, GpibError> {
+        match value {
+            0 => Ok(IbTimeout::TNone),
+            1 => Ok(IbTimeout::T10us),
+            2 => Ok(IbTimeout::T30us),
+            3 => Ok(IbTimeout::T100us),
+            4 => Ok(IbTimeout::T300us),
+            5 => Ok(IbTimeout::T1ms),
+            6 => Ok(IbTimeout::T3ms),
+            7 => Ok(IbTimeout::T10ms),
+            8 => Ok(IbTimeout::T30ms),
+            9 => Ok(IbTimeout::T100ms),
+            10 => Ok(IbTimeout::T300ms),
+            11 => Ok(IbTimeout::T1s),
+            12 => Ok(IbTimeout::T3s),
+            13 => Ok(IbTimeout::T10s),
+            14 => Ok(IbTimeout::T30s),
+            15 => Ok(IbTimeout::T100s),
+            16 => Ok(IbTimeout::T300s),
+            17 => Ok(IbTimeout::T1000s),
+            other => Err(GpibError::ValueError(format!(
+                "Unexpected value ({}) for timeout.",
+                other,
+            ))),
+        }
+    }
+
     pub(crate) fn as_duration(&self) -> Duration {
         match self {
             IbTimeout::TNone => Duration::MAX,
@@ -513,7 +540,7 @@ impl IbTimeout {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct PrimaryAddress {
     pad: c_int,
 }
@@ -541,7 +568,7 @@ impl fmt::Display for PrimaryAddress {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct SecondaryAddress {
     sad: c_int,
 }
@@ -751,6 +778,7 @@ impl fmt::Debug for IbEvent {
 }
 
 impl IbEvent {
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
     pub(crate) fn from_value(value: c_short) -> Result<IbEvent, GpibError> {
         match value {
             0 => Ok(IbEvent::None),
@@ -829,6 +857,7 @@ pub enum IbOnline {
 }
 
 impl IbOnline {
+    #[cfg(any(feature = "linuxgpib", feature = "nigpib"))]
     pub(crate) fn as_online(&self) -> c_int {
         match self {
             IbOnline::Close => 0,
@@ -0,0 +1,165 @@
+//!
+//! Decoding of binary waveforms.
+//!
+//! The payload of an IEEE 488.2 block (see [`crate::block`]) is decoded into typed samples, with
+//! the byte order selected by the instrument (usually big-endian by default, as specified by
+//! IEEE 488.2). Raw samples can then be converted to engineering units with the scale and offset
+//! values of the waveform preamble.
+//!
+//! ```rust
+//! use linux_gpib_rs::waveform::{self, ByteOrder, Scaling};
+//!
+//! let payload = [0x00, 0x01, 0xff, 0xfe];
+//! let raw: Vec<i16> = waveform::decode(&payload, ByteOrder::BigEndian).unwrap();
+//! assert_eq!(raw, vec![1, -2]);
+//!
+//! // Keysight: (raw - YREFerence) * YINCrement + YORigin
+//! let volts = Scaling::new(0.5, 1.0, 0.0);
+//! let values = waveform::decode_scaled::<i16>(&payload, ByteOrder::BigEndian, &volts).unwrap();
+//! assert_eq!(values, vec![1.5, 0.0]);
+//! assert_eq!(Scaling::new(1e-3, 0.0, 0.0).axis(3), vec![0.0, 1e-3, 2e-3]);
+//! ```
+//!
+
+use crate::error::GpibError;
+use std::fmt;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Most significant byte first (IEEE 488.2 default)
+    #[default]
+    BigEndian,
+    /// Least significant byte first (often selected with a `SWAP` or `BORDER LSB` setting)
+    LittleEndian,
+}
+
+impl fmt::Display for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ByteOrder::BigEndian => write!(f, "BigEndian"),
+            ByteOrder::LittleEndian => write!(f, "LittleEndian"),
+        }
+    }
+}
+
+impl fmt::Debug for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ByteOrder({})", self)
+    }
+}
+
+/// Sample type of a binary waveform
+pub trait Sample: Copy {
+    /// Size of a sample in bytes
+    const SIZE: usize;
+
+    /// Decodes a sample from exactly `SIZE` bytes
+    fn from_bytes(bytes: &[u8], order: ByteOrder) -> Self;
+
+    fn as_f64(self) -> f64;
+}
+
+macro_rules! impl_sample {
+    ($($t:ty),*) => {
+        $(
+            impl Sample for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn from_bytes(bytes: &[u8], order: ByteOrder) -> Self {
+                    let mut array = [0u8; std::mem::size_of::<$t>()];
+                    array.copy_from_slice(bytes);
+                    match order {
+                        ByteOrder::BigEndian => <$t>::from_be_bytes(array),
+                        ByteOrder::LittleEndian => <$t>::from_le_bytes(array),
+                    }
+                }
+
+                fn as_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_sample!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+
+/// Decodes the payload into samples
+pub fn decode<T: Sample>(payload: &[u8], order: ByteOrder) -> Result<Vec<T>, GpibError> {
+    if !payload.len().is_multiple_of(T::SIZE) {
+        return Err(GpibError::ValueError(format!(
+            "Payload length ({} bytes) is not a multiple of the sample size ({} bytes).",
+            payload.len(),
+            T::SIZE
+        )));
+    }
+    Ok(payload
+        .chunks_exact(T::SIZE)
+        .map(|bytes| T::from_bytes(bytes, order))
+        .collect())
+}
+
+/// Decodes the payload into samples, and converts them to engineering units
+pub fn decode_scaled<T: Sample>(
+    payload: &[u8],
+    order: ByteOrder,
+    scaling: &Scaling,
+) -> Result<Vec<f64>, GpibError> {
+    Ok(decode::<T>(payload, order)?
+        .into_iter()
+        .map(|sample| scaling.apply(sample.as_f64()))
+        .collect())
+}
+
+/// Linear conversion from raw values: `(raw - reference) * increment + origin`
+///
+/// This covers the usual preambles, e.g. `YINCrement`, `YORigin` and `YREFerence` on Keysight
+/// oscilloscopes, or `YMULT`, `YZERO` and `YOFF` on Tektronix oscilloscopes.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Scaling {
+    pub increment: f64,
+    pub origin: f64,
+    pub reference: f64,
+}
+
+impl Scaling {
+    pub fn new(increment: f64, origin: f64, reference: f64) -> Self {
+        Scaling {
+            increment,
+            origin,
+            reference,
+        }
+    }
+
+    pub fn apply(&self, raw: f64) -> f64 {
+        (raw - self.reference) * self.increment + self.origin
+    }
+
+    /// Values of the axis for sample indices 0 to `length - 1` (e.g. time axis from the `X`
+    /// preamble values)
+    pub fn axis(&self, length: usize) -> Vec<f64> {
+        (0..length).map(|i| self.apply(i as f64)).collect()
+    }
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Scaling::new(1.0, 0.0, 0.0)
+    }
+}
+
+impl fmt::Display for Scaling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(raw - {}) * {} + {}",
+            self.reference, self.increment, self.origin
+        )
+    }
+}
+
+impl fmt::Debug for Scaling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Scaling({})", self)
+    }
+}